@@ -0,0 +1,117 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+/// Signature of [`Anneal::Custom`], taking `(start, end, pct)`.
+pub type AnnealFn = dyn Fn(f64, f64, f64) -> f64 + Send + Sync;
+
+/// How a value moves from its start to its end over a phase.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Anneal {
+    /// Half a cosine wave, PyTorch's `"cos"`.
+    #[default]
+    Cos,
+    /// A straight line, PyTorch's `"linear"`.
+    Linear,
+    /// Geometric interpolation, a straight line in log space. `start` and
+    /// `end` need the same sign and to be non-zero.
+    Exponential,
+    /// `end + (start - end) * (1 - pct)^power`. A power of 1 is linear,
+    /// higher powers drop faster early on.
+    Polynomial { power: f64 },
+    /// `end + (start - end) * (1 - sqrt(pct))`, the cooldown shape from
+    /// warmup-stable-decay schedules.
+    OneMinusSqrt,
+    /// Hold `start` for the whole phase.
+    Constant,
+    /// A user supplied `(start, end, pct)` function. It can't be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(Arc<AnnealFn>),
+}
+
+impl Anneal {
+    /// Wrap a `(start, end, pct)` function as a strategy.
+    pub fn custom(anneal: impl Fn(f64, f64, f64) -> f64 + Send + Sync + 'static) -> Self {
+        Anneal::Custom(Arc::new(anneal))
+    }
+
+    /// The value `pct` of the way from `start` to `end`, with `pct` in `0..=1`.
+    pub fn anneal(&self, start: f64, end: f64, pct: f64) -> f64 {
+        match self {
+            Anneal::Cos => {
+                let cos_out = (pct * PI).cos() + 1.;
+                end + (start - end) / 2. * cos_out
+            }
+            Anneal::Linear => (end - start) * pct + start,
+            Anneal::Exponential => start * (end / start).powf(pct),
+            Anneal::Polynomial { power } => end + (start - end) * (1. - pct).powf(*power),
+            Anneal::OneMinusSqrt => end + (start - end) * (1. - pct.sqrt()),
+            Anneal::Constant => start,
+            Anneal::Custom(anneal) => anneal(start, end, pct),
+        }
+    }
+}
+
+impl fmt::Debug for Anneal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anneal::Cos => f.write_str("Cos"),
+            Anneal::Linear => f.write_str("Linear"),
+            Anneal::Exponential => f.write_str("Exponential"),
+            Anneal::Polynomial { power } => {
+                f.debug_struct("Polynomial").field("power", power).finish()
+            }
+            Anneal::OneMinusSqrt => f.write_str("OneMinusSqrt"),
+            Anneal::Constant => f.write_str("Constant"),
+            Anneal::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Custom strategies are only equal to themselves.
+impl PartialEq for Anneal {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Anneal::Polynomial { power: a }, Anneal::Polynomial { power: b }) => a == b,
+            (Anneal::Custom(a), Anneal::Custom(b)) => Arc::ptr_eq(a, b),
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Anneal;
+
+    #[test]
+    fn anneal_endpoints_test() {
+        let strategies = [
+            Anneal::Cos,
+            Anneal::Linear,
+            Anneal::Exponential,
+            Anneal::Polynomial { power: 2. },
+            Anneal::OneMinusSqrt,
+            Anneal::custom(|start, end, pct| start + (end - start) * pct * pct),
+        ];
+
+        for anneal in strategies {
+            assert_eq!(anneal.anneal(1e-2, 1e-4, 0.), 1e-2);
+            assert!((anneal.anneal(1e-2, 1e-4, 1.) - 1e-4).abs() < 1e-12);
+        }
+
+        assert_eq!(Anneal::Constant.anneal(1e-2, 1e-4, 1.), 1e-2);
+    }
+
+    #[test]
+    fn anneal_midpoint_test() {
+        assert_eq!(Anneal::Linear.anneal(1., 0., 0.5), 0.5);
+        assert_eq!(Anneal::Exponential.anneal(1., 1e-2, 0.5), 0.1);
+        assert_eq!(Anneal::Polynomial { power: 2. }.anneal(1., 0., 0.5), 0.25);
+        assert_eq!(Anneal::OneMinusSqrt.anneal(1., 0., 0.25), 0.5);
+    }
+}
@@ -0,0 +1,518 @@
+//! Preview the lr and momentum of a schedule before launching a job.
+//!
+//! ```text
+//! candle-scheduler 'warmup(500) >> cosine(9500, lr=1e-3)' 10000 --format plot
+//! candle-scheduler scheduler.toml 10000 --format csv > lr.csv
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::process::ExitCode;
+
+use candle_scheduler::{Schedule, SchedulerConfig, SchedulerError};
+
+const USAGE: &str = "\
+Preview the lr and momentum of a schedule.
+
+Usage: candle-scheduler <SCHEDULE> <STEPS> [OPTIONS]
+
+Arguments:
+  <SCHEDULE>  A schedule expression, or a file with a config: .toml, .json, or
+              an expression in a file without an extension
+  <STEPS>     Preview steps 0 to STEPS
+
+Options:
+  -f, --format <FORMAT>  table, csv, json or plot [default: table]
+  -e, --every <N>        Only list every Nth step, and the last [default: 1]
+  -w, --width <COLUMNS>  Plot width [default: 72]
+  -H, --height <ROWS>    Plot height of each chart [default: 12]
+      --ascii            Plot with ASCII characters only
+  -h, --help             Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    Csv,
+    Json,
+    Plot,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    schedule: String,
+    steps: usize,
+    format: Format,
+    every: usize,
+    width: usize,
+    height: usize,
+    ascii: bool,
+}
+
+/// `None` when help was asked for.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut positional = Vec::new();
+    let mut format = Format::Table;
+    let mut every = 1;
+    let mut width = 72;
+    let mut height = 12;
+    let mut ascii = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{name} needs a value"))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--ascii" => ascii = true,
+            "-f" | "--format" => {
+                format = match value("--format")?.as_str() {
+                    "table" => Format::Table,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "plot" => Format::Plot,
+                    other => {
+                        return Err(format!(
+                            "unknown format `{other}`, expected table, csv, json or plot"
+                        ))
+                    }
+                }
+            }
+            "-e" | "--every" => every = positive("--every", &value("--every")?)?,
+            "-w" | "--width" => width = positive("--width", &value("--width")?)?,
+            "-H" | "--height" => height = positive("--height", &value("--height")?)?,
+            flag if flag.starts_with('-') && flag.len() > 1 && !is_number(flag) => {
+                return Err(format!("unknown option `{flag}`"))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let [schedule, steps]: [String; 2] = positional
+        .try_into()
+        .map_err(|_| "expected a schedule and a number of steps".to_string())?;
+    let steps = steps
+        .parse()
+        .map_err(|_| format!("expected a number of steps, got `{steps}`"))?;
+
+    Ok(Some(Options {
+        schedule,
+        steps,
+        format,
+        every,
+        width: width.max(2),
+        height,
+        ascii,
+    }))
+}
+
+fn positive(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) | Err(_) => Err(format!("{name} must be a positive number, got `{value}`")),
+        Ok(value) => Ok(value),
+    }
+}
+
+/// So a leading negative constant isn't taken for an option.
+fn is_number(arg: &str) -> bool {
+    arg.parse::<f64>().is_ok()
+}
+
+/// Read `schedule` as a file if there's one at that path, else as an
+/// expression.
+fn load(schedule: &str) -> Result<SchedulerConfig, String> {
+    let path = Path::new(schedule);
+    if !path.is_file() {
+        return parse_expression(schedule);
+    }
+
+    let source =
+        std::fs::read_to_string(path).map_err(|err| format!("couldn't read {schedule}: {err}"))?;
+    match path.extension() {
+        None => parse_expression(source.trim()),
+        Some(extension) if extension == "toml" => {
+            toml::from_str(&source).map_err(|err| format!("{schedule}: {err}"))
+        }
+        Some(extension) if extension == "json" => {
+            serde_json::from_str(&source).map_err(|err| format!("{schedule}: {err}"))
+        }
+        Some(extension) => Err(format!(
+            "{schedule}: unsupported config format `.{}`, use .toml, .json, or an \
+             expression in a file without an extension",
+            extension.to_string_lossy()
+        )),
+    }
+}
+
+/// Parse errors point at the offending part of a one line expression.
+fn parse_expression(source: &str) -> Result<SchedulerConfig, String> {
+    source.parse().map_err(|err| match err {
+        SchedulerError::Parse { message, span } if !source.contains('\n') => {
+            let start = source[..span.start].chars().count();
+            let len = source[span.clone()].chars().count().max(1);
+            format!(
+                "{message}\n  {source}\n  {}{}",
+                " ".repeat(start),
+                "^".repeat(len)
+            )
+        }
+        err => err.to_string(),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    step: usize,
+    lr: f64,
+    momentum: Option<f64>,
+}
+
+/// Every `every`th step from 0, and always the last one.
+fn sample(schedule: &dyn Schedule, steps: usize, every: usize) -> Vec<Point> {
+    let mut picked: Vec<usize> = (0..=steps).step_by(every).collect();
+    if picked.last() != Some(&steps) {
+        picked.push(steps);
+    }
+
+    picked
+        .into_iter()
+        .map(|step| Point {
+            step,
+            lr: schedule.lr_at(step),
+            momentum: schedule.momentum_at(step),
+        })
+        .collect()
+}
+
+fn table(points: &[Point]) -> String {
+    let step_width = points
+        .last()
+        .map_or(0, |point| point.step.to_string().len())
+        .max("step".len());
+    let momentum = points.iter().any(|point| point.momentum.is_some());
+
+    let mut out = format!("{:>step_width$}  {:>11}", "step", "lr");
+    if momentum {
+        out.push_str("  momentum");
+    }
+    out.push('\n');
+
+    for point in points {
+        write!(out, "{:>step_width$}  {:>11.4e}", point.step, point.lr).unwrap();
+        match point.momentum {
+            Some(value) => write!(out, "  {value:>8.4}").unwrap(),
+            None if momentum => write!(out, "  {:>8}", "-").unwrap(),
+            None => {}
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+fn csv(points: &[Point]) -> String {
+    let mut out = "step,lr,momentum\n".to_string();
+    for point in points {
+        let momentum = point.momentum.map(|value| value.to_string());
+        writeln!(
+            out,
+            "{},{},{}",
+            point.step,
+            point.lr,
+            momentum.unwrap_or_default()
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn json(points: &[Point]) -> String {
+    let number = |value: f64| match value.is_finite() {
+        true => value.to_string(),
+        false => "null".to_string(),
+    };
+
+    let rows: Vec<String> = points
+        .iter()
+        .map(|point| {
+            format!(
+                "  {{\"step\": {}, \"lr\": {}, \"momentum\": {}}}",
+                point.step,
+                number(point.lr),
+                point.momentum.map_or("null".to_string(), number)
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+/// The lr, and momentum when it's driven, as bar charts of `width` columns
+/// sampled evenly over the steps.
+fn plot(schedule: &dyn Schedule, steps: usize, width: usize, height: usize, ascii: bool) -> String {
+    let width = width.min(steps + 1).max(1);
+    let columns: Vec<usize> = (0..width)
+        .map(|column| match width {
+            1 => 0,
+            _ => column * steps / (width - 1),
+        })
+        .collect();
+
+    let lrs: Vec<f64> = columns.iter().map(|&step| schedule.lr_at(step)).collect();
+    let mut out = chart("lr", &lrs, steps, height, ascii);
+
+    let momentum: Option<Vec<f64>> = columns
+        .iter()
+        .map(|&step| schedule.momentum_at(step))
+        .collect();
+    if let Some(momentum) = momentum {
+        out.push('\n');
+        out.push_str(&chart("momentum", &momentum, steps, height, ascii));
+    }
+
+    out
+}
+
+fn chart(title: &str, values: &[f64], steps: usize, height: usize, ascii: bool) -> String {
+    let finite = values.iter().copied().filter(|value| value.is_finite());
+    let low = finite.clone().fold(f64::INFINITY, f64::min);
+    let high = finite.fold(f64::NEG_INFINITY, f64::max);
+
+    // Eighths of a row, at least one so the lowest values still show.
+    let resolution = height * 8;
+    let levels: Vec<usize> = values
+        .iter()
+        .map(|&value| match value.is_finite() {
+            false => 0,
+            true if high > low => {
+                1 + ((value - low) / (high - low) * (resolution - 1) as f64).round() as usize
+            }
+            true => resolution,
+        })
+        .collect();
+
+    let labels = [format!("{high:.3e}"), format!("{low:.3e}")];
+    let label_width = labels[0].len().max(labels[1].len());
+    let (tick, axis, corner, rule) = match ascii {
+        true => ('+', '|', '+', '-'),
+        false => ('┤', '│', '└', '─'),
+    };
+
+    let mut out = format!("{title}\n");
+    for row in (0..height).rev() {
+        let (label, edge) = match row {
+            row if row == height - 1 => (labels[0].as_str(), tick),
+            0 => (labels[1].as_str(), tick),
+            _ => ("", axis),
+        };
+        let bars: String = levels
+            .iter()
+            .map(|level| bar(level.saturating_sub(row * 8).min(8), ascii))
+            .collect();
+        let line = format!("{label:>label_width$} {edge}{bars}");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    let rule = rule.to_string().repeat(values.len());
+    writeln!(out, "{:label_width$} {corner}{rule}", "").unwrap();
+
+    let last = steps.to_string();
+    let gap = values.len().saturating_sub(1 + last.len());
+    writeln!(out, "{:label_width$}  0{:>gap$}{last}", "", "").unwrap();
+
+    out
+}
+
+/// A cell filled `eighths` of the way up.
+fn bar(eighths: usize, ascii: bool) -> char {
+    match ascii {
+        true => [' ', '.', '.', '.', ':', ':', ':', ':', '#'][eighths],
+        false => [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'][eighths],
+    }
+}
+
+fn run(args: impl IntoIterator<Item = String>) -> Result<String, String> {
+    let Some(options) = parse_args(args)? else {
+        return Ok(USAGE.to_string());
+    };
+
+    let scheduler = load(&options.schedule)?
+        .build()
+        .map_err(|err| err.to_string())?;
+    let schedule = scheduler
+        .schedule()
+        .ok_or("reduce_lr_on_plateau depends on the metrics reported, so it can't be previewed")?;
+
+    Ok(match options.format {
+        Format::Table => table(&sample(schedule, options.steps, options.every)),
+        Format::Csv => csv(&sample(schedule, options.steps, options.every)),
+        Format::Json => json(&sample(schedule, options.steps, options.every)),
+        Format::Plot => plot(
+            schedule,
+            options.steps,
+            options.width,
+            options.height,
+            options.ascii,
+        ),
+    })
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(output) => match io::stdout().write_all(output.as_bytes()) {
+            // Piping into `head` is fine.
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                eprintln!("error: {err}");
+                ExitCode::FAILURE
+            }
+            _ => ExitCode::SUCCESS,
+        },
+        Err(err) => {
+            eprintln!("error: {err}\n\nRun `candle-scheduler --help` for usage.");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_scheduler::{CosineAnnealing, OneCycle};
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        assert_eq!(
+            parse_args(args(&[
+                "cosine(10)",
+                "10",
+                "--format=csv",
+                "-e",
+                "2",
+                "--ascii"
+            ]))
+            .unwrap(),
+            Some(Options {
+                schedule: "cosine(10)".to_string(),
+                steps: 10,
+                format: Format::Csv,
+                every: 2,
+                width: 72,
+                height: 12,
+                ascii: true,
+            })
+        );
+        assert_eq!(parse_args(args(&["-h"])).unwrap(), None);
+        assert_eq!(
+            parse_args(args(&["-0.5", "3"])).unwrap().unwrap().schedule,
+            "-0.5"
+        );
+        assert!(parse_args(args(&["cosine(10)"])).is_err());
+        assert!(parse_args(args(&["cosine(10)", "10", "--every", "0"])).is_err());
+        assert!(parse_args(args(&["cosine(10)", "10", "--format", "svg"])).is_err());
+    }
+
+    #[test]
+    fn preview_output_test() {
+        let schedule = CosineAnnealing::new(1., 4, 0.);
+        let points = sample(&schedule, 4, 3);
+
+        assert_eq!(
+            points.iter().map(|point| point.step).collect::<Vec<_>>(),
+            [0, 3, 4]
+        );
+        assert_eq!(
+            table(&points),
+            "step           lr\n   \
+                0     1.0000e0\n   \
+                3    1.4645e-1\n   \
+                4     0.0000e0\n"
+        );
+        assert_eq!(
+            csv(&points),
+            "step,lr,momentum\n0,1,\n3,0.14644660940672627,\n4,0,\n"
+        );
+        assert_eq!(
+            json(&points[..1]),
+            "[\n  {\"step\": 0, \"lr\": 1, \"momentum\": null}\n]\n"
+        );
+    }
+
+    #[test]
+    fn preview_momentum_test() {
+        let schedule = OneCycle::builder(1.).total_steps(10).build().unwrap();
+        let output = table(&sample(&schedule, 10, 10));
+
+        assert_eq!(
+            output,
+            "step           lr  momentum\n   \
+                0    4.0000e-2    0.9500\n  \
+               10    4.0000e-6    0.9500\n"
+        );
+    }
+
+    #[test]
+    fn plot_test() {
+        let schedule = CosineAnnealing::new(1., 4, 0.);
+
+        assert_eq!(
+            plot(&schedule, 4, 72, 2, false),
+            "lr\n\
+             1.000e0 ┤█▆▁\n\
+             0.000e0 ┤███▃▁\n        \
+             └─────\n         \
+             0   4\n"
+        );
+        assert!(plot(&schedule, 4, 72, 2, true).contains("1.000e0 +#:."));
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert_eq!(
+            parse_expression("cosine(10, mn=0.1)").unwrap_err(),
+            "`cosine` has no argument `mn`\n  cosine(10, mn=0.1)\n             ^^"
+        );
+    }
+
+    #[test]
+    fn load_format_test() {
+        let dir = std::env::temp_dir().join(format!("candle-scheduler-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let yaml = dir.join("scheduler.yaml");
+        let expression = dir.join("scheduler");
+        std::fs::write(&yaml, "type: constant\nlr: 0.1\n").unwrap();
+        std::fs::write(&expression, "cosine(10)\n").unwrap();
+
+        let yaml = yaml.to_str().unwrap();
+        assert_eq!(
+            load(yaml).unwrap_err(),
+            format!(
+                "{yaml}: unsupported config format `.yaml`, use .toml, .json, or an \
+                 expression in a file without an extension"
+            )
+        );
+        assert_eq!(
+            load(expression.to_str().unwrap()).unwrap().to_string(),
+            "cosine(10)"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
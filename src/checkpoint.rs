@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use candle_nn::VarMap;
+
+use crate::lr_finder::{restore, snapshot};
+use crate::{SchedulerError, SchedulerState, StateDict};
+
+/// The safetensors metadata key the scheduler state is stored under.
+pub const SCHEDULER_STATE_KEY: &str = "candle_scheduler.state";
+
+fn checkpoint_error(err: impl std::fmt::Display) -> SchedulerError {
+    SchedulerError::Checkpoint(err.to_string())
+}
+
+/// Save the weights in `varmap` to a safetensors file at `path`, with the
+/// scheduler's state as JSON in the file's metadata header.
+pub fn save_checkpoint<S: StateDict, P: AsRef<Path>>(
+    varmap: &VarMap,
+    scheduler: &S,
+    path: P,
+) -> Result<(), SchedulerError> {
+    let state = serde_json::to_string(&scheduler.state_dict()).map_err(checkpoint_error)?;
+    let metadata = HashMap::from([(SCHEDULER_STATE_KEY.to_string(), state)]);
+
+    let data = varmap.data().lock().map_err(checkpoint_error)?;
+    let tensors = data.iter().map(|(name, var)| (name, var.as_tensor()));
+
+    safetensors::serialize_to_file(tensors, &Some(metadata), path.as_ref())
+        .map_err(checkpoint_error)
+}
+
+/// Largest header [`read_checkpoint_state`] accepts, the same limit as
+/// safetensors.
+const MAX_HEADER_SIZE: u64 = 100_000_000;
+
+/// The part of a safetensors header the scheduler state is in. The tensor
+/// entries next to it are ignored.
+#[derive(serde::Deserialize)]
+struct Header {
+    #[serde(rename = "__metadata__")]
+    metadata: Option<HashMap<String, String>>,
+}
+
+/// Read the scheduler state from a checkpoint written by [`save_checkpoint`]
+/// without touching any weights.
+///
+/// Only the header at the start of the file is read, not the weights after
+/// it.
+pub fn read_checkpoint_state<S: StateDict, P: AsRef<Path>>(
+    path: P,
+) -> Result<SchedulerState<S::Config>, SchedulerError> {
+    let mut file = File::open(path).map_err(checkpoint_error)?;
+
+    let mut len = [0; 8];
+    file.read_exact(&mut len).map_err(checkpoint_error)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_HEADER_SIZE {
+        return Err(checkpoint_error(format!(
+            "header of {len} bytes is larger than {MAX_HEADER_SIZE}"
+        )));
+    }
+
+    let mut header = vec![0; len as usize];
+    file.read_exact(&mut header).map_err(checkpoint_error)?;
+    let header = std::str::from_utf8(&header).map_err(checkpoint_error)?;
+    let header: Header = serde_json::from_str(header).map_err(checkpoint_error)?;
+
+    let state = header
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get(SCHEDULER_STATE_KEY))
+        .ok_or_else(|| checkpoint_error(format!("no {SCHEDULER_STATE_KEY} entry in metadata")))?;
+
+    serde_json::from_str(state).map_err(checkpoint_error)
+}
+
+/// Restore the weights in `varmap` and the scheduler's progress from a
+/// checkpoint written by [`save_checkpoint`].
+///
+/// The scheduler state is validated before any weights are loaded. If the
+/// weights then fail to load, both the weights loaded so far and the
+/// scheduler are put back where they were.
+pub fn load_checkpoint<S: StateDict, P: AsRef<Path>>(
+    varmap: &mut VarMap,
+    scheduler: &mut S,
+    path: P,
+) -> Result<(), SchedulerError> {
+    let state = read_checkpoint_state::<S, _>(path.as_ref())?;
+    let current = scheduler.state_dict();
+    scheduler.load_state_dict(state)?;
+
+    let weights = snapshot(varmap).map_err(checkpoint_error)?;
+    if let Err(err) = varmap.load(path) {
+        restore(varmap, &weights).map_err(checkpoint_error)?;
+        scheduler.load_state_dict(current)?;
+        return Err(checkpoint_error(err));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use candle_core::{DType, Device, Tensor};
+    use candle_nn::{AdamW, Init, Optimizer, ParamsAdamW, VarMap};
+
+    use crate::{
+        load_checkpoint, read_checkpoint_state, save_checkpoint, LrScheduler, OneCycle,
+        SchedulerError,
+    };
+
+    /// A file of its own for each test, so concurrent runs don't clash.
+    fn temp_path() -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        std::env::temp_dir().join(format!(
+            "candle_scheduler_checkpoint_{}_{}.safetensors",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    #[test]
+    fn checkpoint_round_trip_test() {
+        let path = temp_path();
+        let mut varmap = VarMap::new();
+        let weights = varmap
+            .get(2, "w", Init::Const(1.), DType::F64, &Device::Cpu)
+            .unwrap();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        for _i in 0..3 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        save_checkpoint(&varmap, &scheduler, &path).unwrap();
+
+        let changed = Tensor::new(&[5f64, 5.], &Device::Cpu).unwrap();
+        varmap.data().lock().unwrap()["w"].set(&changed).unwrap();
+
+        let mut resumed = OneCycle::new(1e-3, 0.9, 25., 10);
+        load_checkpoint(&mut varmap, &mut resumed, &path).unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+        assert_eq!(weights.to_vec1::<f64>().unwrap(), [1., 1.]);
+        assert_eq!(
+            read_checkpoint_state::<OneCycle, _>(&path)
+                .unwrap()
+                .step_num,
+            3
+        );
+
+        let mut other = OneCycle::new(1e-3, 0.9, 25., 20);
+
+        assert!(matches!(
+            load_checkpoint(&mut varmap, &mut other, &path),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_failed_weights_test() {
+        let path = temp_path();
+        let mut varmap = VarMap::new();
+        varmap
+            .get(2, "w", Init::Const(1.), DType::F64, &Device::Cpu)
+            .unwrap();
+        let weights = varmap.data().lock().unwrap()["w"].clone();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        for _i in 0..3 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        save_checkpoint(&varmap, &scheduler, &path).unwrap();
+
+        let changed = Tensor::new(&[5f64, 5.], &Device::Cpu).unwrap();
+        weights.set(&changed).unwrap();
+        // The checkpoint has no weights for `b`.
+        varmap
+            .get(2, "b", Init::Const(0.), DType::F64, &Device::Cpu)
+            .unwrap();
+        let mut resumed = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        assert!(matches!(
+            load_checkpoint(&mut varmap, &mut resumed, &path),
+            Err(SchedulerError::Checkpoint(_))
+        ));
+        assert_eq!(resumed.get_lr(), OneCycle::new(1e-3, 0.9, 25., 10).get_lr());
+        assert_eq!(weights.as_tensor().to_vec1::<f64>().unwrap(), [5., 5.]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use std::fmt;
+
+use crate::error::check_steps;
+use crate::{Hyperparams, LrScheduler, Schedule, SchedulerError, UnsupportedPolicy};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Runs schedules one after the other, switching at milestone steps, like
+/// PyTorch's `SequentialLR`.
+///
+/// Each schedule starts from its own step 0 at its milestone. Schedules are
+/// evaluated in closed form, so anything implementing [`Schedule`] can be
+/// used, including other combinators.
+///
+/// ```
+/// use candle_scheduler::{CosineAnnealing, LambdaLr, Sequential};
+///
+/// // Constant for 1000 steps, then cosine annealing.
+/// let scheduler = Sequential::new(
+///     vec![
+///         Box::new(LambdaLr::new(1e-3, |_step| 1.)),
+///         Box::new(CosineAnnealing::new(1e-3, 9000, 1e-6)),
+///     ],
+///     vec![1000],
+/// );
+/// ```
+pub struct Sequential {
+    schedules: Vec<Box<dyn Schedule + Send>>,
+    milestones: Vec<usize>,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl fmt::Debug for Sequential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sequential")
+            .field("schedules", &self.schedules.len())
+            .field("milestones", &self.milestones)
+            .field("step_num", &self.step_num)
+            .field("unsupported", &self.unsupported)
+            .finish()
+    }
+}
+
+impl Sequential {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`Sequential::try_new`].
+    pub fn new(schedules: Vec<Box<dyn Schedule + Send>>, milestones: Vec<usize>) -> Self {
+        Self::try_new(schedules, milestones)
+            .unwrap_or_else(|err| panic!("invalid Sequential: {err}"))
+    }
+
+    /// Requires at least one schedule, one milestone fewer than schedules,
+    /// and strictly increasing positive milestones.
+    pub fn try_new(
+        schedules: Vec<Box<dyn Schedule + Send>>,
+        milestones: Vec<usize>,
+    ) -> Result<Self, SchedulerError> {
+        if schedules.is_empty() {
+            return Err(SchedulerError::Missing { name: "schedules" });
+        }
+        if milestones.len() + 1 != schedules.len() {
+            return Err(SchedulerError::MilestoneCount {
+                milestones: milestones.len(),
+                schedules: schedules.len(),
+            });
+        }
+        if let Some(&first) = milestones.first() {
+            check_steps("milestones", first)?;
+        }
+        if milestones.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(SchedulerError::Unsorted { name: "milestones" });
+        }
+
+        Ok(Sequential {
+            schedules,
+            milestones,
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn milestones(&self) -> &[usize] {
+        &self.milestones
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+
+    /// The schedule running at `step` and the step within it.
+    fn schedule_at(&self, step: usize) -> (&dyn Schedule, usize) {
+        let index = self
+            .milestones
+            .partition_point(|&milestone| milestone <= step);
+        let start = match index {
+            0 => 0,
+            index => self.milestones[index - 1],
+        };
+
+        (self.schedules[index].as_ref(), step - start)
+    }
+}
+
+impl Schedule for Sequential {
+    fn lr_at(&self, step: usize) -> f64 {
+        let (schedule, step) = self.schedule_at(step);
+        schedule.lr_at(step)
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        let (schedule, step) = self.schedule_at(step);
+        schedule.momentum_at(step)
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for Sequential {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+/// The configuration saved with a [`Sequential`] state. The schedules
+/// themselves aren't saved, so they have to be the same on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SequentialConfig {
+    pub milestones: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for Sequential {
+    type Config = SequentialConfig;
+
+    fn state_dict(&self) -> SchedulerState<SequentialConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: SequentialConfig {
+                milestones: self.milestones.clone(),
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<SequentialConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+/// Multiplies schedules together at every step, like PyTorch's
+/// `ChainedScheduler` applied to chainable schedules.
+///
+/// The lr is the product of every schedule's lr at the same step, so all but
+/// one schedule are usually built with an lr of 1 to act as factors. Momentum
+/// comes from the first schedule that drives it.
+///
+/// ```
+/// use candle_scheduler::{Chained, ExponentialLr, StepLr};
+///
+/// // Step decay with a slow exponential decay on top.
+/// let scheduler = Chained::new(vec![
+///     Box::new(StepLr::new(1e-3, 30, 0.1)),
+///     Box::new(ExponentialLr::new(1., 0.99)),
+/// ]);
+/// ```
+pub struct Chained {
+    schedules: Vec<Box<dyn Schedule + Send>>,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl fmt::Debug for Chained {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chained")
+            .field("schedules", &self.schedules.len())
+            .field("step_num", &self.step_num)
+            .field("unsupported", &self.unsupported)
+            .finish()
+    }
+}
+
+impl Chained {
+    /// # Panics
+    ///
+    /// If `schedules` is empty.
+    pub fn new(schedules: Vec<Box<dyn Schedule + Send>>) -> Self {
+        Self::try_new(schedules).unwrap_or_else(|err| panic!("invalid Chained: {err}"))
+    }
+
+    /// Requires at least one schedule.
+    pub fn try_new(schedules: Vec<Box<dyn Schedule + Send>>) -> Result<Self, SchedulerError> {
+        if schedules.is_empty() {
+            return Err(SchedulerError::Missing { name: "schedules" });
+        }
+
+        Ok(Chained {
+            schedules,
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+}
+
+impl Schedule for Chained {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.schedules
+            .iter()
+            .fold(1., |lr, schedule| lr * schedule.lr_at(step))
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        self.schedules
+            .iter()
+            .find_map(|schedule| schedule.momentum_at(step))
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for Chained {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+/// The configuration saved with a [`Chained`] state. The schedules
+/// themselves aren't saved, so they have to be the same on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChainedConfig {
+    pub schedules: usize,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for Chained {
+    type Config = ChainedConfig;
+
+    fn state_dict(&self) -> SchedulerState<ChainedConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: ChainedConfig {
+                schedules: self.schedules.len(),
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<ChainedConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    use crate::{
+        Chained, CosineAnnealing, ExponentialLr, LambdaLr, LrScheduler, OneCycle, Schedule,
+        SchedulerError, Sequential, StepLr, Warmup,
+    };
+
+    #[test]
+    fn sequential_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Sequential::new(
+            vec![
+                Box::new(LambdaLr::new(1e-4, |_step| 1.)),
+                Box::new(CosineAnnealing::new(1e-3, 10, 0.)),
+            ],
+            vec![3],
+        );
+
+        for _i in 0..2 {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), 1e-4);
+        }
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), 1e-3);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.0009755282581475768);
+        assert_eq!(scheduler.lr_at(8), 0.0005);
+    }
+
+    #[test]
+    fn sequential_nested_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let one_cycle = OneCycle::new(1e-3, 0.95, 25., 10);
+        let momentum = one_cycle.momentum_at(1);
+        let mut scheduler = Sequential::new(
+            vec![
+                Box::new(Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 2)),
+                Box::new(one_cycle),
+            ],
+            vec![5],
+        );
+
+        for _i in 0..6 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(Some(opt.params().beta1), momentum);
+        assert_eq!(scheduler.momentum_at(0), None);
+    }
+
+    #[test]
+    fn chained_test() {
+        let step_lr = StepLr::new(1e-3, 3, 0.1);
+        let exponential = ExponentialLr::new(1., 0.9);
+        let expected: Vec<f64> = (0..8)
+            .map(|step| step_lr.lr_at(step) * exponential.lr_at(step))
+            .collect();
+        let scheduler = Chained::new(vec![Box::new(step_lr), Box::new(exponential)]);
+
+        assert_eq!(scheduler.lr_at(0), 0.001);
+        assert_eq!(scheduler.lr_at(2), 0.0008100000000000001);
+        assert_eq!(scheduler.lr_at(3), 7.290000000000001e-5);
+
+        for (step, lr) in expected.into_iter().enumerate() {
+            assert_eq!(scheduler.lr_at(step), lr);
+        }
+    }
+
+    #[test]
+    fn compose_invalid_test() {
+        let cosine = || Box::new(CosineAnnealing::new(1e-3, 10, 0.));
+
+        assert_eq!(
+            Sequential::try_new(vec![cosine(), cosine()], vec![]).unwrap_err(),
+            SchedulerError::MilestoneCount {
+                milestones: 0,
+                schedules: 2
+            }
+        );
+        assert_eq!(
+            Sequential::try_new(vec![cosine(), cosine(), cosine()], vec![5, 5]).unwrap_err(),
+            SchedulerError::Unsorted { name: "milestones" }
+        );
+        assert_eq!(
+            Sequential::try_new(vec![cosine(), cosine()], vec![0]).unwrap_err(),
+            SchedulerError::ZeroSteps { name: "milestones" }
+        );
+        assert_eq!(
+            Chained::try_new(vec![]).unwrap_err(),
+            SchedulerError::Missing { name: "schedules" }
+        );
+    }
+}
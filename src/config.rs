@@ -0,0 +1,1034 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::{
+    get_scheduler, Anneal, Chained, Constant, CosineAnnealing, CosineAnnealingWarmRestarts,
+    CyclicLr, CyclicMode, ExponentialLr, HfScheduler, Hyperparams, InverseSqrt, LrScheduler,
+    Metric, MultiStepLr, OneCycle, PhaseSchedule, PhaseSpec, PlateauMode, PolynomialLr,
+    ReduceLrOnPlateau, Reduction, Schedule, SchedulerError, SchedulerKwargs, SchedulerType,
+    Sequential, StepLr, ThresholdMode, UnsupportedPolicy, Warmup, Wsd,
+};
+#[cfg(feature = "serde")]
+use crate::{ReduceLrOnPlateauCheckpoint, SchedulerState, StateDict};
+
+/// A scheduler described as data, so it can be loaded from a TOML or JSON
+/// file.
+///
+/// Required fields match each scheduler's constructor, and optional fields
+/// default like its builder. Schedules defined by closures, such as
+/// [`LambdaLr`](crate::LambdaLr), can't be described.
+///
+/// ```toml
+/// type = "sequential"
+/// milestones = [10000]
+///
+/// [[schedules]]
+/// type = "warmup"
+/// warmup_steps = 500
+/// inner = { type = "cosine_annealing", lr = 1e-3, max_step = 9500, eta_min = 1e-5 }
+///
+/// [[schedules]]
+/// type = "exponential_lr"
+/// lr = 1e-5
+/// gamma = 0.999
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)
+)]
+pub enum SchedulerConfig {
+    Constant {
+        lr: f64,
+    },
+    OneCycle {
+        max_lr: f64,
+        /// Or `epochs * steps_per_epoch`.
+        total_steps: Option<usize>,
+        epochs: Option<usize>,
+        steps_per_epoch: Option<usize>,
+        pct_start: Option<f64>,
+        anneal_strategy: Option<Anneal>,
+        /// `(index, anneal)` pairs, see [`OneCycleBuilder::phase_anneal`](crate::OneCycleBuilder::phase_anneal).
+        phase_anneals: Option<Vec<(usize, Anneal)>>,
+        cycle_momentum: Option<bool>,
+        base_momentum: Option<f64>,
+        max_momentum: Option<f64>,
+        div_factor: Option<f64>,
+        final_div_factor: Option<f64>,
+        three_phase: Option<bool>,
+    },
+    CosineAnnealing {
+        lr: f64,
+        max_step: usize,
+        eta_min: f64,
+    },
+    CosineAnnealingWarmRestarts {
+        lr: f64,
+        t_0: usize,
+        t_mult: usize,
+        eta_min: f64,
+        cycle_decay: Option<f64>,
+    },
+    StepLr {
+        lr: f64,
+        step_size: usize,
+        gamma: f64,
+        steps_per_epoch: Option<usize>,
+    },
+    MultiStepLr {
+        lr: f64,
+        milestones: Vec<usize>,
+        gamma: f64,
+        steps_per_epoch: Option<usize>,
+    },
+    ExponentialLr {
+        lr: f64,
+        gamma: f64,
+    },
+    PolynomialLr {
+        lr: f64,
+        end_lr: f64,
+        total_steps: usize,
+        power: f64,
+    },
+    CyclicLr {
+        base_lr: f64,
+        max_lr: f64,
+        step_size_up: Option<usize>,
+        step_size_down: Option<usize>,
+        mode: Option<CyclicMode>,
+        cycle_momentum: Option<bool>,
+        base_momentum: Option<f64>,
+        max_momentum: Option<f64>,
+    },
+    InverseSqrt {
+        lr: f64,
+        d_model: Option<usize>,
+        warmup_steps: Option<usize>,
+        linear_warmup: Option<bool>,
+        timescale: Option<usize>,
+        /// `(cooldown_steps, total_steps)`.
+        cooldown: Option<(usize, usize)>,
+    },
+    Wsd {
+        lr: f64,
+        min_lr: Option<f64>,
+        warmup_steps: Option<usize>,
+        decay: Option<Anneal>,
+        /// `(start, steps)`.
+        cooldown: Option<(usize, usize)>,
+    },
+    Phases {
+        total_steps: Option<usize>,
+        phases: Vec<PhaseSpec>,
+    },
+    ReduceLrOnPlateau {
+        lr: f64,
+        mode: Option<PlateauMode>,
+        factor: Option<f64>,
+        patience: Option<usize>,
+        threshold: Option<f64>,
+        threshold_mode: Option<ThresholdMode>,
+        cooldown: Option<usize>,
+        min_lr: Option<f64>,
+        eps: Option<f64>,
+    },
+    /// A HuggingFace named schedule, see [`get_scheduler`].
+    Hf {
+        name: SchedulerType,
+        lr: f64,
+        warmup_steps: Option<usize>,
+        total_steps: Option<usize>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        kwargs: SchedulerKwargs,
+    },
+    Warmup {
+        warmup_steps: usize,
+        start_factor: Option<f64>,
+        anneal: Option<Anneal>,
+        momentum: Option<(f64, f64)>,
+        inner: Box<SchedulerConfig>,
+    },
+    Sequential {
+        schedules: Vec<SchedulerConfig>,
+        milestones: Vec<usize>,
+    },
+    Chained {
+        schedules: Vec<SchedulerConfig>,
+    },
+}
+
+impl SchedulerConfig {
+    /// Build the scheduler. Errors in a nested config are wrapped in
+    /// [`SchedulerError::Config`] with the path to it, e.g.
+    /// `schedules[1].inner`.
+    pub fn build(&self) -> Result<BoxedScheduler, SchedulerError> {
+        Ok(BoxedScheduler {
+            kind: self.build_kind()?,
+            config: self.clone(),
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+
+    /// Build just the closed-form schedule, e.g. to preview it or nest it in
+    /// another combinator. Fails for `reduce_lr_on_plateau`.
+    pub fn build_schedule(&self) -> Result<Box<dyn Schedule + Send>, SchedulerError> {
+        match self.build_kind()? {
+            Kind::Schedule(schedule) => Ok(schedule),
+            Kind::Plateau(_) => Err(SchedulerError::NotClosedForm {
+                name: "reduce_lr_on_plateau",
+            }),
+        }
+    }
+
+    fn build_kind(&self) -> Result<Kind, SchedulerError> {
+        let schedule: Box<dyn Schedule + Send> = match self {
+            SchedulerConfig::Constant { lr } => Box::new(Constant::try_new(*lr)?),
+            SchedulerConfig::OneCycle {
+                max_lr,
+                total_steps,
+                epochs,
+                steps_per_epoch,
+                pct_start,
+                anneal_strategy,
+                phase_anneals,
+                cycle_momentum,
+                base_momentum,
+                max_momentum,
+                div_factor,
+                final_div_factor,
+                three_phase,
+            } => {
+                let mut builder = OneCycle::builder(*max_lr);
+                if let Some(total_steps) = total_steps {
+                    builder = builder.total_steps(*total_steps);
+                }
+                match (epochs, steps_per_epoch) {
+                    (Some(epochs), Some(steps_per_epoch)) => {
+                        builder = builder.epochs(*epochs, *steps_per_epoch)
+                    }
+                    (Some(_), None) => {
+                        return Err(SchedulerError::Missing {
+                            name: "steps_per_epoch",
+                        })
+                    }
+                    (None, Some(_)) => return Err(SchedulerError::Missing { name: "epochs" }),
+                    (None, None) => {}
+                }
+                if let Some(pct_start) = pct_start {
+                    builder = builder.pct_start(*pct_start);
+                }
+                if let Some(anneal_strategy) = anneal_strategy {
+                    builder = builder.anneal_strategy(anneal_strategy.clone());
+                }
+                for (index, anneal) in phase_anneals.iter().flatten() {
+                    builder = builder.phase_anneal(*index, anneal.clone());
+                }
+                if let Some(cycle_momentum) = cycle_momentum {
+                    builder = builder.cycle_momentum(*cycle_momentum);
+                }
+                if let Some(base_momentum) = base_momentum {
+                    builder = builder.base_momentum(*base_momentum);
+                }
+                if let Some(max_momentum) = max_momentum {
+                    builder = builder.max_momentum(*max_momentum);
+                }
+                if let Some(div_factor) = div_factor {
+                    builder = builder.div_factor(*div_factor);
+                }
+                if let Some(final_div_factor) = final_div_factor {
+                    builder = builder.final_div_factor(*final_div_factor);
+                }
+                if let Some(three_phase) = three_phase {
+                    builder = builder.three_phase(*three_phase);
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::CosineAnnealing {
+                lr,
+                max_step,
+                eta_min,
+            } => Box::new(CosineAnnealing::try_new(*lr, *max_step, *eta_min)?),
+            SchedulerConfig::CosineAnnealingWarmRestarts {
+                lr,
+                t_0,
+                t_mult,
+                eta_min,
+                cycle_decay,
+            } => {
+                let mut scheduler =
+                    CosineAnnealingWarmRestarts::try_new(*lr, *t_0, *t_mult, *eta_min)?;
+                if let Some(cycle_decay) = cycle_decay {
+                    scheduler = scheduler.with_cycle_decay(*cycle_decay)?;
+                }
+                Box::new(scheduler)
+            }
+            SchedulerConfig::StepLr {
+                lr,
+                step_size,
+                gamma,
+                steps_per_epoch,
+            } => {
+                let mut scheduler = StepLr::try_new(*lr, *step_size, *gamma)?;
+                if let Some(steps_per_epoch) = steps_per_epoch {
+                    scheduler = scheduler.with_steps_per_epoch(*steps_per_epoch)?;
+                }
+                Box::new(scheduler)
+            }
+            SchedulerConfig::MultiStepLr {
+                lr,
+                milestones,
+                gamma,
+                steps_per_epoch,
+            } => {
+                let mut scheduler = MultiStepLr::try_new(*lr, milestones.clone(), *gamma)?;
+                if let Some(steps_per_epoch) = steps_per_epoch {
+                    scheduler = scheduler.with_steps_per_epoch(*steps_per_epoch)?;
+                }
+                Box::new(scheduler)
+            }
+            SchedulerConfig::ExponentialLr { lr, gamma } => {
+                Box::new(ExponentialLr::try_new(*lr, *gamma)?)
+            }
+            SchedulerConfig::PolynomialLr {
+                lr,
+                end_lr,
+                total_steps,
+                power,
+            } => Box::new(PolynomialLr::try_new(*lr, *end_lr, *total_steps, *power)?),
+            SchedulerConfig::CyclicLr {
+                base_lr,
+                max_lr,
+                step_size_up,
+                step_size_down,
+                mode,
+                cycle_momentum,
+                base_momentum,
+                max_momentum,
+            } => {
+                let mut builder = CyclicLr::builder(*base_lr, *max_lr);
+                if let Some(step_size_up) = step_size_up {
+                    builder = builder.step_size_up(*step_size_up);
+                }
+                if let Some(step_size_down) = step_size_down {
+                    builder = builder.step_size_down(*step_size_down);
+                }
+                if let Some(mode) = mode {
+                    builder = builder.mode(mode.clone());
+                }
+                if let Some(cycle_momentum) = cycle_momentum {
+                    builder = builder.cycle_momentum(*cycle_momentum);
+                }
+                if let Some(base_momentum) = base_momentum {
+                    builder = builder.base_momentum(*base_momentum);
+                }
+                if let Some(max_momentum) = max_momentum {
+                    builder = builder.max_momentum(*max_momentum);
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::InverseSqrt {
+                lr,
+                d_model,
+                warmup_steps,
+                linear_warmup,
+                timescale,
+                cooldown,
+            } => {
+                let mut builder = InverseSqrt::builder(*lr);
+                if let Some(d_model) = d_model {
+                    builder = builder.d_model(*d_model);
+                }
+                if let Some(warmup_steps) = warmup_steps {
+                    builder = builder.warmup_steps(*warmup_steps);
+                }
+                if let Some(linear_warmup) = linear_warmup {
+                    builder = builder.linear_warmup(*linear_warmup);
+                }
+                if let Some(timescale) = timescale {
+                    builder = builder.timescale(*timescale);
+                }
+                if let Some((cooldown_steps, total_steps)) = cooldown {
+                    builder = builder.cooldown(*cooldown_steps, *total_steps);
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::Wsd {
+                lr,
+                min_lr,
+                warmup_steps,
+                decay,
+                cooldown,
+            } => {
+                let mut builder = Wsd::builder(*lr);
+                if let Some(min_lr) = min_lr {
+                    builder = builder.min_lr(*min_lr);
+                }
+                if let Some(warmup_steps) = warmup_steps {
+                    builder = builder.warmup_steps(*warmup_steps);
+                }
+                if let Some(decay) = decay {
+                    builder = builder.decay(decay.clone());
+                }
+                if let Some((start, steps)) = cooldown {
+                    builder = builder.cooldown(*start, *steps);
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::Phases {
+                total_steps,
+                phases,
+            } => {
+                let mut builder = PhaseSchedule::builder();
+                if let Some(total_steps) = total_steps {
+                    builder = builder.total_steps(*total_steps);
+                }
+                for phase in phases {
+                    builder = builder.phase(phase.clone());
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::ReduceLrOnPlateau {
+                lr,
+                mode,
+                factor,
+                patience,
+                threshold,
+                threshold_mode,
+                cooldown,
+                min_lr,
+                eps,
+            } => {
+                let mut builder = ReduceLrOnPlateau::builder(*lr);
+                if let Some(mode) = mode {
+                    builder = builder.mode(*mode);
+                }
+                if let Some(factor) = factor {
+                    builder = builder.factor(*factor);
+                }
+                if let Some(patience) = patience {
+                    builder = builder.patience(*patience);
+                }
+                if let Some(threshold) = threshold {
+                    builder = builder.threshold(*threshold);
+                }
+                if let Some(threshold_mode) = threshold_mode {
+                    builder = builder.threshold_mode(*threshold_mode);
+                }
+                if let Some(cooldown) = cooldown {
+                    builder = builder.cooldown(*cooldown);
+                }
+                if let Some(min_lr) = min_lr {
+                    builder = builder.min_lr(*min_lr);
+                }
+                if let Some(eps) = eps {
+                    builder = builder.eps(*eps);
+                }
+                return Ok(Kind::Plateau(builder.build()?));
+            }
+            SchedulerConfig::Hf {
+                name,
+                lr,
+                warmup_steps,
+                total_steps,
+                kwargs,
+            } => match get_scheduler(
+                name.as_str(),
+                *lr,
+                warmup_steps.unwrap_or(0),
+                *total_steps,
+                kwargs,
+            )? {
+                HfScheduler::Lambda(scheduler) => Box::new(scheduler),
+                HfScheduler::ReduceLrOnPlateau(scheduler) => return Ok(Kind::Plateau(scheduler)),
+            },
+            SchedulerConfig::Warmup {
+                warmup_steps,
+                start_factor,
+                anneal,
+                momentum,
+                inner,
+            } => {
+                let inner = nested("inner", inner.build_schedule())?;
+                let mut builder = Warmup::builder(inner, *warmup_steps);
+                if let Some(start_factor) = start_factor {
+                    builder = builder.start_factor(*start_factor);
+                }
+                if let Some(anneal) = anneal {
+                    builder = builder.anneal(anneal.clone());
+                }
+                if let Some((start, end)) = momentum {
+                    builder = builder.momentum(*start, *end);
+                }
+                Box::new(builder.build()?)
+            }
+            SchedulerConfig::Sequential {
+                schedules,
+                milestones,
+            } => Box::new(Sequential::try_new(
+                build_all(schedules)?,
+                milestones.clone(),
+            )?),
+            SchedulerConfig::Chained { schedules } => {
+                Box::new(Chained::try_new(build_all(schedules)?)?)
+            }
+        };
+
+        Ok(Kind::Schedule(schedule))
+    }
+}
+
+fn build_all(
+    schedules: &[SchedulerConfig],
+) -> Result<Vec<Box<dyn Schedule + Send>>, SchedulerError> {
+    schedules
+        .iter()
+        .enumerate()
+        .map(|(index, schedule)| nested(&format!("schedules[{index}]"), schedule.build_schedule()))
+        .collect()
+}
+
+/// A scheduler that can be described by the [`SchedulerConfig`] building
+/// the same schedule. Such schedulers display as its schedule expression.
+pub trait ToConfig {
+    fn to_config(&self) -> SchedulerConfig;
+}
+
+/// `None` when `value` is the default, to keep configs described from a
+/// scheduler short.
+pub(crate) fn non_default<T: PartialEq>(value: T, default: T) -> Option<T> {
+    (value != default).then_some(value)
+}
+
+/// Prefix the path of an error from a nested config with `field`.
+fn nested<T>(field: &str, result: Result<T, SchedulerError>) -> Result<T, SchedulerError> {
+    result.map_err(|err| match err {
+        SchedulerError::Config { path, err } => SchedulerError::Config {
+            path: format!("{field}.{path}"),
+            err,
+        },
+        err => SchedulerError::Config {
+            path: field.to_string(),
+            err: Box::new(err),
+        },
+    })
+}
+
+enum Kind {
+    Schedule(Box<dyn Schedule + Send>),
+    Plateau(ReduceLrOnPlateau),
+}
+
+/// A scheduler built from a [`SchedulerConfig`].
+///
+/// Every config builds to this one type, so a training loop can step it
+/// without knowing which scheduler the config chose. Metrics can always be
+/// passed to [`BoxedScheduler::report`]; schedules other than
+/// `reduce_lr_on_plateau` ignore them.
+pub struct BoxedScheduler {
+    kind: Kind,
+    config: SchedulerConfig,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl fmt::Debug for BoxedScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedScheduler")
+            .field("config", &self.config)
+            .field("step_num", &self.step_num)
+            .field("unsupported", &self.unsupported)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ToConfig for BoxedScheduler {
+    fn to_config(&self) -> SchedulerConfig {
+        self.config.clone()
+    }
+}
+
+/// Formats the config it was built from as a schedule expression.
+impl fmt::Display for BoxedScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.config.fmt(f)
+    }
+}
+
+impl BoxedScheduler {
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// The closed-form schedule, unless this is `reduce_lr_on_plateau`.
+    pub fn schedule(&self) -> Option<&(dyn Schedule + Send)> {
+        match &self.kind {
+            Kind::Schedule(schedule) => Some(schedule.as_ref()),
+            Kind::Plateau(_) => None,
+        }
+    }
+
+    /// The metric-driven scheduler, if this is `reduce_lr_on_plateau`.
+    pub fn plateau(&self) -> Option<&ReduceLrOnPlateau> {
+        match &self.kind {
+            Kind::Schedule(_) => None,
+            Kind::Plateau(plateau) => Some(plateau),
+        }
+    }
+
+    /// Report a metric to `reduce_lr_on_plateau`. Any other schedule ignores
+    /// it and returns `None`.
+    pub fn report<O: Optimizer>(
+        &mut self,
+        optimizer: &mut O,
+        metric: impl Metric,
+    ) -> Result<Option<Reduction>, SchedulerError> {
+        match &mut self.kind {
+            Kind::Schedule(_) => Ok(None),
+            Kind::Plateau(plateau) => plateau.report(optimizer, metric),
+        }
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        match &self.kind {
+            Kind::Schedule(schedule) => schedule.lr_at(self.step_num),
+            Kind::Plateau(plateau) => plateau.get_lr(),
+        }
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        match &self.kind {
+            Kind::Schedule(schedule) => schedule.momentum_at(self.step_num),
+            Kind::Plateau(_) => None,
+        }
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for BoxedScheduler {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        if let Kind::Plateau(plateau) = &mut self.kind {
+            return plateau.step(optimizer);
+        }
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+
+        if let Kind::Plateau(plateau) = &mut self.kind {
+            LrScheduler::<O>::reset(plateau);
+        }
+    }
+}
+
+/// What's saved with a [`BoxedScheduler`] state: the config it was built
+/// from, and the progress of `reduce_lr_on_plateau`, which depends on the
+/// metrics reported. Only the config has to match on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BoxedSchedulerCheckpoint {
+    pub config: SchedulerConfig,
+    pub plateau: Option<ReduceLrOnPlateauCheckpoint>,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for BoxedScheduler {
+    type Config = BoxedSchedulerCheckpoint;
+
+    fn state_dict(&self) -> SchedulerState<BoxedSchedulerCheckpoint> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: BoxedSchedulerCheckpoint {
+                config: self.config.clone(),
+                plateau: self.plateau().map(|plateau| plateau.state_dict().config),
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<BoxedSchedulerCheckpoint>,
+    ) -> Result<(), SchedulerError> {
+        if state.config.config != self.config {
+            return Err(SchedulerError::StateMismatch {
+                expected: format!("{:?}", self.config),
+                found: format!("{:?}", state.config.config),
+            });
+        }
+
+        if let (Kind::Plateau(plateau), Some(checkpoint)) = (&mut self.kind, state.config.plateau) {
+            plateau.load_state_dict(SchedulerState {
+                step_num: state.step_num,
+                lr: state.lr,
+                momentum: state.momentum,
+                config: checkpoint,
+            })?;
+        }
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    use crate::{
+        Anneal, CosineAnnealing, ExponentialLr, LrScheduler, OneCycle, Schedule, SchedulerConfig,
+        SchedulerError, SchedulerKwargs, SchedulerType, Sequential, Warmup,
+    };
+    #[cfg(feature = "serde")]
+    use crate::{CyclicMode, Duration, PhaseSpec, StateDict};
+
+    fn cosine(max_step: usize) -> SchedulerConfig {
+        SchedulerConfig::CosineAnnealing {
+            lr: 1e-3,
+            max_step,
+            eta_min: 1e-5,
+        }
+    }
+
+    #[test]
+    fn config_nested_test() {
+        let config = SchedulerConfig::Sequential {
+            schedules: vec![
+                SchedulerConfig::Warmup {
+                    warmup_steps: 5,
+                    start_factor: None,
+                    anneal: Some(Anneal::Cos),
+                    momentum: None,
+                    inner: Box::new(cosine(15)),
+                },
+                SchedulerConfig::ExponentialLr {
+                    lr: 1e-5,
+                    gamma: 0.9,
+                },
+            ],
+            milestones: vec![20],
+        };
+        let expected = Sequential::new(
+            vec![
+                Box::new(
+                    Warmup::builder(CosineAnnealing::new(1e-3, 15, 1e-5), 5)
+                        .anneal(Anneal::Cos)
+                        .build()
+                        .unwrap(),
+                ),
+                Box::new(ExponentialLr::new(1e-5, 0.9)),
+            ],
+            vec![20],
+        );
+
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = config.build().unwrap();
+        let schedule = scheduler.schedule().unwrap();
+
+        for step in 0..30 {
+            assert_eq!(schedule.lr_at(step), expected.lr_at(step));
+        }
+
+        for _i in 0..3 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(opt.learning_rate(), expected.lr_at(3));
+        assert_eq!(scheduler.report(&mut opt, 1.).unwrap(), None);
+    }
+
+    #[test]
+    fn config_momentum_test() {
+        let config = SchedulerConfig::OneCycle {
+            max_lr: 1e-3,
+            total_steps: Some(10),
+            epochs: None,
+            steps_per_epoch: None,
+            pct_start: None,
+            anneal_strategy: None,
+            phase_anneals: None,
+            cycle_momentum: None,
+            base_momentum: None,
+            max_momentum: Some(0.9),
+            div_factor: None,
+            final_div_factor: None,
+            three_phase: None,
+        };
+        let expected = OneCycle::builder(1e-3)
+            .total_steps(10)
+            .max_momentum(0.9)
+            .build()
+            .unwrap();
+
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = config.build().unwrap();
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), expected.lr_at(1));
+        assert_eq!(Some(opt.params().beta1), expected.momentum_at(1));
+    }
+
+    #[test]
+    fn config_plateau_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = SchedulerConfig::Hf {
+            name: SchedulerType::ReduceLrOnPlateau,
+            lr: 0.1,
+            warmup_steps: None,
+            total_steps: None,
+            kwargs: SchedulerKwargs {
+                patience: Some(0),
+                ..Default::default()
+            },
+        }
+        .build()
+        .unwrap();
+
+        assert!(scheduler.schedule().is_none());
+
+        scheduler.report(&mut opt, 1.).unwrap();
+        let reduction = scheduler.report(&mut opt, 1.).unwrap().unwrap();
+
+        assert_eq!(reduction.new_lr, 0.010000000000000002);
+        assert_eq!(scheduler.get_lr(), 0.010000000000000002);
+    }
+
+    #[test]
+    fn config_error_path_test() {
+        let config = SchedulerConfig::Sequential {
+            schedules: vec![
+                cosine(10),
+                SchedulerConfig::Warmup {
+                    warmup_steps: 5,
+                    start_factor: None,
+                    anneal: None,
+                    momentum: None,
+                    inner: Box::new(cosine(0)),
+                },
+            ],
+            milestones: vec![10],
+        };
+        let err = config.build().unwrap_err();
+
+        assert_eq!(
+            err,
+            SchedulerError::Config {
+                path: "schedules[1].inner".to_string(),
+                err: Box::new(SchedulerError::ZeroSteps { name: "max_step" }),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "schedules[1].inner: max_step must be at least 1"
+        );
+
+        let config = SchedulerConfig::Chained {
+            schedules: vec![SchedulerConfig::ReduceLrOnPlateau {
+                lr: 0.1,
+                mode: None,
+                factor: None,
+                patience: None,
+                threshold: None,
+                threshold_mode: None,
+                cooldown: None,
+                min_lr: None,
+                eps: None,
+            }],
+        };
+
+        assert_eq!(
+            config.build().unwrap_err(),
+            SchedulerError::Config {
+                path: "schedules[0]".to_string(),
+                err: Box::new(SchedulerError::NotClosedForm {
+                    name: "reduce_lr_on_plateau"
+                }),
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = cosine(10).build().unwrap();
+
+        for _i in 0..4 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let mut resumed = cosine(10).build().unwrap();
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+
+        let mut other = cosine(20).build().unwrap();
+        assert!(matches!(
+            other.load_state_dict(scheduler.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    fn warmup_cosine() -> SchedulerConfig {
+        SchedulerConfig::Warmup {
+            warmup_steps: 500,
+            start_factor: None,
+            anneal: None,
+            momentum: None,
+            inner: Box::new(cosine(9500)),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_deserialize_test() {
+        // The example from the README.
+        let toml = r#"
+            type = "warmup"
+            warmup_steps = 500
+            inner = { type = "cosine_annealing", lr = 1e-3, max_step = 9500, eta_min = 1e-5 }
+        "#;
+        let json = r#"{
+            "type": "warmup",
+            "warmup_steps": 500,
+            "inner": { "type": "cosine_annealing", "lr": 1e-3, "max_step": 9500, "eta_min": 1e-5 }
+        }"#;
+
+        assert_eq!(
+            toml::from_str::<SchedulerConfig>(toml).unwrap(),
+            warmup_cosine()
+        );
+        assert_eq!(
+            serde_json::from_str::<SchedulerConfig>(json).unwrap(),
+            warmup_cosine()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_unknown_field_test() {
+        let toml = r#"
+            type = "warmup"
+            warmup_steps = 500
+            inner = { type = "cosine_annealing", lr = 1e-3, max_steps = 9500, eta_min = 1e-5 }
+        "#;
+        let err = toml::from_str::<SchedulerConfig>(toml).unwrap_err();
+
+        assert!(
+            err.to_string().contains("unknown field `max_steps`"),
+            "{err}"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_phases_deserialize_test() {
+        let toml = r#"
+            type = "phases"
+
+            [[phases]]
+            duration = { steps = 100 }
+            lr = [0.0, 1e-3]
+            momentum = [0.95, 0.85]
+        "#;
+
+        assert_eq!(
+            toml::from_str::<SchedulerConfig>(toml).unwrap(),
+            SchedulerConfig::Phases {
+                total_steps: None,
+                phases: vec![PhaseSpec::steps(100, 0., 1e-3).momentum(0.95, 0.85)],
+            }
+        );
+
+        let misspelled = toml.replace("momentum", "momentun");
+        let err = toml::from_str::<SchedulerConfig>(&misspelled).unwrap_err();
+
+        assert!(
+            err.to_string().contains("unknown field `momentun`"),
+            "{err}"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_serde_round_trip_test() {
+        let config = SchedulerConfig::Sequential {
+            schedules: vec![
+                SchedulerConfig::Warmup {
+                    warmup_steps: 500,
+                    start_factor: Some(0.1),
+                    anneal: Some(Anneal::Linear),
+                    momentum: Some((0.85, 0.95)),
+                    inner: Box::new(cosine(9500)),
+                },
+                SchedulerConfig::CyclicLr {
+                    base_lr: 1e-4,
+                    max_lr: 1e-3,
+                    step_size_up: Some(200),
+                    step_size_down: None,
+                    mode: Some(CyclicMode::ExpRange { gamma: 0.99 }),
+                    cycle_momentum: Some(false),
+                    base_momentum: None,
+                    max_momentum: None,
+                },
+                SchedulerConfig::Phases {
+                    total_steps: Some(1000),
+                    phases: vec![
+                        PhaseSpec::constant(Duration::Fraction(0.5), 1e-4),
+                        PhaseSpec::steps(500, 1e-4, 1e-6).anneal(Anneal::Polynomial { power: 2. }),
+                    ],
+                },
+            ],
+            milestones: vec![10_000, 12_000],
+        };
+        let json = serde_json::to_string(&config).unwrap();
+
+        assert!(json.contains(r#""anneal":"linear""#), "{json}");
+        assert!(
+            json.contains(r#""mode":{"exp_range":{"gamma":0.99}}"#),
+            "{json}"
+        );
+        assert!(json.contains(r#""duration":{"fraction":0.5}"#), "{json}");
+        assert_eq!(
+            serde_json::from_str::<SchedulerConfig>(&json).unwrap(),
+            config
+        );
+        assert_eq!(
+            toml::from_str::<SchedulerConfig>(&toml::to_string(&config).unwrap()).unwrap(),
+            config
+        );
+    }
+}
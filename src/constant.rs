@@ -0,0 +1,133 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::error::check_finite;
+use crate::{LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Holds the lr at `lr` for every step.
+///
+/// Mostly useful inside combinators, e.g. as a constant factor of a
+/// [`Chained`](crate::Chained) schedule. Unlike PyTorch's `ConstantLR`, it
+/// doesn't switch to another lr after some steps; use a
+/// [`Sequential`](crate::Sequential) for that.
+#[derive(Debug)]
+pub struct Constant {
+    lr: f64,
+    step_num: usize,
+}
+
+impl Constant {
+    /// # Panics
+    ///
+    /// If `lr` isn't finite.
+    pub fn new(lr: f64) -> Self {
+        Self::try_new(lr).unwrap_or_else(|err| panic!("invalid Constant: {err}"))
+    }
+
+    /// Requires a finite `lr`.
+    pub fn try_new(lr: f64) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+
+        Ok(Constant { lr, step_num: 0 })
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr
+    }
+}
+
+impl Schedule for Constant {
+    fn lr_at(&self, _step: usize) -> f64 {
+        self.lr
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for Constant {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.lr);
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for Constant {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::Constant { lr: self.lr }
+    }
+}
+
+/// Formats as a schedule expression, which for a constant is just its lr.
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`Constant`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConstantConfig {
+    pub lr: f64,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for Constant {
+    type Config = ConstantConfig;
+
+    fn state_dict(&self) -> SchedulerState<ConstantConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.lr,
+            momentum: None,
+            config: ConstantConfig { lr: self.lr },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<ConstantConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    use crate::{Constant, LrScheduler, Schedule, SchedulerError};
+
+    #[test]
+    fn constant_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = Constant::new(1e-3);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), 1e-3);
+        assert_eq!(scheduler.lr_at(1_000_000), 1e-3);
+        assert!(matches!(
+            Constant::try_new(f64::NAN),
+            Err(SchedulerError::NonFinite { name: "lr", .. })
+        ));
+    }
+}
@@ -0,0 +1,730 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_order, check_positive, check_range, check_steps};
+use crate::{LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+#[derive(Debug)]
+pub struct CosineAnnealing {
+    base_lr: f64,
+    eta_min: f64,
+    max_step: usize,
+    step_num: usize,
+}
+
+impl CosineAnnealing {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`CosineAnnealing::try_new`].
+    pub fn new(lr: f64, max_step: usize, eta_min: f64) -> Self {
+        Self::try_new(lr, max_step, eta_min)
+            .unwrap_or_else(|err| panic!("invalid CosineAnnealing: {err}"))
+    }
+
+    /// Requires finite `0 <= eta_min <= lr` and a positive `max_step`.
+    pub fn try_new(lr: f64, max_step: usize, eta_min: f64) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_range("eta_min", eta_min, 0., f64::INFINITY)?;
+        check_order(("eta_min", eta_min), ("lr", lr))?;
+        check_steps("max_step", max_step)?;
+
+        Ok(CosineAnnealing {
+            base_lr: lr,
+            eta_min,
+            max_step,
+            step_num: 0,
+        })
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+}
+
+impl Schedule for CosineAnnealing {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.eta_min
+            + (self.base_lr - self.eta_min) * (1. + (PI * step as f64 / self.max_step as f64).cos())
+                / 2.
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for CosineAnnealing {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for CosineAnnealing {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::CosineAnnealing {
+            lr: self.base_lr,
+            max_step: self.max_step,
+            eta_min: self.eta_min,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `cosine(10000, min=1e-6, lr=0.001)`.
+impl fmt::Display for CosineAnnealing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`CosineAnnealing`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CosineAnnealingConfig {
+    pub base_lr: f64,
+    pub eta_min: f64,
+    pub max_step: usize,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for CosineAnnealing {
+    type Config = CosineAnnealingConfig;
+
+    fn state_dict(&self) -> SchedulerState<CosineAnnealingConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: CosineAnnealingConfig {
+                base_lr: self.base_lr,
+                eta_min: self.eta_min,
+                max_step: self.max_step,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<CosineAnnealingConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+/// Where a [`CosineAnnealingWarmRestarts`] is within its cycles.
+#[derive(Debug, Clone, Copy)]
+struct CyclePosition {
+    cycle: usize,
+    t_cur: f64,
+    t_i: f64,
+}
+
+/// Passed to [`CosineAnnealingWarmRestarts::on_restart`] callbacks when a
+/// new cycle begins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Restart {
+    /// Index of the cycle that's starting, the first restart is cycle 1.
+    pub cycle: usize,
+    /// The step the cycle starts on.
+    pub step_num: usize,
+    /// Length of the new cycle in steps.
+    pub cycle_len: usize,
+    /// The lr the new cycle starts from.
+    pub max_lr: f64,
+}
+
+type RestartCallback = Box<dyn FnMut(&Restart) + Send>;
+
+/// Cosine annealing with warm restarts, SGDR (Loshchilov & Hutter, 2016),
+/// matching PyTorch's `CosineAnnealingWarmRestarts`.
+///
+/// The first cycle lasts `t_0` steps and each one after is `t_mult` times
+/// longer. Each cycle starts from the base lr scaled by `cycle_decay` once
+/// per previous cycle, and anneals down to `eta_min`.
+pub struct CosineAnnealingWarmRestarts {
+    base_lr: f64,
+    eta_min: f64,
+    t_0: usize,
+    t_mult: usize,
+    cycle_decay: f64,
+    step_num: usize,
+    /// Set when positioned with a fractional epoch by `step_epoch`, and
+    /// advanced by `step` from then on, with where it falls in the cycles.
+    epoch: Option<(f64, CyclePosition)>,
+    on_restart: Vec<RestartCallback>,
+}
+
+impl fmt::Debug for CosineAnnealingWarmRestarts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CosineAnnealingWarmRestarts")
+            .field("base_lr", &self.base_lr)
+            .field("eta_min", &self.eta_min)
+            .field("t_0", &self.t_0)
+            .field("t_mult", &self.t_mult)
+            .field("cycle_decay", &self.cycle_decay)
+            .field("step_num", &self.step_num)
+            .field("epoch", &self.epoch.map(|(epoch, _)| epoch))
+            .finish_non_exhaustive()
+    }
+}
+
+impl CosineAnnealingWarmRestarts {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`CosineAnnealingWarmRestarts::try_new`].
+    pub fn new(lr: f64, t_0: usize, t_mult: usize, eta_min: f64) -> Self {
+        Self::try_new(lr, t_0, t_mult, eta_min)
+            .unwrap_or_else(|err| panic!("invalid CosineAnnealingWarmRestarts: {err}"))
+    }
+
+    /// Requires finite `0 <= eta_min <= lr`, and positive `t_0` and `t_mult`.
+    pub fn try_new(
+        lr: f64,
+        t_0: usize,
+        t_mult: usize,
+        eta_min: f64,
+    ) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_range("eta_min", eta_min, 0., f64::INFINITY)?;
+        check_order(("eta_min", eta_min), ("lr", lr))?;
+        check_steps("t_0", t_0)?;
+        check_steps("t_mult", t_mult)?;
+
+        Ok(CosineAnnealingWarmRestarts {
+            base_lr: lr,
+            eta_min,
+            t_0,
+            t_mult,
+            cycle_decay: 1.,
+            step_num: 0,
+            epoch: None,
+            on_restart: Vec::new(),
+        })
+    }
+
+    /// Scale the starting lr of each cycle by `cycle_decay` relative to the
+    /// one before, in `(0, 1]`. Defaults to 1, no decay.
+    pub fn with_cycle_decay(mut self, cycle_decay: f64) -> Result<Self, SchedulerError> {
+        check_positive("cycle_decay", cycle_decay)?;
+        self.cycle_decay = check_range("cycle_decay", cycle_decay, 0., 1.)?;
+        Ok(self)
+    }
+
+    /// Call `callback` whenever stepping starts a new cycle.
+    pub fn on_restart(mut self, callback: impl FnMut(&Restart) + Send + 'static) -> Self {
+        self.on_restart.push(Box::new(callback));
+        self
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at_position(&self.current_position())
+    }
+
+    /// The lr at a possibly fractional `epoch`, like passing `epoch` to
+    /// PyTorch's `step`. Fails if the epoch's cycle is too long to count in
+    /// steps.
+    pub fn lr_at_epoch(&self, epoch: f64) -> Result<f64, SchedulerError> {
+        Ok(self.lr_at_position(&self.position_at_epoch(epoch)?))
+    }
+
+    /// Move to a possibly fractional `epoch`, e.g. `epoch + i / iters` to
+    /// anneal within an epoch, and update the optimizer. Plain `step` calls
+    /// then advance the epoch by 1, keeping the fraction, like PyTorch.
+    /// Fails if the epoch's cycle is too long to count in steps.
+    pub fn step_epoch<O: Optimizer>(
+        &mut self,
+        optimizer: &mut O,
+        epoch: f64,
+    ) -> Result<(), SchedulerError> {
+        let epoch = check_range("epoch", epoch, 0., f64::INFINITY)?;
+        let position = self.position_at_epoch(epoch)?;
+        let cycle = self.current_position().cycle;
+
+        self.step_num = epoch.floor() as usize;
+        self.epoch = Some((epoch, position));
+        self.restart_if_new_cycle(cycle);
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn current_position(&self) -> CyclePosition {
+        match self.epoch {
+            Some((_, position)) => position,
+            None => self.position_at(self.step_num),
+        }
+    }
+
+    fn position_at(&self, step: usize) -> CyclePosition {
+        if self.t_mult == 1 {
+            return CyclePosition {
+                cycle: step / self.t_0,
+                t_cur: (step % self.t_0) as f64,
+                t_i: self.t_0 as f64,
+            };
+        }
+
+        let mut cycle = 0;
+        let mut t_cur = step;
+        let mut t_i = self.t_0;
+
+        while t_cur >= t_i {
+            t_cur -= t_i;
+            t_i = t_i.saturating_mul(self.t_mult);
+            cycle += 1;
+        }
+
+        CyclePosition {
+            cycle,
+            t_cur: t_cur as f64,
+            t_i: t_i as f64,
+        }
+    }
+
+    /// PyTorch's closed form for fractional epochs, failing where the cycle
+    /// lengths overflow a `usize`.
+    fn position_at_epoch(&self, epoch: f64) -> Result<CyclePosition, SchedulerError> {
+        let overflow = || SchedulerError::StepOverflow { name: "epoch" };
+        let t_0 = self.t_0 as f64;
+
+        if epoch >= usize::MAX as f64 {
+            return Err(overflow());
+        }
+
+        if epoch < t_0 {
+            return Ok(CyclePosition {
+                cycle: 0,
+                t_cur: epoch,
+                t_i: t_0,
+            });
+        }
+
+        if self.t_mult == 1 {
+            return Ok(CyclePosition {
+                cycle: (epoch / t_0).floor() as usize,
+                t_cur: epoch % t_0,
+                t_i: t_0,
+            });
+        }
+
+        let t_mult = self.t_mult as f64;
+        let n = ((epoch / t_0 * (t_mult - 1.) + 1.).ln() / t_mult.ln()) as u32;
+        let t_mult_n = self.t_mult.checked_pow(n).ok_or_else(overflow)?;
+        // The cycles before this one are shorter than it in total.
+        let t_i = self.t_0.checked_mul(t_mult_n).ok_or_else(overflow)?;
+
+        Ok(CyclePosition {
+            cycle: n as usize,
+            t_cur: epoch - (self.t_0 * (t_mult_n - 1)) as f64 / (self.t_mult - 1) as f64,
+            t_i: t_i as f64,
+        })
+    }
+
+    fn cycle_max_lr(&self, cycle: usize) -> f64 {
+        self.base_lr * self.cycle_decay.powi(cycle as i32)
+    }
+
+    fn lr_at_position(&self, position: &CyclePosition) -> f64 {
+        let base_lr = self.cycle_max_lr(position.cycle);
+
+        self.eta_min
+            + (base_lr - self.eta_min) * (1. + (PI * position.t_cur / position.t_i).cos()) / 2.
+    }
+
+    fn restart_if_new_cycle(&mut self, previous_cycle: usize) {
+        let position = self.current_position();
+
+        if position.cycle <= previous_cycle {
+            return;
+        }
+
+        let restart = Restart {
+            cycle: position.cycle,
+            step_num: self.step_num - position.t_cur as usize,
+            cycle_len: position.t_i as usize,
+            max_lr: self.cycle_max_lr(position.cycle),
+        };
+
+        for callback in self.on_restart.iter_mut() {
+            callback(&restart);
+        }
+    }
+}
+
+impl Schedule for CosineAnnealingWarmRestarts {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.lr_at_position(&self.position_at(step))
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for CosineAnnealingWarmRestarts {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        let cycle = self.current_position().cycle;
+        let epoch = match self.epoch {
+            Some((epoch, _)) => Some((epoch + 1., self.position_at_epoch(epoch + 1.)?)),
+            None => None,
+        };
+
+        self.step_num += 1;
+        self.epoch = epoch;
+        self.restart_if_new_cycle(cycle);
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+        self.epoch = None;
+    }
+}
+
+impl ToConfig for CosineAnnealingWarmRestarts {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::CosineAnnealingWarmRestarts {
+            lr: self.base_lr,
+            t_0: self.t_0,
+            t_mult: self.t_mult,
+            eta_min: self.eta_min,
+            cycle_decay: non_default(self.cycle_decay, 1.),
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `cosine_restarts(1000, t_mult=2)`.
+/// Restart callbacks aren't included.
+impl fmt::Display for CosineAnnealingWarmRestarts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`CosineAnnealingWarmRestarts`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CosineAnnealingWarmRestartsConfig {
+    pub base_lr: f64,
+    pub eta_min: f64,
+    pub t_0: usize,
+    pub t_mult: usize,
+    pub cycle_decay: f64,
+}
+
+/// A fractional epoch is saved as the whole step it falls in.
+#[cfg(feature = "serde")]
+impl StateDict for CosineAnnealingWarmRestarts {
+    type Config = CosineAnnealingWarmRestartsConfig;
+
+    fn state_dict(&self) -> SchedulerState<CosineAnnealingWarmRestartsConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: CosineAnnealingWarmRestartsConfig {
+                base_lr: self.base_lr,
+                eta_min: self.eta_min,
+                t_0: self.t_0,
+                t_mult: self.t_mult,
+                cycle_decay: self.cycle_decay,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<CosineAnnealingWarmRestartsConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        self.epoch = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{
+        CosineAnnealing, CosineAnnealingWarmRestarts, LrScheduler, Restart, Schedule,
+        SchedulerError,
+    };
+
+    #[test]
+    fn cosine_annealing_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(scheduler.get_lr(), 0.0009755527298894294);
+    }
+
+    #[test]
+    fn cosine_annealing_mid_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
+
+        for _i in 0..=5 {
+            scheduler.step(&mut opt).unwrap();
+            println!("{}", scheduler.get_lr());
+        }
+
+        assert_eq!(scheduler.get_lr(), 0.0003461460113097139);
+    }
+
+    #[test]
+    fn cosine_annealing_end_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
+
+        for _i in 0..=10 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(scheduler.get_lr(), 2.5447270110570702e-5);
+    }
+
+    #[test]
+    fn cosine_annealing_lr_at_test() {
+        let scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
+
+        assert_eq!(scheduler.lr_at(0), 1e-3);
+        assert_eq!(scheduler.lr_at(6), 0.0003461460113097139);
+        assert_eq!(scheduler.momentum_at(6), None);
+    }
+
+    #[test]
+    fn cosine_annealing_invalid_test() {
+        assert_eq!(
+            CosineAnnealing::try_new(1e-3, 0, 1e-6).unwrap_err(),
+            SchedulerError::ZeroSteps { name: "max_step" }
+        );
+        assert_eq!(
+            CosineAnnealing::try_new(1e-6, 10, 1e-3).unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "eta_min",
+                upper: "lr"
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn cosine_annealing_state_mismatch_test() {
+        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
+        let state = CosineAnnealing::new(1e-3, 20, 1e-6).state_dict();
+
+        assert!(matches!(
+            scheduler.load_state_dict(state),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+
+    // Reference values from PyTorch's CosineAnnealingWarmRestarts(T_0=3,
+    // T_mult=2, eta_min=0.001) with a base lr of 0.1.
+    const PYTORCH_WARM_RESTARTS_LRS: [f64; 13] = [
+        0.1,
+        0.07525000000000001,
+        0.025750000000000012,
+        0.1,
+        0.09336825748732973,
+        0.07525000000000001,
+        0.0505,
+        0.025750000000000012,
+        0.007631742512670284,
+        0.1,
+        0.09831332840130888,
+        0.09336825748732973,
+        0.0855017856687341,
+    ];
+
+    #[test]
+    fn warm_restarts_pytorch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = CosineAnnealingWarmRestarts::new(0.1, 3, 2, 0.001);
+
+        assert_eq!(scheduler.get_lr(), PYTORCH_WARM_RESTARTS_LRS[0]);
+
+        for lr in &PYTORCH_WARM_RESTARTS_LRS[1..] {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), *lr);
+        }
+    }
+
+    #[test]
+    fn warm_restarts_t_mult_one_test() {
+        let scheduler = CosineAnnealingWarmRestarts::new(0.1, 3, 1, 0.);
+        let lrs = [0.1, 0.07500000000000001, 0.025000000000000012];
+
+        for step in 0..9 {
+            assert_eq!(scheduler.lr_at(step), lrs[step % 3]);
+        }
+    }
+
+    #[test]
+    fn warm_restarts_fractional_epoch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = CosineAnnealingWarmRestarts::new(0.1, 3, 2, 0.001);
+        let epochs = [
+            (0.5, 0.09336825748732973),
+            (2.5, 0.007631742512670284),
+            (3., 0.1),
+            (4.25, 0.08977099034441614),
+            (9.5, 0.09957652063800362),
+            (10., 0.09831332840130888),
+        ];
+
+        for (epoch, lr) in epochs {
+            scheduler.step_epoch(&mut opt, epoch).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), PYTORCH_WARM_RESTARTS_LRS[11]);
+    }
+
+    #[test]
+    fn warm_restarts_mixed_epoch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = CosineAnnealingWarmRestarts::new(0.1, 3, 2, 0.001);
+        // PyTorch's `step(epoch)` for `Some`, `step()` for `None`.
+        let steps = [
+            (Some(1.5), 0.0505),
+            (None, 0.007631742512670284),
+            (None, 0.09831332840130888),
+            (None, 0.0855017856687341),
+            (Some(4.25), 0.08977099034441614),
+            (None, 0.06944282990207196),
+            (None, 0.04403895348510745),
+            (None, 0.02036630926406833),
+            (None, 0.004767963140691307),
+            (None, 0.09989401670031088),
+        ];
+
+        for (epoch, lr) in steps {
+            match epoch {
+                Some(epoch) => scheduler.step_epoch(&mut opt, epoch).unwrap(),
+                None => scheduler.step(&mut opt).unwrap(),
+            }
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        assert_eq!(LrScheduler::<SGD>::step_num(&scheduler), 9);
+    }
+
+    #[test]
+    fn warm_restarts_epoch_overflow_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = CosineAnnealingWarmRestarts::new(0.1, 10, 2, 0.);
+
+        assert_eq!(
+            scheduler.step_epoch(&mut opt, 1e30),
+            Err(SchedulerError::StepOverflow { name: "epoch" })
+        );
+        assert_eq!(LrScheduler::<SGD>::step_num(&scheduler), 0);
+        assert_eq!(scheduler.get_lr(), 0.1);
+        assert_eq!(
+            CosineAnnealingWarmRestarts::new(0.1, 10, 1, 0.).lr_at_epoch(1e30),
+            Err(SchedulerError::StepOverflow { name: "epoch" })
+        );
+        assert_eq!(scheduler.lr_at_epoch(15.), Ok(0.08535533905932738));
+        assert!(scheduler.lr_at(usize::MAX).is_finite());
+    }
+
+    #[test]
+    fn warm_restarts_callback_decay_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let restarts = Arc::new(Mutex::new(Vec::new()));
+        let seen = restarts.clone();
+        let mut scheduler = CosineAnnealingWarmRestarts::new(0.1, 2, 2, 0.)
+            .with_cycle_decay(0.5)
+            .unwrap()
+            .on_restart(move |restart| seen.lock().unwrap().push(*restart));
+
+        for _i in 0..7 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(
+            *restarts.lock().unwrap(),
+            vec![
+                Restart {
+                    cycle: 1,
+                    step_num: 2,
+                    cycle_len: 4,
+                    max_lr: 0.05,
+                },
+                Restart {
+                    cycle: 2,
+                    step_num: 6,
+                    cycle_len: 8,
+                    max_lr: 0.025,
+                },
+            ]
+        );
+        assert_eq!(scheduler.lr_at(6), 0.025);
+    }
+}
@@ -0,0 +1,521 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_order, check_positive, check_range, check_steps};
+use crate::{
+    Hyperparams, LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig,
+    UnsupportedPolicy,
+};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Signature of a custom [`CyclicMode`] scale function.
+pub type ScaleFn = dyn Fn(f64) -> f64 + Send + Sync;
+
+/// What a custom scale function is given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ScaleMode {
+    /// The cycle number, starting at 1.
+    #[default]
+    Cycle,
+    /// The step number.
+    Iterations,
+}
+
+/// How the amplitude of a [`CyclicLr`] changes over time.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CyclicMode {
+    /// A constant amplitude.
+    #[default]
+    Triangular,
+    /// Halve the amplitude every cycle.
+    Triangular2,
+    /// Scale the amplitude by `gamma^step`.
+    ExpRange { gamma: f64 },
+    /// Scale the amplitude by a user supplied function. It can't be
+    /// serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom {
+        scale_fn: Arc<ScaleFn>,
+        scale_mode: ScaleMode,
+    },
+}
+
+impl CyclicMode {
+    /// Scale the amplitude by `scale_fn`, which should stay in `[0, 1]`.
+    pub fn custom(
+        scale_fn: impl Fn(f64) -> f64 + Send + Sync + 'static,
+        scale_mode: ScaleMode,
+    ) -> Self {
+        CyclicMode::Custom {
+            scale_fn: Arc::new(scale_fn),
+            scale_mode,
+        }
+    }
+
+    fn scale(&self, cycle: f64, step: usize) -> f64 {
+        match self {
+            CyclicMode::Triangular => 1.,
+            CyclicMode::Triangular2 => 1. / 2f64.powf(cycle - 1.),
+            CyclicMode::ExpRange { gamma } => gamma.powf(step as f64),
+            CyclicMode::Custom {
+                scale_fn,
+                scale_mode: ScaleMode::Cycle,
+            } => scale_fn(cycle),
+            CyclicMode::Custom {
+                scale_fn,
+                scale_mode: ScaleMode::Iterations,
+            } => scale_fn(step as f64),
+        }
+    }
+}
+
+impl fmt::Debug for CyclicMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CyclicMode::Triangular => f.write_str("Triangular"),
+            CyclicMode::Triangular2 => f.write_str("Triangular2"),
+            CyclicMode::ExpRange { gamma } => {
+                f.debug_struct("ExpRange").field("gamma", gamma).finish()
+            }
+            CyclicMode::Custom { scale_mode, .. } => f
+                .debug_struct("Custom")
+                .field("scale_mode", scale_mode)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Custom modes are only equal to themselves.
+impl PartialEq for CyclicMode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CyclicMode::ExpRange { gamma: a }, CyclicMode::ExpRange { gamma: b }) => a == b,
+            (CyclicMode::Custom { scale_fn: a, .. }, CyclicMode::Custom { scale_fn: b, .. }) => {
+                Arc::ptr_eq(a, b)
+            }
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+}
+
+/// Configures a [`CyclicLr`]. The defaults match PyTorch's.
+#[derive(Debug, Clone)]
+pub struct CyclicLrBuilder {
+    base_lr: f64,
+    max_lr: f64,
+    step_size_up: usize,
+    step_size_down: Option<usize>,
+    mode: CyclicMode,
+    cycle_momentum: bool,
+    base_momentum: f64,
+    max_momentum: f64,
+}
+
+impl CyclicLrBuilder {
+    /// Steps from `base_lr` up to `max_lr`. Defaults to 2000.
+    pub fn step_size_up(mut self, step_size_up: usize) -> Self {
+        self.step_size_up = step_size_up;
+        self
+    }
+
+    /// Steps from `max_lr` back down to `base_lr`. Defaults to
+    /// `step_size_up`.
+    pub fn step_size_down(mut self, step_size_down: usize) -> Self {
+        self.step_size_down = Some(step_size_down);
+        self
+    }
+
+    /// Defaults to [`CyclicMode::Triangular`].
+    pub fn mode(mut self, mode: CyclicMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Cycle momentum inversely to the lr, between `base_momentum` and
+    /// `max_momentum`. Defaults to true.
+    pub fn cycle_momentum(mut self, cycle_momentum: bool) -> Self {
+        self.cycle_momentum = cycle_momentum;
+        self
+    }
+
+    /// Momentum at the peak lr. Defaults to 0.8.
+    pub fn base_momentum(mut self, base_momentum: f64) -> Self {
+        self.base_momentum = base_momentum;
+        self
+    }
+
+    /// Momentum at the base lr. Defaults to 0.9.
+    pub fn max_momentum(mut self, max_momentum: f64) -> Self {
+        self.max_momentum = max_momentum;
+        self
+    }
+
+    /// Requires finite `base_lr <= max_lr`, positive step sizes, a positive
+    /// `gamma` for [`CyclicMode::ExpRange`] and, when cycling momentum,
+    /// `0 <= base_momentum <= max_momentum <= 1`.
+    pub fn build(&self) -> Result<CyclicLr, SchedulerError> {
+        check_finite("base_lr", self.base_lr)?;
+        check_finite("max_lr", self.max_lr)?;
+        check_order(("base_lr", self.base_lr), ("max_lr", self.max_lr))?;
+        check_steps("step_size_up", self.step_size_up)?;
+        let step_size_down = check_steps(
+            "step_size_down",
+            self.step_size_down.unwrap_or(self.step_size_up),
+        )?;
+        if let CyclicMode::ExpRange { gamma } = self.mode {
+            check_positive("gamma", gamma)?;
+        }
+
+        let momentum = if self.cycle_momentum {
+            check_range("base_momentum", self.base_momentum, 0., 1.)?;
+            check_range("max_momentum", self.max_momentum, 0., 1.)?;
+            check_order(
+                ("base_momentum", self.base_momentum),
+                ("max_momentum", self.max_momentum),
+            )?;
+            Some((self.base_momentum, self.max_momentum))
+        } else {
+            None
+        };
+
+        Ok(CyclicLr {
+            base_lr: self.base_lr,
+            max_lr: self.max_lr,
+            step_size_up: self.step_size_up,
+            step_size_down,
+            mode: self.mode.clone(),
+            momentum,
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+}
+
+/// Cyclical learning rates (Smith, 2015), like PyTorch's `CyclicLR`.
+///
+/// The lr cycles between `base_lr` and `max_lr`, with an amplitude scaled by
+/// the [`CyclicMode`]. Momentum cycles the other way, between
+/// `max_momentum` at the base lr and `base_momentum` at the peak.
+///
+/// ```
+/// use candle_scheduler::{CyclicLr, CyclicMode};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let scheduler = CyclicLr::builder(1e-4, 1e-3)
+///     .step_size_up(500)
+///     .mode(CyclicMode::Triangular2)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CyclicLr {
+    base_lr: f64,
+    max_lr: f64,
+    step_size_up: usize,
+    step_size_down: usize,
+    mode: CyclicMode,
+    /// Base and max momentum, if momentum is cycled.
+    momentum: Option<(f64, f64)>,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl CyclicLr {
+    /// A triangular cycle with PyTorch's defaults, `step_size_up` steps up and
+    /// as many down, cycling momentum between 0.8 and 0.9.
+    ///
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`CyclicLrBuilder::build`].
+    pub fn new(base_lr: f64, max_lr: f64, step_size_up: usize) -> Self {
+        Self::builder(base_lr, max_lr)
+            .step_size_up(step_size_up)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid CyclicLr: {err}"))
+    }
+
+    pub fn builder(base_lr: f64, max_lr: f64) -> CyclicLrBuilder {
+        CyclicLrBuilder {
+            base_lr,
+            max_lr,
+            step_size_up: 2000,
+            step_size_down: None,
+            mode: CyclicMode::Triangular,
+            cycle_momentum: true,
+            base_momentum: 0.8,
+            max_momentum: 0.9,
+        }
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+
+    /// How far up the cycle `step` is, in `0..=1`, and the amplitude scale.
+    fn position(&self, step: usize) -> (f64, f64) {
+        let total_size = (self.step_size_up + self.step_size_down) as f64;
+        let step_ratio = self.step_size_up as f64 / total_size;
+        let cycle = (1. + step as f64 / total_size).floor();
+        let x = 1. + step as f64 / total_size - cycle;
+
+        let scale_factor = if x <= step_ratio {
+            x / step_ratio
+        } else {
+            (x - 1.) / (step_ratio - 1.)
+        };
+
+        (scale_factor, self.mode.scale(cycle, step))
+    }
+}
+
+impl Schedule for CyclicLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        let (scale_factor, scale) = self.position(step);
+        let base_height = (self.max_lr - self.base_lr) * scale_factor;
+        self.base_lr + base_height * scale
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        let (base_momentum, max_momentum) = self.momentum?;
+        let (scale_factor, scale) = self.position(step);
+        let base_height = (max_momentum - base_momentum) * scale_factor;
+        Some(max_momentum - base_height * scale)
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for CyclicLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for CyclicLr {
+    fn to_config(&self) -> SchedulerConfig {
+        let (cycle_momentum, base_momentum, max_momentum) = match self.momentum {
+            Some((base_momentum, max_momentum)) => (
+                None,
+                non_default(base_momentum, 0.8),
+                non_default(max_momentum, 0.9),
+            ),
+            None => (Some(false), None, None),
+        };
+
+        SchedulerConfig::CyclicLr {
+            base_lr: self.base_lr,
+            max_lr: self.max_lr,
+            step_size_up: Some(self.step_size_up),
+            step_size_down: non_default(self.step_size_down, self.step_size_up),
+            mode: non_default(self.mode.clone(), CyclicMode::Triangular),
+            cycle_momentum,
+            base_momentum,
+            max_momentum,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `cyclic(0.0001, 0.001, step_size_up=500)`.
+/// A custom mode is written as `custom`, which can't be parsed back.
+impl fmt::Display for CyclicLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`CyclicLr`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CyclicLrConfig {
+    pub base_lr: f64,
+    pub max_lr: f64,
+    pub step_size_up: usize,
+    pub step_size_down: usize,
+    pub mode: CyclicMode,
+    pub momentum: Option<(f64, f64)>,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for CyclicLr {
+    type Config = CyclicLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<CyclicLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: CyclicLrConfig {
+                base_lr: self.base_lr,
+                max_lr: self.max_lr,
+                step_size_up: self.step_size_up,
+                step_size_down: self.step_size_down,
+                mode: self.mode.clone(),
+                momentum: self.momentum,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<CyclicLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap};
+
+    use crate::{CyclicLr, CyclicMode, LrScheduler, ScaleMode, Schedule, SchedulerError};
+
+    fn cyclic(mode: CyclicMode) -> CyclicLr {
+        CyclicLr::builder(0.001, 0.006)
+            .step_size_up(2)
+            .step_size_down(3)
+            .mode(mode)
+            .build()
+            .unwrap()
+    }
+
+    // Reference values from PyTorch's CyclicLR(base_lr=0.001, max_lr=0.006,
+    // step_size_up=2, step_size_down=3).
+    #[test]
+    fn cyclic_triangular_pytorch_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = cyclic(CyclicMode::Triangular);
+        let expected = [
+            (0.0034999999999999996, 0.8500000000000001),
+            (0.005999999999999999, 0.8),
+            (0.004333333333333333, 0.8333333333333334),
+            (0.002666666666666666, 0.8666666666666667),
+            (0.001, 0.9),
+            (0.0035000000000000022, 0.85),
+        ];
+
+        assert_eq!(scheduler.get_lr(), 0.001);
+        assert_eq!(scheduler.get_momentum(), Some(0.9));
+
+        for (lr, momentum) in expected {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+            assert_eq!(opt.params().beta1, momentum);
+        }
+    }
+
+    #[test]
+    fn cyclic_triangular2_pytorch_test() {
+        let scheduler = cyclic(CyclicMode::Triangular2);
+
+        assert_eq!(scheduler.lr_at(2), 0.005999999999999999);
+        assert_eq!(scheduler.lr_at(6), 0.002250000000000001);
+        assert_eq!(scheduler.lr_at(7), 0.0034999999999999996);
+        assert_eq!(scheduler.lr_at(11), 0.0016250000000000006);
+        assert_eq!(scheduler.momentum_at(7), Some(0.8500000000000001));
+    }
+
+    #[test]
+    fn cyclic_exp_range_pytorch_test() {
+        let scheduler = cyclic(CyclicMode::ExpRange { gamma: 0.9 });
+
+        assert_eq!(scheduler.lr_at(1), 0.00325);
+        assert_eq!(scheduler.lr_at(2), 0.00505);
+        assert_eq!(scheduler.lr_at(7), 0.0033914845);
+        assert_eq!(scheduler.momentum_at(2), Some(0.8190000000000001));
+        assert_eq!(scheduler.momentum_at(8), Some(0.871302186));
+    }
+
+    #[test]
+    fn cyclic_custom_scale_test() {
+        let scheduler = cyclic(CyclicMode::custom(
+            |cycle| 1. / 2f64.powf(cycle - 1.),
+            ScaleMode::Cycle,
+        ));
+
+        assert_eq!(scheduler.lr_at(6), cyclic(CyclicMode::Triangular2).lr_at(6));
+    }
+
+    #[test]
+    fn cyclic_without_momentum_test() {
+        let scheduler = CyclicLr::builder(0.001, 0.006)
+            .cycle_momentum(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.momentum_at(10), None);
+    }
+
+    #[test]
+    fn cyclic_invalid_test() {
+        assert_eq!(
+            CyclicLr::builder(0.01, 0.001).build().unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "base_lr",
+                upper: "max_lr"
+            }
+        );
+        assert_eq!(
+            CyclicLr::builder(0.001, 0.01)
+                .step_size_down(0)
+                .build()
+                .unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "step_size_down"
+            }
+        );
+        assert_eq!(
+            CyclicLr::builder(0.001, 0.01)
+                .base_momentum(0.95)
+                .build()
+                .unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "base_momentum",
+                upper: "max_momentum"
+            }
+        );
+    }
+}
@@ -0,0 +1,499 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::error::{check_finite, check_order, check_positive, check_range, check_steps};
+use crate::{LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Decays the lr by `gamma` every step, like PyTorch's `ExponentialLR`.
+#[derive(Debug)]
+pub struct ExponentialLr {
+    base_lr: f64,
+    gamma: f64,
+    step_num: usize,
+}
+
+impl ExponentialLr {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`ExponentialLr::try_new`].
+    pub fn new(lr: f64, gamma: f64) -> Self {
+        Self::try_new(lr, gamma).unwrap_or_else(|err| panic!("invalid ExponentialLr: {err}"))
+    }
+
+    /// Requires a finite `lr` and a positive finite `gamma`.
+    pub fn try_new(lr: f64, gamma: f64) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_positive("gamma", gamma)?;
+
+        Ok(ExponentialLr {
+            base_lr: lr,
+            gamma,
+            step_num: 0,
+        })
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+}
+
+impl Schedule for ExponentialLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.base_lr * self.gamma.powf(step as f64)
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for ExponentialLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for ExponentialLr {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::ExponentialLr {
+            lr: self.base_lr,
+            gamma: self.gamma,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `exp(0.99, lr=0.001)`.
+impl fmt::Display for ExponentialLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with an [`ExponentialLr`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExponentialLrConfig {
+    pub base_lr: f64,
+    pub gamma: f64,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for ExponentialLr {
+    type Config = ExponentialLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<ExponentialLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: ExponentialLrConfig {
+                base_lr: self.base_lr,
+                gamma: self.gamma,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<ExponentialLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+/// Polynomial decay from the base lr to `end_lr` over `total_steps`, then
+/// held at `end_lr`.
+///
+/// `end_lr + (lr - end_lr) * (1 - step / total_steps)^power`, the BERT style
+/// decay. With an `end_lr` of 0 this is PyTorch's `PolynomialLR`.
+#[derive(Debug)]
+pub struct PolynomialLr {
+    base_lr: f64,
+    end_lr: f64,
+    total_steps: usize,
+    power: f64,
+    step_num: usize,
+}
+
+impl PolynomialLr {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`PolynomialLr::try_new`].
+    pub fn new(lr: f64, end_lr: f64, total_steps: usize, power: f64) -> Self {
+        Self::try_new(lr, end_lr, total_steps, power)
+            .unwrap_or_else(|err| panic!("invalid PolynomialLr: {err}"))
+    }
+
+    /// Requires finite `0 <= end_lr <= lr`, a positive `total_steps` and a
+    /// positive finite `power`.
+    pub fn try_new(
+        lr: f64,
+        end_lr: f64,
+        total_steps: usize,
+        power: f64,
+    ) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_range("end_lr", end_lr, 0., f64::INFINITY)?;
+        check_order(("end_lr", end_lr), ("lr", lr))?;
+        check_steps("total_steps", total_steps)?;
+        check_positive("power", power)?;
+
+        Ok(PolynomialLr {
+            base_lr: lr,
+            end_lr,
+            total_steps,
+            power,
+            step_num: 0,
+        })
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+}
+
+impl Schedule for PolynomialLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        let pct = step.min(self.total_steps) as f64 / self.total_steps as f64;
+        (self.base_lr - self.end_lr) * (1. - pct).powf(self.power) + self.end_lr
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for PolynomialLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for PolynomialLr {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::PolynomialLr {
+            lr: self.base_lr,
+            end_lr: self.end_lr,
+            total_steps: self.total_steps,
+            power: self.power,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `poly(1000, power=2.0)`, or
+/// `linear(1000)` for a power of 1.
+impl fmt::Display for PolynomialLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`PolynomialLr`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PolynomialLrConfig {
+    pub base_lr: f64,
+    pub end_lr: f64,
+    pub total_steps: usize,
+    pub power: f64,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for PolynomialLr {
+    type Config = PolynomialLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<PolynomialLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: PolynomialLrConfig {
+                base_lr: self.base_lr,
+                end_lr: self.end_lr,
+                total_steps: self.total_steps,
+                power: self.power,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<PolynomialLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+/// Signature of the per-step closures taken by [`MultiplicativeLr`] and
+/// [`LambdaLr`](crate::LambdaLr), taking the step number.
+pub type FactorFn = dyn Fn(usize) -> f64 + Send + Sync;
+
+/// Multiplies the lr by `factor(step)` on every step, like PyTorch's
+/// `MultiplicativeLR`.
+///
+/// The lr at a step depends on every factor before it, so [`Schedule::lr_at`]
+/// is linear in `step` here. Stepping stays O(1).
+pub struct MultiplicativeLr {
+    base_lr: f64,
+    factor: Box<FactorFn>,
+    lr: f64,
+    step_num: usize,
+}
+
+impl fmt::Debug for MultiplicativeLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiplicativeLr")
+            .field("base_lr", &self.base_lr)
+            .field("lr", &self.lr)
+            .field("step_num", &self.step_num)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MultiplicativeLr {
+    /// # Panics
+    ///
+    /// If `lr` isn't finite.
+    pub fn new(lr: f64, factor: impl Fn(usize) -> f64 + Send + Sync + 'static) -> Self {
+        Self::try_new(lr, factor).unwrap_or_else(|err| panic!("invalid MultiplicativeLr: {err}"))
+    }
+
+    /// Requires a finite `lr`.
+    pub fn try_new(
+        lr: f64,
+        factor: impl Fn(usize) -> f64 + Send + Sync + 'static,
+    ) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+
+        Ok(MultiplicativeLr {
+            base_lr: lr,
+            factor: Box::new(factor),
+            lr,
+            step_num: 0,
+        })
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr
+    }
+}
+
+impl Schedule for MultiplicativeLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        (1..=step).fold(self.base_lr, |lr, step| lr * (self.factor)(step))
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for MultiplicativeLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+        self.lr *= (self.factor)(self.step_num);
+
+        optimizer.set_learning_rate(self.lr);
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+        self.lr = self.base_lr;
+    }
+}
+
+/// The configuration saved with a [`MultiplicativeLr`] state. The factor
+/// function can't be saved, so it has to be the same on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MultiplicativeLrConfig {
+    pub base_lr: f64,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for MultiplicativeLr {
+    type Config = MultiplicativeLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<MultiplicativeLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.lr,
+            momentum: None,
+            config: MultiplicativeLrConfig {
+                base_lr: self.base_lr,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<MultiplicativeLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        self.lr = self.lr_at(state.step_num);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{
+        ExponentialLr, LrScheduler, MultiplicativeLr, PolynomialLr, Schedule, SchedulerError,
+    };
+
+    #[test]
+    fn exponential_lr_pytorch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = ExponentialLr::new(0.1, 0.9);
+        let lrs = [
+            0.09000000000000001,
+            0.08100000000000002,
+            0.0729,
+            0.06561,
+            0.05904900000000001,
+        ];
+
+        for lr in lrs {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+    }
+
+    #[test]
+    fn polynomial_lr_pytorch_test() {
+        // The example from PyTorch's PolynomialLR docs.
+        let scheduler = PolynomialLr::new(0.001, 0., 4, 1.);
+        let lrs = [0.001, 0.00075, 0.0005, 0.00025, 0., 0.];
+
+        for (step, lr) in lrs.into_iter().enumerate() {
+            assert_eq!(scheduler.lr_at(step), lr);
+        }
+    }
+
+    #[test]
+    fn polynomial_lr_end_lr_test() {
+        let scheduler = PolynomialLr::new(1e-3, 1e-5, 10, 2.);
+
+        assert_eq!(scheduler.lr_at(0), 0.001);
+        assert_eq!(scheduler.lr_at(3), 0.0004950999999999999);
+        assert_eq!(scheduler.lr_at(5), 0.0002575);
+        assert_eq!(scheduler.lr_at(10), 1e-5);
+        assert_eq!(scheduler.lr_at(12), 1e-5);
+    }
+
+    #[test]
+    fn multiplicative_lr_pytorch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = MultiplicativeLr::new(0.1, |_step| 0.95);
+        let lrs = [
+            0.095,
+            0.09025,
+            0.0857375,
+            0.08145062499999998,
+            0.07737809374999999,
+        ];
+
+        for lr in lrs {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        assert_eq!(scheduler.lr_at(5), scheduler.get_lr());
+    }
+
+    #[test]
+    fn multiplicative_lr_step_factor_test() {
+        let scheduler = MultiplicativeLr::new(1., |step| 1. / (step + 1) as f64);
+
+        assert_eq!(scheduler.lr_at(2), 0.16666666666666666);
+        assert_eq!(scheduler.lr_at(4), 0.008333333333333333);
+    }
+
+    #[test]
+    fn decay_invalid_test() {
+        assert_eq!(
+            ExponentialLr::try_new(0.1, 0.).unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "gamma",
+                value: 0.
+            }
+        );
+        assert_eq!(
+            PolynomialLr::try_new(1e-3, 1e-2, 10, 1.).unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "end_lr",
+                upper: "lr"
+            }
+        );
+        assert_eq!(
+            PolynomialLr::try_new(1e-3, 0., 0, 1.).unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "total_steps"
+            }
+        );
+        assert!(matches!(
+            MultiplicativeLr::try_new(f64::NAN, |_step| 0.9),
+            Err(SchedulerError::NonFinite { name: "lr", .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn multiplicative_lr_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = MultiplicativeLr::new(0.1, |_step| 0.95);
+
+        for _i in 0..3 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let mut resumed = MultiplicativeLr::new(0.1, |_step| 0.95);
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+    }
+}
@@ -0,0 +1,1775 @@
+use std::fmt::{self, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::config::non_default;
+use crate::{
+    Anneal, CyclicMode, Duration, PhaseSpec, PlateauMode, SchedulerConfig, SchedulerError,
+    SchedulerKwargs, SchedulerType, ThresholdMode, WsdShape,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Open,
+    Close,
+    OpenList,
+    CloseList,
+    Comma,
+    Equals,
+    Then,
+    Times,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(text) | Token::Ident(text) => write!(f, "`{text}`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::OpenList => f.write_str("`[`"),
+            Token::CloseList => f.write_str("`]`"),
+            Token::Comma => f.write_str("`,`"),
+            Token::Equals => f.write_str("`=`"),
+            Token::Then => f.write_str("`>>`"),
+            Token::Times => f.write_str("`*`"),
+            Token::End => f.write_str("the end"),
+        }
+    }
+}
+
+fn parse_error(message: impl Into<String>, span: Range<usize>) -> SchedulerError {
+    SchedulerError::Parse {
+        message: message.into(),
+        span,
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Range<usize>)>, SchedulerError> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    while let Some(next) = source[start..].chars().next() {
+        let rest = &source[start..];
+        let (token, len) = match next {
+            next if next.is_whitespace() => {
+                start += next.len_utf8();
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '[' => (Token::OpenList, 1),
+            ']' => (Token::CloseList, 1),
+            ',' => (Token::Comma, 1),
+            '=' => (Token::Equals, 1),
+            '*' => (Token::Times, 1),
+            '>' if rest.starts_with(">>") => (Token::Then, 2),
+            '0'..='9' | '-' | '.' => {
+                let len = number_len(rest);
+                let text = &rest[..len];
+                if text.parse::<f64>().is_err() {
+                    return Err(parse_error(
+                        format!("invalid number `{text}`"),
+                        start..start + len,
+                    ));
+                }
+                (Token::Number(text.to_string()), len)
+            }
+            next if next.is_ascii_alphabetic() || next == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (Token::Ident(rest[..len].to_string()), len)
+            }
+            next => {
+                return Err(parse_error(
+                    format!("unexpected `{next}`"),
+                    start..start + next.len_utf8(),
+                ))
+            }
+        };
+
+        tokens.push((token, start..start + len));
+        start += len;
+    }
+
+    tokens.push((Token::End, source.len()..source.len()));
+    Ok(tokens)
+}
+
+/// Length of the number at the start of `source`, with an optional sign,
+/// fraction and exponent.
+fn number_len(source: &str) -> usize {
+    let bytes = source.as_bytes();
+    let mut len = 0;
+
+    while let Some(&byte) = bytes.get(len) {
+        let sign =
+            matches!(byte, b'-' | b'+') && (len == 0 || matches!(bytes[len - 1], b'e' | b'E'));
+        if !(byte.is_ascii_digit() || matches!(byte, b'.' | b'e' | b'E') || sign) {
+            break;
+        }
+        len += 1;
+    }
+
+    len
+}
+
+#[derive(Debug)]
+struct Expr {
+    kind: ExprKind,
+    span: Range<usize>,
+}
+
+#[derive(Debug)]
+enum ExprKind {
+    Number(String),
+    Ident(String),
+    Call {
+        name: String,
+        args: Vec<Arg>,
+    },
+    List(Vec<Expr>),
+    /// Kept so a parenthesized `>>` or `*` isn't flattened into the one
+    /// around it.
+    Group(Box<Expr>),
+    Then(Box<Expr>, Box<Expr>),
+    Times(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+struct Arg {
+    keyword: Option<(String, Range<usize>)>,
+    value: Expr,
+}
+
+impl Expr {
+    fn binary(kind: fn(Box<Expr>, Box<Expr>) -> ExprKind, lhs: Expr, rhs: Expr) -> Self {
+        Expr {
+            span: lhs.span.start..rhs.span.end,
+            kind: kind(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.kind {
+            ExprKind::Number(text) | ExprKind::Ident(text) => format!("`{text}`"),
+            ExprKind::Call { name, .. } => format!("`{name}(..)`"),
+            ExprKind::List(_) => "a list".to_string(),
+            ExprKind::Group(_) | ExprKind::Then(..) | ExprKind::Times(..) => {
+                "a schedule".to_string()
+            }
+        }
+    }
+}
+
+/// Recursive descent over the grammar
+///
+/// ```text
+/// expr := term (">>" term)*
+/// term := atom ("*" atom)*
+/// atom := number | ident | ident "(" args ")" | "(" expr ")" | "[" expr,* "]"
+/// args := ((ident "=")? expr),*
+/// ```
+struct Parser {
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> (Token, Range<usize>) {
+        let next = self.tokens[self.pos].clone();
+        if next.0 != Token::End {
+            self.pos += 1;
+        }
+        next
+    }
+
+    fn expect(&mut self, expected: Token, after: &str) -> Result<Range<usize>, SchedulerError> {
+        match self.next() {
+            (token, span) if token == expected => Ok(span),
+            (token, span) => Err(parse_error(
+                format!("expected {expected} after {after}, found {token}"),
+                span,
+            )),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, SchedulerError> {
+        let mut lhs = self.term()?;
+        while *self.peek() == Token::Then {
+            self.next();
+            lhs = Expr::binary(ExprKind::Then, lhs, self.term()?);
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, SchedulerError> {
+        let mut lhs = self.atom()?;
+        while *self.peek() == Token::Times {
+            self.next();
+            lhs = Expr::binary(ExprKind::Times, lhs, self.atom()?);
+        }
+        Ok(lhs)
+    }
+
+    fn atom(&mut self) -> Result<Expr, SchedulerError> {
+        let (token, span) = self.next();
+        let (kind, end) = match token {
+            Token::Number(text) => (ExprKind::Number(text), span.end),
+            Token::Ident(name) if *self.peek() == Token::Open => {
+                self.next();
+                let args = self.args()?;
+                let close = self.expect(Token::Close, "the arguments")?;
+                (ExprKind::Call { name, args }, close.end)
+            }
+            Token::Ident(name) => (ExprKind::Ident(name), span.end),
+            Token::Open => {
+                let inner = self.expr()?;
+                let close = self.expect(Token::Close, "the group")?;
+                (ExprKind::Group(Box::new(inner)), close.end)
+            }
+            Token::OpenList => {
+                let mut items = Vec::new();
+                while *self.peek() != Token::CloseList {
+                    items.push(self.expr()?);
+                    if *self.peek() != Token::Comma {
+                        break;
+                    }
+                    self.next();
+                }
+                let close = self.expect(Token::CloseList, "the list")?;
+                (ExprKind::List(items), close.end)
+            }
+            token => {
+                return Err(parse_error(
+                    format!("expected a schedule or value, found {token}"),
+                    span,
+                ))
+            }
+        };
+
+        Ok(Expr {
+            kind,
+            span: span.start..end,
+        })
+    }
+
+    fn args(&mut self) -> Result<Vec<Arg>, SchedulerError> {
+        let mut args = Vec::new();
+        while *self.peek() != Token::Close {
+            let keyword = match (self.peek(), self.tokens.get(self.pos + 1)) {
+                (Token::Ident(name), Some((Token::Equals, _))) => {
+                    let name = name.clone();
+                    let (_, span) = self.next();
+                    self.next();
+                    Some((name, span))
+                }
+                _ => None,
+            };
+            args.push(Arg {
+                keyword,
+                value: self.expr()?,
+            });
+            if *self.peek() != Token::Comma {
+                break;
+            }
+            self.next();
+        }
+        Ok(args)
+    }
+}
+
+fn parse(source: &str) -> Result<Expr, SchedulerError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    let expr = parser.expr()?;
+
+    match parser.next() {
+        (Token::End, _) => Ok(expr),
+        (token, span) => Err(parse_error(
+            format!("expected `>>`, `*` or the end, found {token}"),
+            span,
+        )),
+    }
+}
+
+/// Parses a schedule expression, the compact form of a config.
+///
+/// Schedules are written as calls, like `cosine(10000, min=1e-6)`, and
+/// combined with `>>` to run one after the other and `*` to multiply them. A
+/// bare number is a constant, so `cosine(10000) * 0.5` halves the cosine.
+/// Schedules that only give a shape, like `cosine`, have an lr of 1 unless
+/// `lr=` is given. `warmup(..) >> s` warms up into `s`, and `a >> b` switches
+/// to `b` once `a` is over, which needs `a` to have a fixed length; otherwise
+/// use `sequential(a, b, milestones=[..])`.
+///
+/// ```
+/// use candle_scheduler::SchedulerConfig;
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let config: SchedulerConfig = "warmup(linear, 500) >> cosine(10000, min=1e-6) * 0.5".parse()?;
+/// let scheduler = config.build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The schedules and their arguments, with defaults:
+///
+/// ```text
+/// constant(lr)
+/// cosine(max_step, min=0, lr=1)
+/// cosine_restarts(t_0, t_mult=1, min=0, cycle_decay=1, lr=1)
+/// step(step_size, gamma, steps_per_epoch=1, lr=1)
+/// multistep([milestones], gamma, steps_per_epoch=1, lr=1)
+/// exp(gamma, lr=1)
+/// poly(total_steps, power=1, end_lr=0, lr=1)
+/// linear(total_steps, end_lr=0, lr=1)
+/// cyclic(base_lr, max_lr, step_size_up=, step_size_down=, mode=, cycle_momentum=,
+///        base_momentum=, max_momentum=)
+/// one_cycle(max_lr, total_steps, epochs=, steps_per_epoch=, pct_start=, anneal_strategy=,
+///           phase_anneals=[[index, anneal]], cycle_momentum=, base_momentum=,
+///           max_momentum=, div_factor=, final_div_factor=, three_phase=)
+/// inverse_sqrt(warmup_steps=0, d_model=, linear_warmup=, timescale=,
+///              cooldown=[cooldown_steps, total_steps], lr=1)
+/// wsd(warmup_steps=0, min=0, decay=linear, cooldown=[start, steps], lr=1)
+/// phases(phase(duration, start_lr, end_lr, anneal=cos, momentum=[start, end]), ..,
+///        total_steps=)
+/// plateau(mode=min, factor=, patience=, threshold=, threshold_mode=rel, cooldown=,
+///         min=, eps=, lr=1)
+/// hf(name, warmup_steps=0, total_steps=, lr=1, <SchedulerKwargs fields>)
+/// warmup(anneal=linear, warmup_steps, start_factor=0, momentum=[start, end]) >> schedule
+/// sequential(schedule, .., milestones=[..])
+/// chained(schedule, ..)
+/// ```
+///
+/// Anneal strategies are `cos`, `linear`, `exponential`, `polynomial(power)`,
+/// `one_minus_sqrt` and `constant`. A phase duration with a decimal point is a
+/// fraction of the total steps.
+impl FromStr for SchedulerConfig {
+    type Err = SchedulerError;
+
+    fn from_str(source: &str) -> Result<Self, SchedulerError> {
+        let (config, spans) = schedule(&parse(source)?)?;
+
+        config.build().map_err(|err| spans.error(err))?;
+        Ok(config)
+    }
+}
+
+/// The span of a config in the expression, and those of the configs nested
+/// in it, in the order [`SchedulerConfig::build`] numbers them.
+struct Spans {
+    span: Range<usize>,
+    nested: Vec<Spans>,
+}
+
+impl Spans {
+    /// Place an error from building the whole config at the sub-expression
+    /// that the path of a [`SchedulerError::Config`] leads to.
+    fn error(&self, err: SchedulerError) -> SchedulerError {
+        let SchedulerError::Config { path, err } = err else {
+            return parse_error(err.to_string(), self.span.clone());
+        };
+        let nested = path.split('.').try_fold(self, |spans, field| {
+            let index = match field {
+                "inner" => 0,
+                field => field
+                    .strip_prefix("schedules[")?
+                    .strip_suffix(']')?
+                    .parse()
+                    .ok()?,
+            };
+            spans.nested.get(index)
+        });
+
+        match nested {
+            Some(spans) => parse_error(err.to_string(), spans.span.clone()),
+            None => parse_error(format!("{path}: {err}"), self.span.clone()),
+        }
+    }
+}
+
+fn schedule(expr: &Expr) -> Result<(SchedulerConfig, Spans), SchedulerError> {
+    let (config, nested) = match &expr.kind {
+        ExprKind::Number(_) => (SchedulerConfig::Constant { lr: number(expr)? }, Vec::new()),
+        ExprKind::Group(inner) => return schedule(inner),
+        ExprKind::Then(lhs, rhs) => return then(expr, lhs, rhs),
+        ExprKind::Times(lhs, rhs) => {
+            let (mut schedules, mut nested) = match (&lhs.kind, schedule(lhs)?) {
+                (ExprKind::Times(..), (SchedulerConfig::Chained { schedules }, spans)) => {
+                    (schedules, spans.nested)
+                }
+                (_, (lhs, spans)) => (vec![lhs], vec![spans]),
+            };
+            let (rhs, spans) = schedule(rhs)?;
+            schedules.push(rhs);
+            nested.push(spans);
+            (SchedulerConfig::Chained { schedules }, nested)
+        }
+        ExprKind::Call { name, args } => call(expr, name, args)?,
+        ExprKind::Ident(_) | ExprKind::List(_) => {
+            return Err(parse_error(
+                format!("expected a schedule, found {}", expr.describe()),
+                expr.span.clone(),
+            ))
+        }
+    };
+
+    Ok((
+        config,
+        Spans {
+            span: expr.span.clone(),
+            nested,
+        },
+    ))
+}
+
+fn schedules(exprs: &[&Expr]) -> Result<(Vec<SchedulerConfig>, Vec<Spans>), SchedulerError> {
+    let schedules = exprs
+        .iter()
+        .map(|expr| schedule(expr))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(schedules.into_iter().unzip())
+}
+
+fn then(expr: &Expr, lhs: &Expr, rhs: &Expr) -> Result<(SchedulerConfig, Spans), SchedulerError> {
+    if let ExprKind::Call { name, args } = &lhs.kind {
+        if name == "warmup" {
+            return warmup(lhs, args, schedule(rhs)?);
+        }
+    }
+
+    let (mut schedules, mut milestones, mut nested, last) = match (&lhs.kind, schedule(lhs)?) {
+        (
+            ExprKind::Then(_, last),
+            (
+                SchedulerConfig::Sequential {
+                    schedules,
+                    milestones,
+                },
+                spans,
+            ),
+        ) => (schedules, milestones, spans.nested, last.as_ref()),
+        (_, (config, spans)) => (vec![config], Vec::new(), vec![spans], lhs),
+    };
+    let start = milestones.last().copied().unwrap_or(0);
+    let length = schedules.last().and_then(length).ok_or_else(|| {
+        parse_error(
+            "this schedule has no fixed length to switch after, use \
+             `sequential(.., milestones=[..])` instead",
+            last.span.clone(),
+        )
+    })?;
+
+    let (rhs, spans) = schedule(rhs)?;
+
+    milestones.push(start + length);
+    schedules.push(rhs);
+    nested.push(spans);
+    Ok((
+        SchedulerConfig::Sequential {
+            schedules,
+            milestones,
+        },
+        Spans {
+            span: expr.span.clone(),
+            nested,
+        },
+    ))
+}
+
+/// Steps until `config` is over, for schedules that end.
+fn length(config: &SchedulerConfig) -> Option<usize> {
+    match config {
+        SchedulerConfig::CosineAnnealing { max_step, .. } => Some(*max_step),
+        SchedulerConfig::PolynomialLr { total_steps, .. } => Some(*total_steps),
+        SchedulerConfig::OneCycle {
+            total_steps,
+            epochs,
+            steps_per_epoch,
+            ..
+        } => total_steps.or(epochs
+            .zip(*steps_per_epoch)
+            .map(|(epochs, steps_per_epoch)| epochs * steps_per_epoch)),
+        SchedulerConfig::InverseSqrt { cooldown, .. } => {
+            cooldown.map(|(_, total_steps)| total_steps)
+        }
+        SchedulerConfig::Wsd { cooldown, .. } => cooldown.map(|(start, steps)| start + steps),
+        SchedulerConfig::Phases {
+            total_steps,
+            phases,
+        } => total_steps.or_else(|| {
+            phases
+                .iter()
+                .map(|phase| match phase.duration {
+                    Duration::Steps(steps) => Some(steps),
+                    Duration::Fraction(_) => None,
+                })
+                .sum()
+        }),
+        SchedulerConfig::Hf { total_steps, .. } => *total_steps,
+        SchedulerConfig::Warmup {
+            warmup_steps,
+            inner,
+            ..
+        } => Some(warmup_steps + length(inner)?),
+        SchedulerConfig::Sequential {
+            schedules,
+            milestones,
+        } => Some(milestones.last().copied().unwrap_or(0) + length(schedules.last()?)?),
+        SchedulerConfig::Chained { schedules } => schedules.iter().filter_map(length).max(),
+        _ => None,
+    }
+}
+
+/// The arguments of a call, taken one by one so any left over are reported.
+struct Args<'a> {
+    call: &'a Expr,
+    name: &'a str,
+    positional: Vec<&'a Expr>,
+    keywords: Vec<(&'a str, &'a Range<usize>, &'a Expr, bool)>,
+    /// Positional arguments asked for so far.
+    taken: usize,
+}
+
+impl<'a> Args<'a> {
+    fn new(call: &'a Expr, name: &'a str, args: &'a [Arg]) -> Result<Self, SchedulerError> {
+        let mut positional = Vec::new();
+        let mut keywords: Vec<(&str, &Range<usize>, &Expr, bool)> = Vec::new();
+
+        for arg in args {
+            match &arg.keyword {
+                Some((keyword, span)) if keywords.iter().any(|(other, ..)| other == keyword) => {
+                    return Err(parse_error(
+                        format!("`{keyword}` is given twice"),
+                        span.clone(),
+                    ))
+                }
+                Some((keyword, span)) => keywords.push((keyword, span, &arg.value, false)),
+                None if !keywords.is_empty() => {
+                    return Err(parse_error(
+                        "positional arguments have to come before keyword arguments",
+                        arg.value.span.clone(),
+                    ))
+                }
+                None => positional.push(&arg.value),
+            }
+        }
+
+        Ok(Args {
+            call,
+            name,
+            positional,
+            keywords,
+            taken: 0,
+        })
+    }
+
+    /// The argument at `index`, if it's given positionally, or else the one
+    /// named `keyword`.
+    fn get(
+        &mut self,
+        index: Option<usize>,
+        keyword: &str,
+    ) -> Result<Option<&'a Expr>, SchedulerError> {
+        let named = self.keywords.iter_mut().find(|(name, ..)| *name == keyword);
+        let positional = index.and_then(|index| {
+            self.taken = self.taken.max(index + 1);
+            self.positional.get(index).copied()
+        });
+
+        match (positional, named) {
+            (Some(_), Some((_, span, ..))) => Err(parse_error(
+                format!("`{keyword}` is given twice"),
+                (*span).clone(),
+            )),
+            (Some(expr), None) => Ok(Some(expr)),
+            (None, Some((_, _, expr, used))) => {
+                *used = true;
+                Ok(Some(*expr))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn required<T>(
+        &mut self,
+        index: usize,
+        keyword: &str,
+        parse: impl FnOnce(&'a Expr) -> Result<T, SchedulerError>,
+    ) -> Result<T, SchedulerError> {
+        match self.get(Some(index), keyword)? {
+            Some(expr) => parse(expr),
+            None => Err(self.missing(keyword)),
+        }
+    }
+
+    /// A keyword only argument.
+    fn keyword<T>(
+        &mut self,
+        keyword: &str,
+        parse: impl FnOnce(&'a Expr) -> Result<T, SchedulerError>,
+    ) -> Result<Option<T>, SchedulerError> {
+        self.get(None, keyword)?.map(parse).transpose()
+    }
+
+    /// An optional argument that can also be given at `index`.
+    fn optional<T>(
+        &mut self,
+        index: usize,
+        keyword: &str,
+        parse: impl FnOnce(&'a Expr) -> Result<T, SchedulerError>,
+    ) -> Result<Option<T>, SchedulerError> {
+        self.get(Some(index), keyword)?.map(parse).transpose()
+    }
+
+    /// The lr of a schedule that gives a shape, 1 by default.
+    fn lr(&mut self) -> Result<f64, SchedulerError> {
+        Ok(self.keyword("lr", number)?.unwrap_or(1.))
+    }
+
+    /// Every positional argument from `index` on.
+    fn rest(&mut self, index: usize) -> &[&'a Expr] {
+        self.taken = self.taken.max(self.positional.len());
+        self.positional.get(index..).unwrap_or_default()
+    }
+
+    fn missing(&self, keyword: &str) -> SchedulerError {
+        parse_error(
+            format!("`{}` is missing `{keyword}`", self.name),
+            self.call.span.clone(),
+        )
+    }
+
+    /// Fails on any argument that wasn't taken.
+    fn finish(self) -> Result<(), SchedulerError> {
+        if let Some(extra) = self.positional.get(self.taken) {
+            return Err(parse_error(
+                format!(
+                    "`{}` takes {} positional argument{}",
+                    self.name,
+                    self.taken,
+                    if self.taken == 1 { "" } else { "s" }
+                ),
+                extra.span.clone(),
+            ));
+        }
+        match self.keywords.iter().find(|(.., used)| !used) {
+            Some((keyword, span, ..)) => Err(parse_error(
+                format!("`{}` has no argument `{keyword}`", self.name),
+                (*span).clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The config of a call, and the spans of the schedules nested in it.
+fn call(
+    expr: &Expr,
+    name: &str,
+    args: &[Arg],
+) -> Result<(SchedulerConfig, Vec<Spans>), SchedulerError> {
+    let mut args = Args::new(expr, name, args)?;
+    let mut nested = Vec::new();
+
+    let config =
+        match name {
+            "constant" => SchedulerConfig::Constant {
+                lr: args.required(0, "lr", number)?,
+            },
+            "cosine" => SchedulerConfig::CosineAnnealing {
+                max_step: args.required(0, "max_step", integer)?,
+                eta_min: args.keyword("min", number)?.unwrap_or(0.),
+                lr: args.lr()?,
+            },
+            "cosine_restarts" => SchedulerConfig::CosineAnnealingWarmRestarts {
+                t_0: args.required(0, "t_0", integer)?,
+                t_mult: args.keyword("t_mult", integer)?.unwrap_or(1),
+                eta_min: args.keyword("min", number)?.unwrap_or(0.),
+                cycle_decay: args.keyword("cycle_decay", number)?,
+                lr: args.lr()?,
+            },
+            "step" => SchedulerConfig::StepLr {
+                step_size: args.required(0, "step_size", integer)?,
+                gamma: args.required(1, "gamma", number)?,
+                steps_per_epoch: args.keyword("steps_per_epoch", integer)?,
+                lr: args.lr()?,
+            },
+            "multistep" => SchedulerConfig::MultiStepLr {
+                milestones: args.required(0, "milestones", |expr| list(expr, integer))?,
+                gamma: args.required(1, "gamma", number)?,
+                steps_per_epoch: args.keyword("steps_per_epoch", integer)?,
+                lr: args.lr()?,
+            },
+            "exp" => SchedulerConfig::ExponentialLr {
+                gamma: args.required(0, "gamma", number)?,
+                lr: args.lr()?,
+            },
+            "poly" | "linear" => SchedulerConfig::PolynomialLr {
+                total_steps: args.required(0, "total_steps", integer)?,
+                power: match name {
+                    "poly" => args.keyword("power", number)?.unwrap_or(1.),
+                    _ => 1.,
+                },
+                end_lr: args.keyword("end_lr", number)?.unwrap_or(0.),
+                lr: args.lr()?,
+            },
+            "cyclic" => SchedulerConfig::CyclicLr {
+                base_lr: args.required(0, "base_lr", number)?,
+                max_lr: args.required(1, "max_lr", number)?,
+                step_size_up: args.keyword("step_size_up", integer)?,
+                step_size_down: args.keyword("step_size_down", integer)?,
+                mode: args.keyword("mode", cyclic_mode)?,
+                cycle_momentum: args.keyword("cycle_momentum", boolean)?,
+                base_momentum: args.keyword("base_momentum", number)?,
+                max_momentum: args.keyword("max_momentum", number)?,
+            },
+            "one_cycle" => SchedulerConfig::OneCycle {
+                max_lr: args.required(0, "max_lr", number)?,
+                total_steps: args.optional(1, "total_steps", integer)?,
+                epochs: args.keyword("epochs", integer)?,
+                steps_per_epoch: args.keyword("steps_per_epoch", integer)?,
+                pct_start: args.keyword("pct_start", number)?,
+                anneal_strategy: args.keyword("anneal_strategy", anneal)?,
+                phase_anneals: args.keyword("phase_anneals", |expr| {
+                    list(expr, |expr| pair(expr, integer, anneal))
+                })?,
+                cycle_momentum: args.keyword("cycle_momentum", boolean)?,
+                base_momentum: args.keyword("base_momentum", number)?,
+                max_momentum: args.keyword("max_momentum", number)?,
+                div_factor: args.keyword("div_factor", number)?,
+                final_div_factor: args.keyword("final_div_factor", number)?,
+                three_phase: args.keyword("three_phase", boolean)?,
+            },
+            "inverse_sqrt" => SchedulerConfig::InverseSqrt {
+                warmup_steps: args.optional(0, "warmup_steps", integer)?,
+                d_model: args.keyword("d_model", integer)?,
+                linear_warmup: args.keyword("linear_warmup", boolean)?,
+                timescale: args.keyword("timescale", integer)?,
+                cooldown: args.keyword("cooldown", |expr| pair(expr, integer, integer))?,
+                lr: args.lr()?,
+            },
+            "wsd" => SchedulerConfig::Wsd {
+                warmup_steps: args.optional(0, "warmup_steps", integer)?,
+                min_lr: args.keyword("min", number)?,
+                decay: args.keyword("decay", anneal)?,
+                cooldown: args.keyword("cooldown", |expr| pair(expr, integer, integer))?,
+                lr: args.lr()?,
+            },
+            "phases" => SchedulerConfig::Phases {
+                phases: args
+                    .rest(0)
+                    .iter()
+                    .map(|expr| phase(expr))
+                    .collect::<Result<_, _>>()?,
+                total_steps: args.keyword("total_steps", integer)?,
+            },
+            "plateau" => SchedulerConfig::ReduceLrOnPlateau {
+                mode: args.keyword("mode", plateau_mode)?,
+                factor: args.keyword("factor", number)?,
+                patience: args.keyword("patience", integer)?,
+                threshold: args.keyword("threshold", number)?,
+                threshold_mode: args.keyword("threshold_mode", threshold_mode)?,
+                cooldown: args.keyword("cooldown", integer)?,
+                min_lr: args.keyword("min", number)?,
+                eps: args.keyword("eps", number)?,
+                lr: args.lr()?,
+            },
+            "hf" => SchedulerConfig::Hf {
+                name: args.required(0, "name", scheduler_type)?,
+                warmup_steps: args.optional(1, "warmup_steps", integer)?,
+                total_steps: args.optional(2, "total_steps", integer)?,
+                lr: args.lr()?,
+                kwargs: SchedulerKwargs {
+                    num_cycles: args.keyword("num_cycles", number)?,
+                    power: args.keyword("power", number)?,
+                    lr_end: args.keyword("lr_end", number)?,
+                    timescale: args.keyword("timescale", integer)?,
+                    min_lr: args.keyword("min_lr", number)?,
+                    min_lr_rate: args.keyword("min_lr_rate", number)?,
+                    num_stable_steps: args.keyword("num_stable_steps", integer)?,
+                    num_decay_steps: args.keyword("num_decay_steps", integer)?,
+                    warmup_type: args.keyword("warmup_type", wsd_shape)?,
+                    decay_type: args.keyword("decay_type", wsd_shape)?,
+                    min_lr_ratio: args.keyword("min_lr_ratio", number)?,
+                    mode: args.keyword("mode", plateau_mode)?,
+                    factor: args.keyword("factor", number)?,
+                    patience: args.keyword("patience", integer)?,
+                    threshold: args.keyword("threshold", number)?,
+                    threshold_mode: args.keyword("threshold_mode", threshold_mode)?,
+                    cooldown: args.keyword("cooldown", integer)?,
+                    eps: args.keyword("eps", number)?,
+                },
+            },
+            "sequential" => {
+                let (schedules, spans) = schedules(args.rest(0))?;
+                nested = spans;
+                SchedulerConfig::Sequential {
+                    schedules,
+                    milestones: match args.keyword("milestones", |expr| list(expr, integer))? {
+                        Some(milestones) => milestones,
+                        None => return Err(args.missing("milestones")),
+                    },
+                }
+            }
+            "chained" => {
+                let (schedules, spans) = schedules(args.rest(0))?;
+                nested = spans;
+                SchedulerConfig::Chained { schedules }
+            }
+            "warmup" => return Err(parse_error(
+                "`warmup` needs a schedule to warm up into, e.g. `warmup(500) >> cosine(10000)`",
+                expr.span.clone(),
+            )),
+            _ => {
+                return Err(parse_error(
+                    format!("unknown schedule `{name}`"),
+                    expr.span.start..expr.span.start + name.len(),
+                ))
+            }
+        };
+
+    args.finish()?;
+    Ok((config, nested))
+}
+
+/// `expr` is the `warmup(..)` call on the left of `>>`, which errors in the
+/// warmup itself point at.
+fn warmup(
+    expr: &Expr,
+    args: &[Arg],
+    (inner, spans): (SchedulerConfig, Spans),
+) -> Result<(SchedulerConfig, Spans), SchedulerError> {
+    let mut args = Args::new(expr, "warmup", args)?;
+    // The strategy can lead, as in `warmup(linear, 500)`.
+    let (anneal, warmup_steps) = if args.positional.len() > 1 {
+        (
+            Some(args.required(0, "anneal", anneal)?),
+            args.required(1, "warmup_steps", integer)?,
+        )
+    } else {
+        (
+            args.keyword("anneal", anneal)?,
+            args.required(0, "warmup_steps", integer)?,
+        )
+    };
+    let config = SchedulerConfig::Warmup {
+        warmup_steps,
+        start_factor: args.keyword("start_factor", number)?,
+        anneal,
+        momentum: args.keyword("momentum", |expr| pair(expr, number, number))?,
+        inner: Box::new(inner),
+    };
+
+    args.finish()?;
+    Ok((
+        config,
+        Spans {
+            span: expr.span.clone(),
+            nested: vec![spans],
+        },
+    ))
+}
+
+fn phase(expr: &Expr) -> Result<PhaseSpec, SchedulerError> {
+    let ExprKind::Call { name, args } = &expr.kind else {
+        return Err(expected("a `phase(..)`", expr));
+    };
+    if name != "phase" {
+        return Err(expected("a `phase(..)`", expr));
+    }
+
+    let mut args = Args::new(expr, name, args)?;
+    let mut phase = PhaseSpec::new(
+        args.required(0, "duration", duration)?,
+        args.required(1, "start_lr", number)?,
+        args.required(2, "end_lr", number)?,
+    );
+    if let Some(anneal) = args.keyword("anneal", anneal)? {
+        phase = phase.anneal(anneal);
+    }
+    if let Some((start, end)) = args.keyword("momentum", |expr| pair(expr, number, number))? {
+        phase = phase.momentum(start, end);
+    }
+
+    args.finish()?;
+    Ok(phase)
+}
+
+fn expected(what: &str, expr: &Expr) -> SchedulerError {
+    parse_error(
+        format!("expected {what}, found {}", expr.describe()),
+        expr.span.clone(),
+    )
+}
+
+fn number(expr: &Expr) -> Result<f64, SchedulerError> {
+    match &expr.kind {
+        ExprKind::Number(text) => text.parse().map_err(|_| expected("a number", expr)),
+        _ => Err(expected("a number", expr)),
+    }
+}
+
+fn integer(expr: &Expr) -> Result<usize, SchedulerError> {
+    match &expr.kind {
+        ExprKind::Number(text) => text.parse().map_err(|_| expected("a whole number", expr)),
+        _ => Err(expected("a whole number", expr)),
+    }
+}
+
+/// Whole numbers are steps and anything else a fraction of the total.
+fn duration(expr: &Expr) -> Result<Duration, SchedulerError> {
+    match integer(expr) {
+        Ok(steps) => Ok(Duration::Steps(steps)),
+        Err(_) => Ok(Duration::Fraction(number(expr)?)),
+    }
+}
+
+fn list<'a, T>(
+    expr: &'a Expr,
+    item: impl Fn(&'a Expr) -> Result<T, SchedulerError>,
+) -> Result<Vec<T>, SchedulerError> {
+    match &expr.kind {
+        ExprKind::List(items) => items.iter().map(item).collect(),
+        _ => Err(expected("a list", expr)),
+    }
+}
+
+fn pair<'a, A, B>(
+    expr: &'a Expr,
+    first: impl FnOnce(&'a Expr) -> Result<A, SchedulerError>,
+    second: impl FnOnce(&'a Expr) -> Result<B, SchedulerError>,
+) -> Result<(A, B), SchedulerError> {
+    match &expr.kind {
+        ExprKind::List(items) if items.len() == 2 => Ok((first(&items[0])?, second(&items[1])?)),
+        _ => Err(expected("a list of two", expr)),
+    }
+}
+
+/// The name of an identifier, e.g. a mode, with `options` for the error.
+fn ident<'a>(expr: &'a Expr, options: &str) -> Result<&'a str, SchedulerError> {
+    match &expr.kind {
+        ExprKind::Ident(name) => Ok(name),
+        _ => Err(expected(options, expr)),
+    }
+}
+
+fn boolean(expr: &Expr) -> Result<bool, SchedulerError> {
+    match ident(expr, "`true` or `false`")? {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(expected("`true` or `false`", expr)),
+    }
+}
+
+fn anneal(expr: &Expr) -> Result<Anneal, SchedulerError> {
+    const OPTIONS: &str =
+        "`cos`, `linear`, `exponential`, `polynomial(power)`, `one_minus_sqrt` or `constant`";
+
+    if let ExprKind::Call { name, args } = &expr.kind {
+        if name == "polynomial" {
+            let mut args = Args::new(expr, name, args)?;
+            let power = args.required(0, "power", number)?;
+            args.finish()?;
+            return Ok(Anneal::Polynomial { power });
+        }
+    }
+
+    match ident(expr, OPTIONS)? {
+        "cos" => Ok(Anneal::Cos),
+        "linear" => Ok(Anneal::Linear),
+        "exponential" => Ok(Anneal::Exponential),
+        "one_minus_sqrt" => Ok(Anneal::OneMinusSqrt),
+        "constant" => Ok(Anneal::Constant),
+        _ => Err(expected(OPTIONS, expr)),
+    }
+}
+
+fn cyclic_mode(expr: &Expr) -> Result<CyclicMode, SchedulerError> {
+    const OPTIONS: &str = "`triangular`, `triangular2` or `exp_range(gamma)`";
+
+    if let ExprKind::Call { name, args } = &expr.kind {
+        if name == "exp_range" {
+            let mut args = Args::new(expr, name, args)?;
+            let gamma = args.required(0, "gamma", number)?;
+            args.finish()?;
+            return Ok(CyclicMode::ExpRange { gamma });
+        }
+    }
+
+    match ident(expr, OPTIONS)? {
+        "triangular" => Ok(CyclicMode::Triangular),
+        "triangular2" => Ok(CyclicMode::Triangular2),
+        _ => Err(expected(OPTIONS, expr)),
+    }
+}
+
+fn plateau_mode(expr: &Expr) -> Result<PlateauMode, SchedulerError> {
+    match ident(expr, "`min` or `max`")? {
+        "min" => Ok(PlateauMode::Min),
+        "max" => Ok(PlateauMode::Max),
+        _ => Err(expected("`min` or `max`", expr)),
+    }
+}
+
+fn threshold_mode(expr: &Expr) -> Result<ThresholdMode, SchedulerError> {
+    match ident(expr, "`rel` or `abs`")? {
+        "rel" => Ok(ThresholdMode::Rel),
+        "abs" => Ok(ThresholdMode::Abs),
+        _ => Err(expected("`rel` or `abs`", expr)),
+    }
+}
+
+fn wsd_shape(expr: &Expr) -> Result<WsdShape, SchedulerError> {
+    const OPTIONS: &str = "`linear`, `cosine` or `one_minus_sqrt`";
+
+    match ident(expr, OPTIONS)? {
+        "linear" => Ok(WsdShape::Linear),
+        "cosine" => Ok(WsdShape::Cosine),
+        "one_minus_sqrt" => Ok(WsdShape::OneMinusSqrt),
+        _ => Err(expected(OPTIONS, expr)),
+    }
+}
+
+fn scheduler_type(expr: &Expr) -> Result<SchedulerType, SchedulerError> {
+    ident(expr, "a HuggingFace schedule name")?
+        .parse()
+        .map_err(|err: SchedulerError| parse_error(err.to_string(), expr.span.clone()))
+}
+
+/// How tightly each form binds, loosest first.
+const THEN: u8 = 0;
+const TIMES: u8 = 1;
+const ATOM: u8 = 2;
+
+fn precedence(config: &SchedulerConfig) -> u8 {
+    match config {
+        SchedulerConfig::Warmup { .. } => THEN,
+        SchedulerConfig::Sequential {
+            schedules,
+            milestones,
+        } if back_to_back(schedules, milestones) => THEN,
+        SchedulerConfig::Chained { schedules } if schedules.len() > 1 => TIMES,
+        _ => ATOM,
+    }
+}
+
+/// Whether each schedule starts when the one before it ends, so they can be
+/// written `a >> b`.
+fn back_to_back(schedules: &[SchedulerConfig], milestones: &[usize]) -> bool {
+    let mut start = 0;
+
+    schedules.len() > 1
+        && milestones.len() + 1 == schedules.len()
+        && schedules
+            .iter()
+            .zip(milestones)
+            .all(|(schedule, &milestone)| {
+                let ends = length(schedule).map(|length| start + length) == Some(milestone);
+                start = milestone;
+                ends
+            })
+}
+
+/// Formats as a schedule expression, which parses back to an equal config.
+/// Defaults are left out, and custom anneal strategies and cyclic modes are
+/// written as `custom`, which can't be parsed.
+impl fmt::Display for SchedulerConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_schedule(f, self, THEN)
+    }
+}
+
+fn write_schedule(f: &mut Formatter<'_>, config: &SchedulerConfig, min: u8) -> fmt::Result {
+    if precedence(config) < min {
+        f.write_str("(")?;
+        write_schedule(f, config, THEN)?;
+        return f.write_str(")");
+    }
+
+    match config {
+        SchedulerConfig::Constant { lr } => lr.write(f),
+        SchedulerConfig::OneCycle {
+            max_lr,
+            total_steps,
+            epochs,
+            steps_per_epoch,
+            pct_start,
+            anneal_strategy,
+            phase_anneals,
+            cycle_momentum,
+            base_momentum,
+            max_momentum,
+            div_factor,
+            final_div_factor,
+            three_phase,
+        } => {
+            let mut call = Call::new(f, "one_cycle")?;
+            call.arg(max_lr)?;
+            if let Some(total_steps) = total_steps {
+                call.arg(total_steps)?;
+            }
+            call.keyword("epochs", epochs)?;
+            call.keyword("steps_per_epoch", steps_per_epoch)?;
+            call.keyword("pct_start", pct_start)?;
+            call.keyword("anneal_strategy", anneal_strategy)?;
+            call.keyword("phase_anneals", phase_anneals)?;
+            call.keyword("cycle_momentum", cycle_momentum)?;
+            call.keyword("base_momentum", base_momentum)?;
+            call.keyword("max_momentum", max_momentum)?;
+            call.keyword("div_factor", div_factor)?;
+            call.keyword("final_div_factor", final_div_factor)?;
+            call.keyword("three_phase", three_phase)?;
+            call.finish()
+        }
+        SchedulerConfig::CosineAnnealing {
+            lr,
+            max_step,
+            eta_min,
+        } => {
+            let mut call = Call::new(f, "cosine")?;
+            call.arg(max_step)?;
+            call.keyword("min", &non_default(*eta_min, 0.))?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::CosineAnnealingWarmRestarts {
+            lr,
+            t_0,
+            t_mult,
+            eta_min,
+            cycle_decay,
+        } => {
+            let mut call = Call::new(f, "cosine_restarts")?;
+            call.arg(t_0)?;
+            call.keyword("t_mult", &non_default(*t_mult, 1))?;
+            call.keyword("min", &non_default(*eta_min, 0.))?;
+            call.keyword("cycle_decay", cycle_decay)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::StepLr {
+            lr,
+            step_size,
+            gamma,
+            steps_per_epoch,
+        } => {
+            let mut call = Call::new(f, "step")?;
+            call.arg(step_size)?;
+            call.arg(gamma)?;
+            call.keyword("steps_per_epoch", steps_per_epoch)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::MultiStepLr {
+            lr,
+            milestones,
+            gamma,
+            steps_per_epoch,
+        } => {
+            let mut call = Call::new(f, "multistep")?;
+            call.arg(milestones)?;
+            call.arg(gamma)?;
+            call.keyword("steps_per_epoch", steps_per_epoch)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::ExponentialLr { lr, gamma } => {
+            let mut call = Call::new(f, "exp")?;
+            call.arg(gamma)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::PolynomialLr {
+            lr,
+            end_lr,
+            total_steps,
+            power,
+        } => {
+            let mut call = Call::new(f, if *power == 1. { "linear" } else { "poly" })?;
+            call.arg(total_steps)?;
+            call.keyword("power", &non_default(*power, 1.))?;
+            call.keyword("end_lr", &non_default(*end_lr, 0.))?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::CyclicLr {
+            base_lr,
+            max_lr,
+            step_size_up,
+            step_size_down,
+            mode,
+            cycle_momentum,
+            base_momentum,
+            max_momentum,
+        } => {
+            let mut call = Call::new(f, "cyclic")?;
+            call.arg(base_lr)?;
+            call.arg(max_lr)?;
+            call.keyword("step_size_up", step_size_up)?;
+            call.keyword("step_size_down", step_size_down)?;
+            call.keyword("mode", mode)?;
+            call.keyword("cycle_momentum", cycle_momentum)?;
+            call.keyword("base_momentum", base_momentum)?;
+            call.keyword("max_momentum", max_momentum)?;
+            call.finish()
+        }
+        SchedulerConfig::InverseSqrt {
+            lr,
+            d_model,
+            warmup_steps,
+            linear_warmup,
+            timescale,
+            cooldown,
+        } => {
+            let mut call = Call::new(f, "inverse_sqrt")?;
+            if let Some(warmup_steps) = warmup_steps {
+                call.arg(warmup_steps)?;
+            }
+            call.keyword("d_model", d_model)?;
+            call.keyword("linear_warmup", linear_warmup)?;
+            call.keyword("timescale", timescale)?;
+            call.keyword("cooldown", cooldown)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::Wsd {
+            lr,
+            min_lr,
+            warmup_steps,
+            decay,
+            cooldown,
+        } => {
+            let mut call = Call::new(f, "wsd")?;
+            if let Some(warmup_steps) = warmup_steps {
+                call.arg(warmup_steps)?;
+            }
+            call.keyword("min", min_lr)?;
+            call.keyword("decay", decay)?;
+            call.keyword("cooldown", cooldown)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::Phases {
+            total_steps,
+            phases,
+        } => {
+            let mut call = Call::new(f, "phases")?;
+            for phase in phases {
+                call.arg(phase)?;
+            }
+            call.keyword("total_steps", total_steps)?;
+            call.finish()
+        }
+        SchedulerConfig::ReduceLrOnPlateau {
+            lr,
+            mode,
+            factor,
+            patience,
+            threshold,
+            threshold_mode,
+            cooldown,
+            min_lr,
+            eps,
+        } => {
+            let mut call = Call::new(f, "plateau")?;
+            call.keyword("mode", mode)?;
+            call.keyword("factor", factor)?;
+            call.keyword("patience", patience)?;
+            call.keyword("threshold", threshold)?;
+            call.keyword("threshold_mode", threshold_mode)?;
+            call.keyword("cooldown", cooldown)?;
+            call.keyword("min", min_lr)?;
+            call.keyword("eps", eps)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.finish()
+        }
+        SchedulerConfig::Hf {
+            name,
+            lr,
+            warmup_steps,
+            total_steps,
+            kwargs,
+        } => {
+            let mut call = Call::new(f, "hf")?;
+            call.arg(name)?;
+            call.keyword("warmup_steps", warmup_steps)?;
+            call.keyword("total_steps", total_steps)?;
+            call.keyword("lr", &non_default(*lr, 1.))?;
+            call.keyword("num_cycles", &kwargs.num_cycles)?;
+            call.keyword("power", &kwargs.power)?;
+            call.keyword("lr_end", &kwargs.lr_end)?;
+            call.keyword("timescale", &kwargs.timescale)?;
+            call.keyword("min_lr", &kwargs.min_lr)?;
+            call.keyword("min_lr_rate", &kwargs.min_lr_rate)?;
+            call.keyword("num_stable_steps", &kwargs.num_stable_steps)?;
+            call.keyword("num_decay_steps", &kwargs.num_decay_steps)?;
+            call.keyword("warmup_type", &kwargs.warmup_type)?;
+            call.keyword("decay_type", &kwargs.decay_type)?;
+            call.keyword("min_lr_ratio", &kwargs.min_lr_ratio)?;
+            call.keyword("mode", &kwargs.mode)?;
+            call.keyword("factor", &kwargs.factor)?;
+            call.keyword("patience", &kwargs.patience)?;
+            call.keyword("threshold", &kwargs.threshold)?;
+            call.keyword("threshold_mode", &kwargs.threshold_mode)?;
+            call.keyword("cooldown", &kwargs.cooldown)?;
+            call.keyword("eps", &kwargs.eps)?;
+            call.finish()
+        }
+        SchedulerConfig::Warmup {
+            warmup_steps,
+            start_factor,
+            anneal,
+            momentum,
+            inner,
+        } => {
+            let mut call = Call::new(f, "warmup")?;
+            if let Some(anneal) = anneal {
+                call.arg(anneal)?;
+            }
+            call.arg(warmup_steps)?;
+            call.keyword("start_factor", start_factor)?;
+            call.keyword("momentum", momentum)?;
+            call.finish()?;
+            f.write_str(" >> ")?;
+            write_schedule(f, inner, TIMES)
+        }
+        SchedulerConfig::Sequential {
+            schedules,
+            milestones,
+        } if back_to_back(schedules, milestones) => {
+            for (index, schedule) in schedules.iter().enumerate() {
+                match (index, schedule) {
+                    // A leading warmup takes the next schedule as its inner
+                    // one, so doesn't need parentheses.
+                    (0, SchedulerConfig::Warmup { .. }) => write_schedule(f, schedule, THEN)?,
+                    (0, _) => write_schedule(f, schedule, TIMES)?,
+                    _ => {
+                        f.write_str(" >> ")?;
+                        write_schedule(f, schedule, TIMES)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        SchedulerConfig::Sequential {
+            schedules,
+            milestones,
+        } => {
+            let mut call = Call::new(f, "sequential")?;
+            for schedule in schedules {
+                call.arg(schedule)?;
+            }
+            call.keyword("milestones", &Some(milestones))?;
+            call.finish()
+        }
+        SchedulerConfig::Chained { schedules } if schedules.len() > 1 => {
+            for (index, schedule) in schedules.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(" * ")?;
+                }
+                write_schedule(f, schedule, ATOM)?;
+            }
+            Ok(())
+        }
+        SchedulerConfig::Chained { schedules } => {
+            let mut call = Call::new(f, "chained")?;
+            for schedule in schedules {
+                call.arg(schedule)?;
+            }
+            call.finish()
+        }
+    }
+}
+
+/// Writes `name(arg, .., keyword=value, ..)`.
+struct Call<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    first: bool,
+}
+
+impl<'a, 'b> Call<'a, 'b> {
+    fn new(f: &'a mut Formatter<'b>, name: &str) -> Result<Self, fmt::Error> {
+        write!(f, "{name}(")?;
+        Ok(Call { f, first: true })
+    }
+
+    fn separate(&mut self) -> fmt::Result {
+        if !std::mem::take(&mut self.first) {
+            self.f.write_str(", ")?;
+        }
+        Ok(())
+    }
+
+    fn arg(&mut self, value: &impl Value) -> fmt::Result {
+        self.separate()?;
+        value.write(self.f)
+    }
+
+    /// Skipped when `value` is `None`.
+    fn keyword(&mut self, name: &str, value: &Option<impl Value>) -> fmt::Result {
+        match value {
+            Some(value) => {
+                self.separate()?;
+                write!(self.f, "{name}=")?;
+                value.write(self.f)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn finish(self) -> fmt::Result {
+        self.f.write_str(")")
+    }
+}
+
+/// An argument in a schedule expression.
+trait Value {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result;
+}
+
+impl<T: Value + ?Sized> Value for &T {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).write(f)
+    }
+}
+
+/// Always written with a decimal point or exponent, so it reads back as a
+/// float.
+impl Value for f64 {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Value for usize {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Value for bool {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl<A: Value, B: Value> Value for (A, B) {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        self.0.write(f)?;
+        f.write_str(", ")?;
+        self.1.write(f)?;
+        f.write_str("]")
+    }
+}
+
+impl<T: Value> Value for Vec<T> {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (index, item) in self.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            item.write(f)?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl Value for Anneal {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Anneal::Cos => f.write_str("cos"),
+            Anneal::Linear => f.write_str("linear"),
+            Anneal::Exponential => f.write_str("exponential"),
+            Anneal::Polynomial { power } => {
+                f.write_str("polynomial(")?;
+                power.write(f)?;
+                f.write_str(")")
+            }
+            Anneal::OneMinusSqrt => f.write_str("one_minus_sqrt"),
+            Anneal::Constant => f.write_str("constant"),
+            Anneal::Custom(_) => f.write_str("custom"),
+        }
+    }
+}
+
+impl Value for CyclicMode {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CyclicMode::Triangular => f.write_str("triangular"),
+            CyclicMode::Triangular2 => f.write_str("triangular2"),
+            CyclicMode::ExpRange { gamma } => {
+                f.write_str("exp_range(")?;
+                gamma.write(f)?;
+                f.write_str(")")
+            }
+            CyclicMode::Custom { .. } => f.write_str("custom"),
+        }
+    }
+}
+
+impl Value for PlateauMode {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlateauMode::Min => f.write_str("min"),
+            PlateauMode::Max => f.write_str("max"),
+        }
+    }
+}
+
+impl Value for ThresholdMode {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ThresholdMode::Rel => f.write_str("rel"),
+            ThresholdMode::Abs => f.write_str("abs"),
+        }
+    }
+}
+
+impl Value for WsdShape {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WsdShape::Linear => f.write_str("linear"),
+            WsdShape::Cosine => f.write_str("cosine"),
+            WsdShape::OneMinusSqrt => f.write_str("one_minus_sqrt"),
+        }
+    }
+}
+
+impl Value for SchedulerType {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Value for Duration {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Duration::Steps(steps) => steps.write(f),
+            Duration::Fraction(fraction) => fraction.write(f),
+        }
+    }
+}
+
+impl Value for PhaseSpec {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut call = Call::new(f, "phase")?;
+        call.arg(&self.duration)?;
+        call.arg(&self.lr.0)?;
+        call.arg(&self.lr.1)?;
+        call.keyword("anneal", &non_default(&self.anneal, &Anneal::Cos))?;
+        call.keyword("momentum", &self.momentum)?;
+        call.finish()
+    }
+}
+
+impl Value for SchedulerConfig {
+    fn write(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_schedule(f, self, THEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Anneal, CosineAnnealing, Duration, OneCycle, PhaseSpec, SchedulerConfig, SchedulerError,
+        SchedulerKwargs, SchedulerType, StepLr, ToConfig, Warmup,
+    };
+
+    fn round_trip(config: &SchedulerConfig) {
+        let source = config.to_string();
+
+        assert_eq!(
+            &source.parse::<SchedulerConfig>().unwrap(),
+            config,
+            "{source}"
+        );
+    }
+
+    fn span(source: &str) -> (String, std::ops::Range<usize>) {
+        match source.parse::<SchedulerConfig>().unwrap_err() {
+            SchedulerError::Parse { message, span } => (message, span),
+            err => panic!("{err}"),
+        }
+    }
+
+    #[test]
+    fn dsl_parse_test() {
+        let source = "warmup(linear, 500) >> cosine(10000, min=1e-6) * 0.5";
+        let config: SchedulerConfig = source.parse().unwrap();
+
+        assert_eq!(
+            config,
+            SchedulerConfig::Warmup {
+                warmup_steps: 500,
+                start_factor: None,
+                anneal: Some(Anneal::Linear),
+                momentum: None,
+                inner: Box::new(SchedulerConfig::Chained {
+                    schedules: vec![
+                        SchedulerConfig::CosineAnnealing {
+                            lr: 1.,
+                            max_step: 10000,
+                            eta_min: 1e-6,
+                        },
+                        SchedulerConfig::Constant { lr: 0.5 },
+                    ],
+                }),
+            }
+        );
+        assert_eq!(config.to_string(), source);
+    }
+
+    #[test]
+    fn dsl_sequence_test() {
+        let config: SchedulerConfig =
+            "linear(100, end_lr=0.5) >> cosine(50, min=0.1, lr=0.5) >> 0.1"
+                .parse()
+                .unwrap();
+
+        match &config {
+            SchedulerConfig::Sequential { milestones, .. } => assert_eq!(milestones, &[100, 150]),
+            config => panic!("{config:?}"),
+        }
+        round_trip(&config);
+
+        let nested: SchedulerConfig = "(cosine(10) >> cosine(20)) >> exp(0.9)".parse().unwrap();
+
+        match &nested {
+            SchedulerConfig::Sequential {
+                schedules,
+                milestones,
+            } => {
+                assert_eq!(schedules.len(), 2);
+                assert_eq!(milestones, &[30]);
+            }
+            config => panic!("{config:?}"),
+        }
+        assert_eq!(nested.to_string(), "(cosine(10) >> cosine(20)) >> exp(0.9)");
+    }
+
+    #[test]
+    fn dsl_round_trip_test() {
+        let configs = [
+            "one_cycle(0.001, 1000, pct_start=0.25, phase_anneals=[[1, linear]], three_phase=true)",
+            "one_cycle(0.01, epochs=10, steps_per_epoch=100, anneal_strategy=polynomial(2.0))",
+            "cosine_restarts(10, t_mult=2, min=0.001, cycle_decay=0.5, lr=0.1)",
+            "step(30, 0.1, steps_per_epoch=100, lr=0.1) * exp(0.999)",
+            "multistep([30, 80], 0.1, lr=0.1)",
+            "poly(1000, power=2.0, end_lr=1e-5, lr=0.001)",
+            "cyclic(0.0001, 0.001, step_size_up=500, mode=exp_range(0.99), cycle_momentum=false)",
+            "inverse_sqrt(4000, d_model=512, cooldown=[1000, 100000])",
+            "wsd(100, min=1e-5, decay=one_minus_sqrt, cooldown=[900, 100], lr=0.001) >> 1e-5",
+            "phases(phase(100, 0.0, 1.0, anneal=linear, momentum=[0.95, 0.85]), phase(0.5, 1.0, 0.1), total_steps=1000)",
+            "plateau(mode=max, patience=3, threshold_mode=abs, min=1e-6, lr=0.01)",
+            "hf(cosine_with_min_lr, warmup_steps=100, total_steps=1000, lr=0.001, min_lr_rate=0.1)",
+            "warmup(exponential, 100, start_factor=0.01, momentum=[0.8, 0.9]) >> cosine(100, lr=0.001) >> exp(0.9, lr=0.001)",
+            "sequential(exp(0.9), cosine(10), milestones=[5])",
+            "chained(cosine(10))",
+            "(cosine(10) * 0.5) * (cosine(10) >> 0.2) * -1.0",
+        ];
+
+        for source in configs {
+            let config: SchedulerConfig = source.parse().unwrap();
+
+            assert_eq!(config.to_string(), source);
+            round_trip(&config);
+        }
+
+        round_trip(&SchedulerConfig::Hf {
+            name: SchedulerType::ReduceLrOnPlateau,
+            lr: 0.1,
+            warmup_steps: None,
+            total_steps: None,
+            kwargs: SchedulerKwargs {
+                patience: Some(0),
+                ..Default::default()
+            },
+        });
+        round_trip(&SchedulerConfig::Phases {
+            total_steps: None,
+            phases: vec![
+                PhaseSpec::new(Duration::Steps(10), 1e-3, 1e-4).anneal(Anneal::OneMinusSqrt)
+            ],
+        });
+    }
+
+    #[test]
+    fn dsl_error_test() {
+        assert_eq!(
+            span("cosine(10000, min=1e-6"),
+            (
+                "expected `)` after the arguments, found the end".to_string(),
+                22..22
+            )
+        );
+        assert_eq!(
+            span("warmup(500) >> cosine(10, lr=-0.1)"),
+            ("eta_min must not be greater than lr".to_string(), 15..34)
+        );
+        assert_eq!(
+            span("cosine(10) >> cosine(10) >> cosine(5, lr=-1)"),
+            ("eta_min must not be greater than lr".to_string(), 28..44)
+        );
+        assert_eq!(
+            span("chained(cosine(10), sequential(exp(0.9), cosine(0), milestones=[5]))"),
+            ("max_step must be at least 1".to_string(), 41..50)
+        );
+        assert_eq!(span("cosine(10) * 0.5 * cosine(0)").1, 19..28);
+        assert_eq!(
+            span("exp(0.9) >> cosine(10)"),
+            (
+                "this schedule has no fixed length to switch after, use \
+                 `sequential(.., milestones=[..])` instead"
+                    .to_string(),
+                0..8
+            )
+        );
+        assert_eq!(span("cosin(10)").1, 0..5);
+        assert_eq!(span("cosine(10, mn=0.1)").1, 11..13);
+        assert_eq!(span("cosine(10.5)").1, 7..11);
+        assert_eq!(span("cosine(10) >> warmup(5)").1, 14..23);
+        assert_eq!(span("cosine(10) $").1, 11..12);
+        assert_eq!(
+            span("cosine(10) 0.5").0,
+            "expected `>>`, `*` or the end, found `0.5`"
+        );
+    }
+
+    #[test]
+    fn dsl_display_test() {
+        assert_eq!(
+            CosineAnnealing::new(1e-3, 10000, 1e-6).to_string(),
+            "cosine(10000, min=1e-6, lr=0.001)"
+        );
+        assert_eq!(
+            OneCycle::new(1e-3, 0.9, 25., 10).to_string(),
+            "one_cycle(0.001, 10, base_momentum=0.036000000000000004, max_momentum=0.9, \
+             final_div_factor=1.0)"
+        );
+        assert_eq!(
+            Warmup::new(StepLr::new(0.1, 30, 0.1), 5).to_string(),
+            "warmup(5) >> step(30, 0.1, lr=0.1)"
+        );
+
+        let one_cycle = OneCycle::builder(0.01)
+            .epochs(10, 100)
+            .phase_anneal(1, Anneal::Linear)
+            .build()
+            .unwrap();
+        let config: SchedulerConfig = one_cycle.to_string().parse().unwrap();
+
+        assert_eq!(config, one_cycle.to_config());
+    }
+}
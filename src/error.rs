@@ -0,0 +1,238 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::Hyperparam;
+
+/// Errors returned by the schedulers in this crate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulerError {
+    /// The optimizer has no setting for a hyperparameter the schedule drives.
+    UnsupportedHyperparam {
+        hyperparam: Hyperparam,
+        optimizer: &'static str,
+    },
+    /// A saved state doesn't match the scheduler it's loaded into.
+    StateMismatch { expected: String, found: String },
+    /// Reading or writing a checkpoint file failed.
+    Checkpoint(String),
+    /// An argument is NaN or infinite.
+    NonFinite { name: &'static str, value: f64 },
+    /// An argument that has to be greater than zero isn't.
+    NonPositive { name: &'static str, value: f64 },
+    /// An argument is outside its valid, inclusive, range.
+    OutOfRange {
+        name: &'static str,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    /// A step count that has to be positive is zero.
+    ZeroSteps { name: &'static str },
+    /// A position given in epochs is too far to count in steps.
+    StepOverflow { name: &'static str },
+    /// Two arguments are in the wrong order, e.g. a minimum lr above the
+    /// maximum.
+    Unordered {
+        lower: &'static str,
+        upper: &'static str,
+    },
+    /// An argument that has to be strictly less than another isn't.
+    NotLess {
+        lower: &'static str,
+        upper: &'static str,
+    },
+    /// A required argument wasn't given.
+    Missing { name: &'static str },
+    /// Two arguments were given where only one of them can be.
+    Conflicting {
+        first: &'static str,
+        second: &'static str,
+    },
+    /// A schedule name that isn't recognized.
+    UnknownSchedule(String),
+    /// A phase of the schedule spans no steps, usually because the total
+    /// number of steps is too small.
+    EmptyPhase { index: usize },
+    /// A phase index is past the last phase of the schedule.
+    NoSuchPhase { index: usize },
+    /// A phase anneals exponentially between values that are zero or of
+    /// different signs, which has no geometric path.
+    ExponentialEndpoints { index: usize, start: f64, end: f64 },
+    /// The number of milestones doesn't fit the number of schedules they
+    /// switch between.
+    MilestoneCount { milestones: usize, schedules: usize },
+    /// A list that has to be strictly increasing isn't.
+    Unsorted { name: &'static str },
+    /// A metric couldn't be read, e.g. a tensor that isn't a scalar.
+    Metric(String),
+    /// A scheduler without a closed form was nested in a combinator.
+    NotClosedForm { name: &'static str },
+    /// A nested scheduler config is invalid. `path` leads to it from the
+    /// outermost config, e.g. `schedules[1].inner`.
+    Config {
+        path: String,
+        err: Box<SchedulerError>,
+    },
+    /// A schedule expression couldn't be parsed. `span` is the byte range of
+    /// the offending part of the expression.
+    Parse { message: String, span: Range<usize> },
+    /// Rendering or saving a plot failed.
+    Plot(String),
+    /// The loss or the optimizer step failed during an lr range test.
+    Training(String),
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::UnsupportedHyperparam {
+                hyperparam,
+                optimizer,
+            } => write!(f, "{optimizer} does not support setting {hyperparam}"),
+            SchedulerError::StateMismatch { expected, found } => write!(
+                f,
+                "scheduler state was saved with config {found}, expected {expected}"
+            ),
+            SchedulerError::Checkpoint(err) => write!(f, "checkpoint error: {err}"),
+            SchedulerError::Plot(err) => write!(f, "plot error: {err}"),
+            SchedulerError::Training(err) => write!(f, "training step failed: {err}"),
+            SchedulerError::NonFinite { name, value } => {
+                write!(f, "{name} must be finite, got {value}")
+            }
+            SchedulerError::NonPositive { name, value } => {
+                write!(f, "{name} must be greater than 0, got {value}")
+            }
+            SchedulerError::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } if max.is_infinite() => write!(f, "{name} must be at least {min}, got {value}"),
+            SchedulerError::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(f, "{name} must be in [{min}, {max}], got {value}"),
+            SchedulerError::ZeroSteps { name } => write!(f, "{name} must be at least 1"),
+            SchedulerError::StepOverflow { name } => {
+                write!(f, "{name} is too large to count in steps")
+            }
+            SchedulerError::Unordered { lower, upper } => {
+                write!(f, "{lower} must not be greater than {upper}")
+            }
+            SchedulerError::NotLess { lower, upper } => {
+                write!(f, "{lower} must be less than {upper}")
+            }
+            SchedulerError::Missing { name } => write!(f, "{name} must be set"),
+            SchedulerError::Conflicting { first, second } => {
+                write!(f, "only one of {first} and {second} can be set")
+            }
+            SchedulerError::UnknownSchedule(name) => write!(f, "unknown schedule {name:?}"),
+            SchedulerError::EmptyPhase { index } => write!(
+                f,
+                "phase {index} spans no steps, the schedule needs more total steps"
+            ),
+            SchedulerError::NoSuchPhase { index } => write!(f, "there is no phase {index}"),
+            SchedulerError::ExponentialEndpoints { index, start, end } => write!(
+                f,
+                "phase {index} anneals exponentially from {start} to {end}, \
+                 which need to be non-zero with the same sign"
+            ),
+            SchedulerError::MilestoneCount {
+                milestones,
+                schedules,
+            } => write!(
+                f,
+                "{schedules} schedules need {} milestones, got {milestones}",
+                schedules.saturating_sub(1)
+            ),
+            SchedulerError::Unsorted { name } => write!(f, "{name} must be strictly increasing"),
+            SchedulerError::Metric(err) => write!(f, "invalid metric: {err}"),
+            SchedulerError::NotClosedForm { name } => {
+                write!(f, "{name} has no closed form, so it can't be nested")
+            }
+            SchedulerError::Config { path, err } => write!(f, "{path}: {err}"),
+            SchedulerError::Parse { message, span } => {
+                write!(f, "{message} at {}..{}", span.start, span.end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+pub(crate) fn check_finite(name: &'static str, value: f64) -> Result<f64, SchedulerError> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(SchedulerError::NonFinite { name, value })
+    }
+}
+
+pub(crate) fn check_positive(name: &'static str, value: f64) -> Result<f64, SchedulerError> {
+    check_finite(name, value)?;
+
+    if value > 0. {
+        Ok(value)
+    } else {
+        Err(SchedulerError::NonPositive { name, value })
+    }
+}
+
+/// `max` may be infinite for a lower bound only.
+pub(crate) fn check_range(
+    name: &'static str,
+    value: f64,
+    min: f64,
+    max: f64,
+) -> Result<f64, SchedulerError> {
+    check_finite(name, value)?;
+
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(SchedulerError::OutOfRange {
+            name,
+            value,
+            min,
+            max,
+        })
+    }
+}
+
+pub(crate) fn check_steps(name: &'static str, steps: usize) -> Result<usize, SchedulerError> {
+    if steps == 0 {
+        Err(SchedulerError::ZeroSteps { name })
+    } else {
+        Ok(steps)
+    }
+}
+
+pub(crate) fn check_order(
+    lower: (&'static str, f64),
+    upper: (&'static str, f64),
+) -> Result<(), SchedulerError> {
+    if lower.1 <= upper.1 {
+        Ok(())
+    } else {
+        Err(SchedulerError::Unordered {
+            lower: lower.0,
+            upper: upper.0,
+        })
+    }
+}
+
+pub(crate) fn check_strict_order(
+    lower: (&'static str, f64),
+    upper: (&'static str, f64),
+) -> Result<(), SchedulerError> {
+    if lower.1 < upper.1 {
+        Ok(())
+    } else {
+        Err(SchedulerError::NotLess {
+            lower: lower.0,
+            upper: upper.0,
+        })
+    }
+}
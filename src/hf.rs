@@ -0,0 +1,682 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{
+    check_finite, check_order, check_positive, check_range, check_steps, check_strict_order,
+};
+use crate::{
+    Hyperparams, LambdaLr, LrScheduler, PlateauMode, ReduceLrOnPlateau, SchedulerError,
+    ThresholdMode,
+};
+
+/// The `lr_scheduler_type` names understood by HuggingFace's `get_scheduler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SchedulerType {
+    Linear,
+    Cosine,
+    CosineWithRestarts,
+    Polynomial,
+    Constant,
+    ConstantWithWarmup,
+    InverseSqrt,
+    ReduceLrOnPlateau,
+    CosineWithMinLr,
+    WarmupStableDecay,
+}
+
+impl SchedulerType {
+    pub const ALL: [SchedulerType; 10] = [
+        SchedulerType::Linear,
+        SchedulerType::Cosine,
+        SchedulerType::CosineWithRestarts,
+        SchedulerType::Polynomial,
+        SchedulerType::Constant,
+        SchedulerType::ConstantWithWarmup,
+        SchedulerType::InverseSqrt,
+        SchedulerType::ReduceLrOnPlateau,
+        SchedulerType::CosineWithMinLr,
+        SchedulerType::WarmupStableDecay,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchedulerType::Linear => "linear",
+            SchedulerType::Cosine => "cosine",
+            SchedulerType::CosineWithRestarts => "cosine_with_restarts",
+            SchedulerType::Polynomial => "polynomial",
+            SchedulerType::Constant => "constant",
+            SchedulerType::ConstantWithWarmup => "constant_with_warmup",
+            SchedulerType::InverseSqrt => "inverse_sqrt",
+            SchedulerType::ReduceLrOnPlateau => "reduce_lr_on_plateau",
+            SchedulerType::CosineWithMinLr => "cosine_with_min_lr",
+            SchedulerType::WarmupStableDecay => "warmup_stable_decay",
+        }
+    }
+}
+
+impl fmt::Display for SchedulerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SchedulerType {
+    type Err = SchedulerError;
+
+    fn from_str(name: &str) -> Result<Self, SchedulerError> {
+        SchedulerType::ALL
+            .into_iter()
+            .find(|ty| ty.as_str() == name)
+            .ok_or_else(|| SchedulerError::UnknownSchedule(name.to_string()))
+    }
+}
+
+/// The warmup and decay shapes of HuggingFace's `warmup_stable_decay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum WsdShape {
+    Linear,
+    Cosine,
+    #[cfg_attr(feature = "serde", serde(rename = "1-sqrt"))]
+    OneMinusSqrt,
+}
+
+/// The schedule specific arguments, HuggingFace's `lr_scheduler_kwargs`.
+///
+/// Unset arguments take HuggingFace's defaults. Arguments the schedule
+/// doesn't take are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SchedulerKwargs {
+    /// Cosine waves over the decay. Defaults to 0.5, or 1 for
+    /// `cosine_with_restarts`.
+    pub num_cycles: Option<f64>,
+    /// `polynomial` power, defaulting to 1.
+    pub power: Option<f64>,
+    /// `polynomial` final lr, defaulting to 1e-7. Has to be below the
+    /// initial lr, like in HuggingFace.
+    pub lr_end: Option<f64>,
+    /// `inverse_sqrt` timescale, defaulting to the warmup steps, or 10,000
+    /// without warmup.
+    pub timescale: Option<usize>,
+    /// `cosine_with_min_lr` final lr, which needs a positive initial lr, or
+    /// `reduce_lr_on_plateau` lower bound.
+    pub min_lr: Option<f64>,
+    /// `cosine_with_min_lr` final lr as a fraction of the initial lr.
+    pub min_lr_rate: Option<f64>,
+    /// `warmup_stable_decay` stable phase length, defaulting to whatever the
+    /// total steps leave after warmup and decay.
+    pub num_stable_steps: Option<usize>,
+    /// `warmup_stable_decay` decay phase length. Required for it.
+    pub num_decay_steps: Option<usize>,
+    /// `warmup_stable_decay` warmup shape, defaulting to linear.
+    pub warmup_type: Option<WsdShape>,
+    /// `warmup_stable_decay` decay shape, defaulting to cosine.
+    pub decay_type: Option<WsdShape>,
+    /// `warmup_stable_decay` final lr as a fraction of the initial lr,
+    /// defaulting to 0.
+    pub min_lr_ratio: Option<f64>,
+    pub mode: Option<PlateauMode>,
+    pub factor: Option<f64>,
+    pub patience: Option<usize>,
+    pub threshold: Option<f64>,
+    pub threshold_mode: Option<ThresholdMode>,
+    pub cooldown: Option<usize>,
+    pub eps: Option<f64>,
+}
+
+/// A scheduler built by [`get_scheduler`]. Everything but
+/// `reduce_lr_on_plateau` is a [`LambdaLr`], like in HuggingFace.
+#[derive(Debug)]
+pub enum HfScheduler {
+    Lambda(LambdaLr),
+    ReduceLrOnPlateau(ReduceLrOnPlateau),
+}
+
+impl HfScheduler {
+    pub fn get_lr(&self) -> f64 {
+        match self {
+            HfScheduler::Lambda(scheduler) => scheduler.get_lr(),
+            HfScheduler::ReduceLrOnPlateau(scheduler) => scheduler.get_lr(),
+        }
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for HfScheduler {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        match self {
+            HfScheduler::Lambda(scheduler) => scheduler.step(optimizer),
+            HfScheduler::ReduceLrOnPlateau(scheduler) => scheduler.step(optimizer),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        match self {
+            HfScheduler::Lambda(scheduler) => LrScheduler::<O>::step_num(scheduler),
+            HfScheduler::ReduceLrOnPlateau(scheduler) => LrScheduler::<O>::step_num(scheduler),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            HfScheduler::Lambda(scheduler) => LrScheduler::<O>::reset(scheduler),
+            HfScheduler::ReduceLrOnPlateau(scheduler) => LrScheduler::<O>::reset(scheduler),
+        }
+    }
+}
+
+/// Build the schedule HuggingFace's `get_scheduler` would for `name`,
+/// starting from the optimizer's initial `lr`.
+///
+/// The lr at each step is computed with the same expressions as
+/// `transformers.optimization`, so a ported recipe gives identical values.
+/// `constant` ignores `warmup_steps`, and `total_steps` is only optional for
+/// schedules that don't decay to a fixed end.
+///
+/// ```
+/// use candle_scheduler::{get_scheduler, SchedulerKwargs};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let kwargs = SchedulerKwargs {
+///     min_lr: Some(1e-6),
+///     ..Default::default()
+/// };
+/// let scheduler = get_scheduler("cosine_with_min_lr", 3e-4, 500, Some(10_000), &kwargs)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn get_scheduler(
+    name: &str,
+    lr: f64,
+    warmup_steps: usize,
+    total_steps: Option<usize>,
+    kwargs: &SchedulerKwargs,
+) -> Result<HfScheduler, SchedulerError> {
+    let ty: SchedulerType = name.parse()?;
+    check_finite("lr", lr)?;
+    let total = || {
+        total_steps.ok_or(SchedulerError::Missing {
+            name: "total_steps",
+        })
+    };
+    let w = warmup_steps;
+
+    let factor: Box<dyn Fn(usize) -> f64 + Send + Sync> = match ty {
+        SchedulerType::Constant => Box::new(|_step| 1.),
+        SchedulerType::ConstantWithWarmup => Box::new(move |step| {
+            if step < w {
+                return warmup(step, w);
+            }
+            1.
+        }),
+        SchedulerType::Linear => {
+            let total = total()?;
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                ((total as f64 - step as f64) / decay_steps(w, total)).max(0.)
+            })
+        }
+        SchedulerType::Cosine => {
+            let total = total()?;
+            let num_cycles = check_finite("num_cycles", kwargs.num_cycles.unwrap_or(0.5))?;
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                let progress = (step - w) as f64 / decay_steps(w, total);
+                (0.5 * (1. + (PI * num_cycles * 2. * progress).cos())).max(0.)
+            })
+        }
+        SchedulerType::CosineWithRestarts => {
+            let total = total()?;
+            let num_cycles = check_finite("num_cycles", kwargs.num_cycles.unwrap_or(1.))?;
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                let progress = (step - w) as f64 / decay_steps(w, total);
+                if progress >= 1. {
+                    return 0.;
+                }
+                (0.5 * (1. + (PI * ((num_cycles * progress) % 1.)).cos())).max(0.)
+            })
+        }
+        SchedulerType::Polynomial => {
+            let total = total()?;
+            let lr_end = check_finite("lr_end", kwargs.lr_end.unwrap_or(1e-7))?;
+            let power = check_finite("power", kwargs.power.unwrap_or(1.))?;
+            check_positive("lr", lr)?;
+            check_strict_order(("lr_end", lr_end), ("lr", lr))?;
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                if step > total {
+                    return lr_end / lr;
+                }
+                let pct_remaining = 1. - (step - w) as f64 / (total - w).max(1) as f64;
+                ((lr - lr_end) * pct_remaining.powf(power) + lr_end) / lr
+            })
+        }
+        SchedulerType::InverseSqrt => {
+            let timescale = match (kwargs.timescale, w) {
+                (Some(timescale), _) => check_steps("timescale", timescale)?,
+                (None, 0) => 10_000,
+                (None, w) => w,
+            } as f64;
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                let shift = timescale - w as f64;
+                1. / ((step as f64 + shift) / timescale).sqrt()
+            })
+        }
+        SchedulerType::CosineWithMinLr => {
+            let total = total()?;
+            let num_cycles = check_finite("num_cycles", kwargs.num_cycles.unwrap_or(0.5))?;
+            let min_lr_rate = match (kwargs.min_lr, kwargs.min_lr_rate) {
+                (Some(_), Some(_)) => {
+                    return Err(SchedulerError::Conflicting {
+                        first: "min_lr",
+                        second: "min_lr_rate",
+                    })
+                }
+                (Some(min_lr), None) => check_finite("min_lr", min_lr)? / check_positive("lr", lr)?,
+                (None, Some(min_lr_rate)) => check_finite("min_lr_rate", min_lr_rate)?,
+                (None, None) => return Err(SchedulerError::Missing { name: "min_lr" }),
+            };
+            Box::new(move |step| {
+                if step < w {
+                    return warmup(step, w);
+                }
+                let progress = (step - w) as f64 / decay_steps(w, total);
+                let factor = 0.5 * (1. + (PI * num_cycles * 2. * progress).cos());
+                (factor * (1. - min_lr_rate) + min_lr_rate).max(0.)
+            })
+        }
+        SchedulerType::WarmupStableDecay => {
+            let decay = kwargs.num_decay_steps.ok_or(SchedulerError::Missing {
+                name: "num_decay_steps",
+            })?;
+            let stable = match kwargs.num_stable_steps {
+                Some(stable) => stable,
+                None => {
+                    let total = total()?;
+                    check_order(
+                        ("warmup_steps + num_decay_steps", (w + decay) as f64),
+                        ("total_steps", total as f64),
+                    )?;
+                    total - w - decay
+                }
+            };
+            let warmup_type = kwargs.warmup_type.unwrap_or(WsdShape::Linear);
+            let decay_type = kwargs.decay_type.unwrap_or(WsdShape::Cosine);
+            let min_lr_ratio =
+                check_range("min_lr_ratio", kwargs.min_lr_ratio.unwrap_or(0.), 0., 1.)?;
+            let num_cycles = check_finite("num_cycles", kwargs.num_cycles.unwrap_or(0.5))?;
+            Box::new(move |step| {
+                let factor = if step < w {
+                    let progress = warmup(step, w);
+                    match warmup_type {
+                        WsdShape::Linear => progress,
+                        WsdShape::Cosine => 0.5 * (1. - (PI * progress).cos()),
+                        WsdShape::OneMinusSqrt => 1. - (1. - progress).sqrt(),
+                    }
+                } else if step < w + stable {
+                    return 1.;
+                } else if step < w + stable + decay {
+                    let progress = (step - w - stable) as f64 / decay.max(1) as f64;
+                    match decay_type {
+                        WsdShape::Linear => 1. - progress,
+                        WsdShape::Cosine => 0.5 * (1. + (PI * num_cycles * 2. * progress).cos()),
+                        WsdShape::OneMinusSqrt => 1. - progress.sqrt(),
+                    }
+                } else {
+                    return min_lr_ratio;
+                };
+                (factor * (1. - min_lr_ratio) + min_lr_ratio).max(0.)
+            })
+        }
+        SchedulerType::ReduceLrOnPlateau => {
+            let mut builder = ReduceLrOnPlateau::builder(lr)
+                .mode(kwargs.mode.unwrap_or_default())
+                .threshold_mode(kwargs.threshold_mode.unwrap_or_default());
+            if let Some(factor) = kwargs.factor {
+                builder = builder.factor(factor);
+            }
+            if let Some(patience) = kwargs.patience {
+                builder = builder.patience(patience);
+            }
+            if let Some(threshold) = kwargs.threshold {
+                builder = builder.threshold(threshold);
+            }
+            if let Some(cooldown) = kwargs.cooldown {
+                builder = builder.cooldown(cooldown);
+            }
+            if let Some(min_lr) = kwargs.min_lr {
+                builder = builder.min_lr(min_lr);
+            }
+            if let Some(eps) = kwargs.eps {
+                builder = builder.eps(eps);
+            }
+            return builder.build().map(HfScheduler::ReduceLrOnPlateau);
+        }
+    };
+
+    Ok(HfScheduler::Lambda(LambdaLr::try_new(lr, factor)?))
+}
+
+/// `float(step) / float(max(1, warmup_steps))`
+fn warmup(step: usize, warmup_steps: usize) -> f64 {
+    step as f64 / warmup_steps.max(1) as f64
+}
+
+/// `float(max(1, total_steps - warmup_steps))`
+fn decay_steps(warmup_steps: usize, total_steps: usize) -> f64 {
+    (total_steps as f64 - warmup_steps as f64).max(1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    use crate::{
+        get_scheduler, HfScheduler, LrScheduler, SchedulerError, SchedulerKwargs, WsdShape,
+    };
+
+    fn lrs(
+        name: &str,
+        warmup_steps: usize,
+        total_steps: usize,
+        kwargs: &SchedulerKwargs,
+        steps: usize,
+    ) -> Vec<f64> {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler =
+            get_scheduler(name, 0.1, warmup_steps, Some(total_steps), kwargs).unwrap();
+
+        (0..steps)
+            .map(|_step| {
+                scheduler.step(&mut opt).unwrap();
+                opt.learning_rate()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hf_linear_test() {
+        assert_eq!(
+            lrs("linear", 2, 6, &SchedulerKwargs::default(), 7),
+            [0.05, 0.1, 0.07500000000000001, 0.05, 0.025, 0., 0.]
+        );
+    }
+
+    #[test]
+    fn hf_cosine_test() {
+        assert_eq!(
+            lrs("cosine", 2, 6, &SchedulerKwargs::default(), 7),
+            [
+                0.05,
+                0.1,
+                0.08535533905932738,
+                0.05,
+                0.014644660940672627,
+                0.,
+                0.014644660940672617
+            ]
+        );
+
+        let kwargs = SchedulerKwargs {
+            num_cycles: Some(2.),
+            ..Default::default()
+        };
+        assert_eq!(
+            lrs("cosine_with_restarts", 2, 10, &kwargs, 10),
+            [
+                0.05,
+                0.1,
+                0.08535533905932738,
+                0.05,
+                0.014644660940672627,
+                0.1,
+                0.08535533905932738,
+                0.05,
+                0.014644660940672627,
+                0.
+            ]
+        );
+
+        let kwargs = SchedulerKwargs {
+            min_lr: Some(1e-3),
+            ..Default::default()
+        };
+        assert_eq!(
+            lrs("cosine_with_min_lr", 2, 6, &kwargs, 7),
+            [
+                0.05,
+                0.1,
+                0.0855017856687341,
+                0.0505,
+                0.015498214331265903,
+                0.001,
+                0.015498214331265893
+            ]
+        );
+    }
+
+    #[test]
+    fn hf_polynomial_test() {
+        let kwargs = SchedulerKwargs {
+            lr_end: Some(1e-3),
+            power: Some(2.),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            lrs("polynomial", 2, 6, &kwargs, 7),
+            [
+                0.05,
+                0.1,
+                0.0566875,
+                0.025750000000000002,
+                0.0071874999999999994,
+                0.001,
+                0.001
+            ]
+        );
+    }
+
+    #[test]
+    fn hf_constant_test() {
+        assert_eq!(
+            lrs("constant", 2, 4, &SchedulerKwargs::default(), 2),
+            [0.1, 0.1]
+        );
+        assert_eq!(
+            lrs("constant_with_warmup", 2, 4, &SchedulerKwargs::default(), 3),
+            [0.05, 0.1, 0.1]
+        );
+        assert_eq!(
+            lrs("inverse_sqrt", 2, 4, &SchedulerKwargs::default(), 5),
+            [
+                0.05,
+                0.1,
+                0.08164965809277262,
+                0.07071067811865475,
+                0.0632455532033676
+            ]
+        );
+    }
+
+    #[test]
+    fn hf_warmup_stable_decay_test() {
+        let kwargs = SchedulerKwargs {
+            num_stable_steps: Some(2),
+            num_decay_steps: Some(4),
+            warmup_type: Some(WsdShape::Cosine),
+            decay_type: Some(WsdShape::OneMinusSqrt),
+            min_lr_ratio: Some(0.1),
+            ..Default::default()
+        };
+        let scheduler = get_scheduler("warmup_stable_decay", 0.1, 2, None, &kwargs).unwrap();
+
+        assert_eq!(scheduler.get_lr(), 0.010000000000000002);
+        assert_eq!(
+            lrs("warmup_stable_decay", 2, 0, &kwargs, 9),
+            [
+                0.05499999999999999,
+                0.1,
+                0.1,
+                0.1,
+                0.05500000000000001,
+                0.03636038969321072,
+                0.022057713659400527,
+                0.010000000000000002,
+                0.010000000000000002
+            ]
+        );
+    }
+
+    #[test]
+    fn hf_reduce_lr_on_plateau_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let kwargs = SchedulerKwargs {
+            patience: Some(0),
+            factor: Some(0.5),
+            ..Default::default()
+        };
+        let HfScheduler::ReduceLrOnPlateau(mut scheduler) =
+            get_scheduler("reduce_lr_on_plateau", 0.1, 0, None, &kwargs).unwrap()
+        else {
+            panic!("expected ReduceLrOnPlateau");
+        };
+
+        scheduler.report(&mut opt, 1.).unwrap();
+        scheduler.report(&mut opt, 1.).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.05);
+    }
+
+    #[test]
+    fn hf_invalid_test() {
+        let kwargs = SchedulerKwargs::default();
+
+        assert_eq!(
+            get_scheduler("cosine_annealing", 0.1, 0, Some(10), &kwargs).unwrap_err(),
+            SchedulerError::UnknownSchedule("cosine_annealing".to_string())
+        );
+        assert_eq!(
+            get_scheduler("linear", 0.1, 0, None, &kwargs).unwrap_err(),
+            SchedulerError::Missing {
+                name: "total_steps"
+            }
+        );
+        assert_eq!(
+            get_scheduler("cosine_with_min_lr", 0.1, 0, Some(10), &kwargs).unwrap_err(),
+            SchedulerError::Missing { name: "min_lr" }
+        );
+        assert_eq!(
+            get_scheduler(
+                "cosine_with_min_lr",
+                0.1,
+                0,
+                Some(10),
+                &SchedulerKwargs {
+                    min_lr: Some(1e-3),
+                    min_lr_rate: Some(0.01),
+                    ..Default::default()
+                }
+            )
+            .unwrap_err(),
+            SchedulerError::Conflicting {
+                first: "min_lr",
+                second: "min_lr_rate"
+            }
+        );
+        assert_eq!(
+            get_scheduler(
+                "polynomial",
+                0.1,
+                0,
+                Some(10),
+                &SchedulerKwargs {
+                    lr_end: Some(1.),
+                    ..Default::default()
+                }
+            )
+            .unwrap_err(),
+            SchedulerError::NotLess {
+                lower: "lr_end",
+                upper: "lr"
+            }
+        );
+        assert_eq!(
+            get_scheduler("polynomial", 1e-7, 0, Some(10), &SchedulerKwargs::default())
+                .unwrap_err(),
+            SchedulerError::NotLess {
+                lower: "lr_end",
+                upper: "lr"
+            }
+        );
+        assert_eq!(
+            get_scheduler(
+                "inverse_sqrt",
+                0.1,
+                0,
+                None,
+                &SchedulerKwargs {
+                    timescale: Some(0),
+                    ..Default::default()
+                }
+            )
+            .unwrap_err(),
+            SchedulerError::ZeroSteps { name: "timescale" }
+        );
+        assert_eq!(
+            get_scheduler(
+                "cosine_with_min_lr",
+                0.,
+                0,
+                Some(10),
+                &SchedulerKwargs {
+                    min_lr: Some(0.),
+                    ..Default::default()
+                }
+            )
+            .unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "lr",
+                value: 0.
+            }
+        );
+    }
+
+    #[test]
+    fn scheduler_type_names_test() {
+        for ty in crate::SchedulerType::ALL {
+            assert_eq!(ty.to_string().parse(), Ok(ty));
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+use candle_nn::{AdamW, Optimizer, SGD};
+
+use crate::SchedulerError;
+
+/// Optimizer hyperparameters a schedule can drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hyperparam {
+    LearningRate,
+    /// Momentum, or `beta1` for Adam style optimizers.
+    Momentum,
+    Beta2,
+    WeightDecay,
+    Eps,
+}
+
+impl fmt::Display for Hyperparam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hyperparam::LearningRate => "learning rate",
+            Hyperparam::Momentum => "momentum",
+            Hyperparam::Beta2 => "beta2",
+            Hyperparam::WeightDecay => "weight decay",
+            Hyperparam::Eps => "eps",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What a scheduler does when the optimizer can't take a hyperparameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnsupportedPolicy {
+    /// Skip the hyperparameter and keep going. The learning rate is still set.
+    #[default]
+    Ignore,
+    /// Return [`SchedulerError::UnsupportedHyperparam`] from `step`.
+    Error,
+}
+
+impl UnsupportedPolicy {
+    /// Filter the result of setting a hyperparameter through this policy.
+    pub fn apply(self, result: Result<(), SchedulerError>) -> Result<(), SchedulerError> {
+        match (self, result) {
+            (UnsupportedPolicy::Ignore, Err(SchedulerError::UnsupportedHyperparam { .. })) => {
+                Ok(())
+            }
+            (_, result) => result,
+        }
+    }
+}
+
+fn unsupported<O: ?Sized>(hyperparam: Hyperparam) -> SchedulerError {
+    SchedulerError::UnsupportedHyperparam {
+        hyperparam,
+        optimizer: std::any::type_name::<O>(),
+    }
+}
+
+/// An optimizer whose hyperparameters can be read and set by a scheduler.
+///
+/// The learning rate comes from [`Optimizer`]. Every other hyperparameter
+/// defaults to unsupported, so an empty `impl Hyperparams for MyOptimizer {}`
+/// is enough to use lr-only schedules.
+pub trait Hyperparams: Optimizer {
+    fn momentum(&self) -> Option<f64> {
+        None
+    }
+
+    fn set_momentum(&mut self, _momentum: f64) -> Result<(), SchedulerError> {
+        Err(unsupported::<Self>(Hyperparam::Momentum))
+    }
+
+    fn beta2(&self) -> Option<f64> {
+        None
+    }
+
+    fn set_beta2(&mut self, _beta2: f64) -> Result<(), SchedulerError> {
+        Err(unsupported::<Self>(Hyperparam::Beta2))
+    }
+
+    fn weight_decay(&self) -> Option<f64> {
+        None
+    }
+
+    fn set_weight_decay(&mut self, _weight_decay: f64) -> Result<(), SchedulerError> {
+        Err(unsupported::<Self>(Hyperparam::WeightDecay))
+    }
+
+    fn eps(&self) -> Option<f64> {
+        None
+    }
+
+    fn set_eps(&mut self, _eps: f64) -> Result<(), SchedulerError> {
+        Err(unsupported::<Self>(Hyperparam::Eps))
+    }
+
+    fn hyperparam(&self, hyperparam: Hyperparam) -> Option<f64> {
+        match hyperparam {
+            Hyperparam::LearningRate => Some(self.learning_rate()),
+            Hyperparam::Momentum => self.momentum(),
+            Hyperparam::Beta2 => self.beta2(),
+            Hyperparam::WeightDecay => self.weight_decay(),
+            Hyperparam::Eps => self.eps(),
+        }
+    }
+
+    fn set_hyperparam(&mut self, hyperparam: Hyperparam, value: f64) -> Result<(), SchedulerError> {
+        match hyperparam {
+            Hyperparam::LearningRate => {
+                self.set_learning_rate(value);
+                Ok(())
+            }
+            Hyperparam::Momentum => self.set_momentum(value),
+            Hyperparam::Beta2 => self.set_beta2(value),
+            Hyperparam::WeightDecay => self.set_weight_decay(value),
+            Hyperparam::Eps => self.set_eps(value),
+        }
+    }
+}
+
+impl Hyperparams for AdamW {
+    /// `beta1`
+    fn momentum(&self) -> Option<f64> {
+        Some(self.params().beta1)
+    }
+
+    fn set_momentum(&mut self, momentum: f64) -> Result<(), SchedulerError> {
+        let mut params = self.params().clone();
+        params.beta1 = momentum;
+        self.set_params(params);
+        Ok(())
+    }
+
+    fn beta2(&self) -> Option<f64> {
+        Some(self.params().beta2)
+    }
+
+    fn set_beta2(&mut self, beta2: f64) -> Result<(), SchedulerError> {
+        let mut params = self.params().clone();
+        params.beta2 = beta2;
+        self.set_params(params);
+        Ok(())
+    }
+
+    fn weight_decay(&self) -> Option<f64> {
+        Some(self.params().weight_decay)
+    }
+
+    fn set_weight_decay(&mut self, weight_decay: f64) -> Result<(), SchedulerError> {
+        let mut params = self.params().clone();
+        params.weight_decay = weight_decay;
+        self.set_params(params);
+        Ok(())
+    }
+
+    fn eps(&self) -> Option<f64> {
+        Some(self.params().eps)
+    }
+
+    fn set_eps(&mut self, eps: f64) -> Result<(), SchedulerError> {
+        let mut params = self.params().clone();
+        params.eps = eps;
+        self.set_params(params);
+        Ok(())
+    }
+}
+
+/// candle's `SGD` only has a learning rate.
+impl Hyperparams for SGD {}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    use crate::{Hyperparam, Hyperparams, SchedulerError, UnsupportedPolicy};
+
+    #[test]
+    fn adamw_hyperparams_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+
+        opt.set_momentum(0.85).unwrap();
+        opt.set_hyperparam(Hyperparam::WeightDecay, 0.1).unwrap();
+        opt.set_hyperparam(Hyperparam::LearningRate, 3e-4).unwrap();
+
+        assert_eq!(opt.params().beta1, 0.85);
+        assert_eq!(opt.hyperparam(Hyperparam::Momentum), Some(0.85));
+        assert_eq!(opt.params().weight_decay, 0.1);
+        assert_eq!(opt.learning_rate(), 3e-4);
+    }
+
+    #[test]
+    fn sgd_unsupported_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1e-3).unwrap();
+
+        let result = opt.set_momentum(0.9);
+
+        assert!(matches!(
+            result,
+            Err(SchedulerError::UnsupportedHyperparam {
+                hyperparam: Hyperparam::Momentum,
+                ..
+            })
+        ));
+        assert_eq!(opt.momentum(), None);
+        assert_eq!(UnsupportedPolicy::Ignore.apply(result.clone()), Ok(()));
+        assert_eq!(UnsupportedPolicy::Error.apply(result.clone()), result);
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_order, check_steps};
+use crate::{LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Configures an [`InverseSqrt`].
+#[derive(Debug, Clone)]
+pub struct InverseSqrtBuilder {
+    lr: f64,
+    d_model: Option<usize>,
+    warmup_steps: usize,
+    linear_warmup: bool,
+    timescale: Option<usize>,
+    cooldown: Option<(usize, usize)>,
+}
+
+impl InverseSqrtBuilder {
+    /// Scale the lr by `d_model^-0.5`, as in "Attention Is All You Need".
+    pub fn d_model(mut self, d_model: usize) -> Self {
+        self.d_model = Some(d_model);
+        self
+    }
+
+    /// Steps before the decay starts. Defaults to 0.
+    pub fn warmup_steps(mut self, warmup_steps: usize) -> Self {
+        self.warmup_steps = warmup_steps;
+        self
+    }
+
+    /// Ramp the lr up linearly from 0 during warmup. Defaults to true; when
+    /// false the lr is held at its peak during warmup, like T5.
+    pub fn linear_warmup(mut self, linear_warmup: bool) -> Self {
+        self.linear_warmup = linear_warmup;
+        self
+    }
+
+    /// Steps over which the lr decays by `1/sqrt(2)` once warmup is over.
+    /// Defaults to `warmup_steps`, or 10,000 without warmup.
+    pub fn timescale(mut self, timescale: usize) -> Self {
+        self.timescale = Some(timescale);
+        self
+    }
+
+    /// Decay linearly to 0 over the last `cooldown_steps` of `total_steps`.
+    pub fn cooldown(mut self, cooldown_steps: usize, total_steps: usize) -> Self {
+        self.cooldown = Some((cooldown_steps, total_steps));
+        self
+    }
+
+    /// Requires a finite `lr`, positive `d_model` and `timescale` when set,
+    /// and a positive cooldown no longer than `total_steps`.
+    pub fn build(&self) -> Result<InverseSqrt, SchedulerError> {
+        check_finite("lr", self.lr)?;
+        if let Some(d_model) = self.d_model {
+            check_steps("d_model", d_model)?;
+        }
+        let timescale = match (self.timescale, self.warmup_steps) {
+            (Some(timescale), _) => check_steps("timescale", timescale)?,
+            (None, 0) => 10_000,
+            (None, warmup_steps) => warmup_steps,
+        };
+        if let Some((cooldown_steps, total_steps)) = self.cooldown {
+            check_steps("cooldown_steps", cooldown_steps)?;
+            check_order(
+                ("cooldown_steps", cooldown_steps as f64),
+                ("total_steps", total_steps as f64),
+            )?;
+        }
+
+        Ok(InverseSqrt {
+            lr: self.lr,
+            d_model: self.d_model,
+            warmup_steps: self.warmup_steps,
+            linear_warmup: self.linear_warmup,
+            timescale,
+            cooldown: self.cooldown,
+            step_num: 0,
+        })
+    }
+}
+
+/// Inverse square root decay after a warmup, the default transformer
+/// schedule.
+///
+/// After warmup the lr is `lr / sqrt((step - warmup_steps + timescale) /
+/// timescale)`, matching HuggingFace's `inverse_sqrt`. [`InverseSqrt::noam`]
+/// gives the "Attention Is All You Need" schedule
+/// `d_model^-0.5 * min(step^-0.5, step * warmup^-1.5)`.
+///
+/// ```
+/// use candle_scheduler::InverseSqrt;
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// // T5's rsqrt: `1 / sqrt(max(step, 10_000))`.
+/// let t5 = InverseSqrt::builder(0.01)
+///     .warmup_steps(10_000)
+///     .linear_warmup(false)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct InverseSqrt {
+    lr: f64,
+    d_model: Option<usize>,
+    warmup_steps: usize,
+    linear_warmup: bool,
+    timescale: usize,
+    cooldown: Option<(usize, usize)>,
+    step_num: usize,
+}
+
+impl InverseSqrt {
+    /// A linear warmup over `warmup_steps` to `lr`, then inverse square root
+    /// decay.
+    ///
+    /// # Panics
+    ///
+    /// If `lr` isn't finite.
+    pub fn new(lr: f64, warmup_steps: usize) -> Self {
+        Self::builder(lr)
+            .warmup_steps(warmup_steps)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid InverseSqrt: {err}"))
+    }
+
+    /// The Noam schedule, `factor * d_model^-0.5 * min(step^-0.5, step *
+    /// warmup_steps^-1.5)`.
+    ///
+    /// # Panics
+    ///
+    /// If `d_model` or `warmup_steps` is 0, or `factor` isn't finite.
+    pub fn noam(d_model: usize, warmup_steps: usize, factor: f64) -> Self {
+        Self::try_noam(d_model, warmup_steps, factor)
+            .unwrap_or_else(|err| panic!("invalid InverseSqrt: {err}"))
+    }
+
+    /// Requires positive `d_model` and `warmup_steps`, and a finite `factor`.
+    pub fn try_noam(
+        d_model: usize,
+        warmup_steps: usize,
+        factor: f64,
+    ) -> Result<Self, SchedulerError> {
+        let warmup_steps = check_steps("warmup_steps", warmup_steps)?;
+
+        Self::builder(factor * (warmup_steps as f64).powf(-0.5))
+            .d_model(d_model)
+            .warmup_steps(warmup_steps)
+            .build()
+    }
+
+    /// Start configuring a schedule peaking at `lr`.
+    pub fn builder(lr: f64) -> InverseSqrtBuilder {
+        InverseSqrtBuilder {
+            lr,
+            d_model: None,
+            warmup_steps: 0,
+            linear_warmup: true,
+            timescale: None,
+            cooldown: None,
+        }
+    }
+
+    /// The lr at the end of warmup, including the `d_model` scaling.
+    pub fn peak_lr(&self) -> f64 {
+        match self.d_model {
+            Some(d_model) => self.lr * (d_model as f64).powf(-0.5),
+            None => self.lr,
+        }
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+}
+
+impl Schedule for InverseSqrt {
+    fn lr_at(&self, step: usize) -> f64 {
+        let factor = if step < self.warmup_steps && self.linear_warmup {
+            step as f64 / self.warmup_steps as f64
+        } else if step < self.warmup_steps {
+            1.
+        } else {
+            let timescale = self.timescale as f64;
+            let shift = timescale - self.warmup_steps as f64;
+            1. / ((step as f64 + shift) / timescale).sqrt()
+        };
+        let lr = self.peak_lr() * factor;
+
+        match self.cooldown {
+            Some((cooldown_steps, total_steps)) if step >= total_steps - cooldown_steps => {
+                lr * total_steps.saturating_sub(step) as f64 / cooldown_steps as f64
+            }
+            _ => lr,
+        }
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for InverseSqrt {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for InverseSqrt {
+    fn to_config(&self) -> SchedulerConfig {
+        let default_timescale = match self.warmup_steps {
+            0 => 10_000,
+            warmup_steps => warmup_steps,
+        };
+
+        SchedulerConfig::InverseSqrt {
+            lr: self.lr,
+            d_model: self.d_model,
+            warmup_steps: non_default(self.warmup_steps, 0),
+            linear_warmup: non_default(self.linear_warmup, true),
+            timescale: non_default(self.timescale, default_timescale),
+            cooldown: self.cooldown,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `inverse_sqrt(4000, lr=0.001)`.
+impl fmt::Display for InverseSqrt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with an [`InverseSqrt`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct InverseSqrtConfig {
+    pub lr: f64,
+    pub d_model: Option<usize>,
+    pub warmup_steps: usize,
+    pub linear_warmup: bool,
+    pub timescale: usize,
+    pub cooldown: Option<(usize, usize)>,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for InverseSqrt {
+    type Config = InverseSqrtConfig;
+
+    fn state_dict(&self) -> SchedulerState<InverseSqrtConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: InverseSqrtConfig {
+                lr: self.lr,
+                d_model: self.d_model,
+                warmup_steps: self.warmup_steps,
+                linear_warmup: self.linear_warmup,
+                timescale: self.timescale,
+                cooldown: self.cooldown,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<InverseSqrtConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    use crate::{InverseSqrt, LrScheduler, Schedule, SchedulerError};
+
+    #[test]
+    fn noam_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = InverseSqrt::noam(512, 4, 2.);
+        // 2 * 512^-0.5 * min(step^-0.5, step * 4^-1.5)
+        let lrs = [
+            0.011048543456039806,
+            0.02209708691207961,
+            0.03314563036811942,
+            0.04419417382415922,
+            0.03952847075210474,
+            0.036084391824351615,
+            0.03340765523905305,
+        ];
+
+        assert_eq!(scheduler.get_lr(), 0.);
+
+        for lr in lrs {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+    }
+
+    #[test]
+    fn inverse_sqrt_timescale_test() {
+        let scheduler = InverseSqrt::builder(1e-3)
+            .warmup_steps(10)
+            .timescale(100)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(5), 0.0005);
+        assert_eq!(scheduler.lr_at(10), 0.001);
+        assert_eq!(scheduler.lr_at(100), 0.0007254762501100118);
+        assert_eq!(scheduler.lr_at(1000), 0.00030289126640769133);
+    }
+
+    #[test]
+    fn inverse_sqrt_t5_test() {
+        let scheduler = InverseSqrt::builder(0.01)
+            .warmup_steps(10_000)
+            .linear_warmup(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(0), 0.01);
+        assert_eq!(scheduler.lr_at(9_999), 0.01);
+        assert_eq!(scheduler.lr_at(40_000), 0.005);
+    }
+
+    #[test]
+    fn inverse_sqrt_cooldown_test() {
+        let scheduler = InverseSqrt::builder(1.)
+            .timescale(1)
+            .cooldown(4, 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(5), 0.4082482904638631);
+        assert_eq!(scheduler.lr_at(6), 0.3779644730092272);
+        assert_eq!(scheduler.lr_at(8), 0.16666666666666666);
+        assert_eq!(scheduler.lr_at(10), 0.);
+        assert_eq!(scheduler.lr_at(12), 0.);
+    }
+
+    #[test]
+    fn inverse_sqrt_invalid_test() {
+        assert_eq!(
+            InverseSqrt::try_noam(0, 4000, 1.).unwrap_err(),
+            SchedulerError::ZeroSteps { name: "d_model" }
+        );
+        assert_eq!(
+            InverseSqrt::builder(1e-3)
+                .cooldown(200, 100)
+                .build()
+                .unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "cooldown_steps",
+                upper: "total_steps"
+            }
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+
+use crate::error::check_finite;
+use crate::{FactorFn, Hyperparams, LrScheduler, Schedule, SchedulerError, UnsupportedPolicy};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// A schedule defined by a closure, like PyTorch's `LambdaLR`.
+///
+/// The lr at each step is `lr * factor(step)`. A momentum closure, returning
+/// the momentum itself rather than a factor, can be added with
+/// [`LambdaLr::with_momentum`].
+///
+/// ```
+/// use candle_scheduler::LambdaLr;
+///
+/// // Inverse time decay.
+/// let scheduler = LambdaLr::new(1e-3, |step| 1. / (1. + step as f64 / 100.));
+/// ```
+pub struct LambdaLr {
+    base_lr: f64,
+    factor: Box<FactorFn>,
+    momentum: Option<Box<FactorFn>>,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl fmt::Debug for LambdaLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LambdaLr")
+            .field("base_lr", &self.base_lr)
+            .field("momentum", &self.momentum.is_some())
+            .field("step_num", &self.step_num)
+            .field("unsupported", &self.unsupported)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LambdaLr {
+    /// # Panics
+    ///
+    /// If `lr` isn't finite.
+    pub fn new(lr: f64, factor: impl Fn(usize) -> f64 + Send + Sync + 'static) -> Self {
+        Self::try_new(lr, factor).unwrap_or_else(|err| panic!("invalid LambdaLr: {err}"))
+    }
+
+    /// Requires a finite `lr`.
+    pub fn try_new(
+        lr: f64,
+        factor: impl Fn(usize) -> f64 + Send + Sync + 'static,
+    ) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+
+        Ok(LambdaLr {
+            base_lr: lr,
+            factor: Box::new(factor),
+            momentum: None,
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+
+    /// Also set momentum to `momentum(step)` on every step.
+    pub fn with_momentum(
+        mut self,
+        momentum: impl Fn(usize) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        self.momentum = Some(Box::new(momentum));
+        self
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+}
+
+impl Schedule for LambdaLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.base_lr * (self.factor)(step)
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        self.momentum.as_ref().map(|momentum| momentum(step))
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for LambdaLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+/// The configuration saved with a [`LambdaLr`] state. The closures can't be
+/// saved, so they have to be the same on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LambdaLrConfig {
+    pub base_lr: f64,
+    pub momentum: bool,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for LambdaLr {
+    type Config = LambdaLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<LambdaLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: LambdaLrConfig {
+                base_lr: self.base_lr,
+                momentum: self.momentum.is_some(),
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<LambdaLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{LambdaLr, LrScheduler, Schedule, SchedulerError, UnsupportedPolicy};
+
+    #[test]
+    fn lambda_lr_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = LambdaLr::new(0.1, |step| 1. / (1 + step) as f64);
+
+        assert_eq!(scheduler.get_lr(), 0.1);
+
+        for lr in [0.05, 0.03333333333333333, 0.025] {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        assert_eq!(scheduler.lr_at(1), 0.05);
+        assert_eq!(scheduler.get_momentum(), None);
+    }
+
+    #[test]
+    fn lambda_lr_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler =
+            LambdaLr::new(1e-3, |_step| 1.)
+                .with_momentum(|step| if step < 2 { 0.95 } else { 0.85 });
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, 0.95);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, 0.85);
+        assert_eq!(scheduler.momentum_at(0), Some(0.95));
+    }
+
+    #[test]
+    fn lambda_lr_unsupported_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = LambdaLr::new(0.1, |_step| 0.5)
+            .with_momentum(|_step| 0.9)
+            .with_unsupported_policy(UnsupportedPolicy::Error);
+
+        assert!(matches!(
+            scheduler.step(&mut opt),
+            Err(SchedulerError::UnsupportedHyperparam { .. })
+        ));
+        assert_eq!(opt.learning_rate(), 0.05);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn lambda_lr_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = LambdaLr::new(0.1, |step| 0.5f64.powi(step as i32));
+
+        for _i in 0..2 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let mut resumed = LambdaLr::new(0.1, |step| 0.5f64.powi(step as i32));
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(resumed.get_lr(), 0.025);
+
+        let mut with_momentum = LambdaLr::new(0.1, |_step| 1.).with_momentum(|_step| 0.9);
+        assert!(matches!(
+            with_momentum.load_state_dict(scheduler.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+}
@@ -1,267 +1,153 @@
-use std::f64::consts::PI;
-
-use candle_nn::{AdamW, Optimizer};
-
-#[derive(Debug)]
-pub struct OneCycle {
-    lr: f64,
-    momentum: f64,
-    step_num: usize,
-    phases: Vec<Phase>,
-}
-
-#[derive(Debug)]
-pub struct Phase {
-    end_step: usize,
-    start_lr: f64,
-    end_lr: f64,
-    start_momentum: f64,
-    end_momentum: f64,
-}
-
-fn cos_annealing(start: f64, end: f64, pct: f64) -> f64 {
-    let cos_out = (pct * PI).cos() + 1.;
-    end + (start - end) / 2. * cos_out
-}
-
-fn build_phases(
-    max_lr: f64,
-    min_lr: f64,
-    max_momentum: f64,
-    min_momentum: f64,
-    total_steps: usize,
-    percent_start: f64,
-) -> Vec<Phase> {
-    vec![
-        Phase {
-            end_step: (percent_start * total_steps as f64 - 1.).round() as usize,
-            start_lr: min_lr,
-            end_lr: max_lr,
-            start_momentum: max_momentum,
-            end_momentum: min_momentum,
-        },
-        Phase {
-            end_step: total_steps - 1,
-            start_lr: max_lr,
-            end_lr: min_lr,
-            start_momentum: min_momentum,
-            end_momentum: max_momentum,
-        },
-    ]
+use candle_nn::Optimizer;
+
+mod anneal;
+#[cfg(feature = "safetensors")]
+mod checkpoint;
+mod compose;
+mod config;
+mod constant;
+mod cosine;
+mod cyclic;
+mod decay;
+mod dsl;
+mod error;
+mod hf;
+mod hyperparams;
+mod inverse_sqrt;
+mod lambda;
+mod lr_finder;
+mod one_cycle;
+mod phase;
+mod plateau;
+#[cfg(feature = "plot")]
+mod plot;
+#[cfg(feature = "serde")]
+mod state;
+mod step_lr;
+mod warmup;
+mod wsd;
+
+pub use anneal::Anneal;
+#[cfg(feature = "safetensors")]
+pub use checkpoint::{
+    load_checkpoint, read_checkpoint_state, save_checkpoint, SCHEDULER_STATE_KEY,
+};
+pub use compose::{Chained, Sequential};
+#[cfg(feature = "serde")]
+pub use compose::{ChainedConfig, SequentialConfig};
+#[cfg(feature = "serde")]
+pub use config::BoxedSchedulerCheckpoint;
+pub use config::{BoxedScheduler, SchedulerConfig, ToConfig};
+pub use constant::Constant;
+#[cfg(feature = "serde")]
+pub use constant::ConstantConfig;
+pub use cosine::{CosineAnnealing, CosineAnnealingWarmRestarts, Restart};
+#[cfg(feature = "serde")]
+pub use cosine::{CosineAnnealingConfig, CosineAnnealingWarmRestartsConfig};
+#[cfg(feature = "serde")]
+pub use cyclic::CyclicLrConfig;
+pub use cyclic::{CyclicLr, CyclicLrBuilder, CyclicMode, ScaleFn, ScaleMode};
+pub use decay::{ExponentialLr, FactorFn, MultiplicativeLr, PolynomialLr};
+#[cfg(feature = "serde")]
+pub use decay::{ExponentialLrConfig, MultiplicativeLrConfig, PolynomialLrConfig};
+pub use error::SchedulerError;
+pub use hf::{get_scheduler, HfScheduler, SchedulerKwargs, SchedulerType, WsdShape};
+pub use hyperparams::{Hyperparam, Hyperparams, UnsupportedPolicy};
+#[cfg(feature = "serde")]
+pub use inverse_sqrt::InverseSqrtConfig;
+pub use inverse_sqrt::{InverseSqrt, InverseSqrtBuilder};
+pub use lambda::LambdaLr;
+#[cfg(feature = "serde")]
+pub use lambda::LambdaLrConfig;
+pub use lr_finder::{LrFinder, LrFinderBuilder, LrSweep, Suggestion, SweepMode};
+pub use one_cycle::{OneCycle, OneCycleBuilder};
+pub use phase::{Duration, Phase, PhaseSchedule, PhaseScheduleBuilder, PhaseSpec};
+#[cfg(feature = "serde")]
+pub use plateau::ReduceLrOnPlateauCheckpoint;
+pub use plateau::{
+    Metric, PlateauMode, ReduceLrOnPlateau, ReduceLrOnPlateauBuilder, ReduceLrOnPlateauConfig,
+    Reduction, ThresholdMode,
+};
+#[cfg(feature = "plot")]
+pub use plot::Plot;
+#[cfg(feature = "serde")]
+pub use state::{SchedulerState, StateDict};
+pub use step_lr::{MultiStepLr, StepLr};
+#[cfg(feature = "serde")]
+pub use step_lr::{MultiStepLrConfig, StepLrConfig};
+#[cfg(feature = "serde")]
+pub use warmup::WarmupConfig;
+pub use warmup::{Warmup, WarmupBuilder};
+pub use wsd::{Wsd, WsdBuilder};
+#[cfg(feature = "serde")]
+pub use wsd::{WsdCheckpoint, WsdConfig};
+
+/// A learning rate scheduler that can drive any [`Optimizer`].
+///
+/// Every scheduler in this crate implements this trait, so they can be used
+/// interchangeably in generic code or behind a `Box<dyn LrScheduler<O>>`.
+pub trait LrScheduler<O: Optimizer> {
+    /// Advance the schedule by one step and update the optimizer.
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError>;
+
+    /// The learning rate at the current step.
+    fn get_lr(&self) -> f64;
+
+    /// Number of steps taken so far.
+    fn step_num(&self) -> usize;
+
+    /// Rewind the schedule back to its first step.
+    fn reset(&mut self);
 }
 
-impl OneCycle {
-    pub fn new(max_lr: f64, max_momentum: f64, div_factor: f32, total_steps: usize) -> Self {
-        let min_lr = max_lr / div_factor as f64;
-
-        OneCycle {
-            lr: min_lr,
-            momentum: max_momentum,
-            phases: build_phases(
-                max_lr,
-                max_lr / div_factor as f64,
-                max_momentum,
-                max_momentum / div_factor as f64,
-                total_steps,
-                0.3,
-            ),
-            step_num: 0,
-        }
-    }
-
-    pub fn step(&mut self, optimizer: &mut AdamW) {
-        self.step_num += 1;
-
-        let mut start_step = 0;
-
-        for phase in self.phases.as_slice() {
-            if self.step_num <= phase.end_step {
-                let pct =
-                    (self.step_num - start_step) as f64 / (phase.end_step - start_step) as f64;
-                self.lr = cos_annealing(phase.start_lr, phase.end_lr, pct);
-
-                self.momentum = cos_annealing(phase.start_momentum, phase.end_momentum, pct);
-                break;
-            };
-            start_step = phase.end_step;
-        }
-
-        optimizer.set_learning_rate(self.lr);
-        let mut params = optimizer.params().clone();
-        params.beta1 = self.momentum;
-        optimizer.set_params(params.clone());
-    }
-
-    pub fn get_lr(&self) -> f64 {
-        self.lr
+/// Closed-form evaluation of a schedule, without an optimizer.
+///
+/// Lets a schedule be previewed, plotted or resumed at any step in O(1)
+/// rather than replaying every `step()` up to it.
+pub trait Schedule {
+    /// The learning rate at `step`.
+    fn lr_at(&self, step: usize) -> f64;
+
+    /// The momentum at `step`, for schedules that drive momentum.
+    fn momentum_at(&self, _step: usize) -> Option<f64> {
+        None
     }
-
-    pub fn get_momentum(&self) -> f64 {
-        self.momentum
-    }
-}
-
-#[derive(Debug)]
-pub struct CosineAnnealing {
-    base_lr: f64,
-    lr: f64,
-    eta_min: f64,
-    max_step: usize,
-    step_num: usize,
 }
 
-impl CosineAnnealing {
-    pub fn new(lr: f64, max_step: usize, eta_min: f64) -> Self {
-        CosineAnnealing {
-            base_lr: lr,
-            lr,
-            eta_min,
-            max_step,
-            step_num: 0,
-        }
+impl<S: Schedule + ?Sized> Schedule for Box<S> {
+    fn lr_at(&self, step: usize) -> f64 {
+        (**self).lr_at(step)
     }
 
-    pub fn step(&mut self, optimizer: &mut AdamW) {
-        self.step_num += 1;
-
-        self.lr = self.eta_min
-            + (self.base_lr - self.eta_min)
-                * (1. + (PI * self.step_num as f64 / self.max_step as f64).cos())
-                / 2.;
-
-        optimizer.set_learning_rate(self.lr);
-    }
-
-    pub fn get_lr(&self) -> f64 {
-        self.lr
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        (**self).momentum_at(step)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap};
+    use candle_nn::{Optimizer, VarMap, SGD};
 
-    use crate::{CosineAnnealing, OneCycle};
+    use crate::{CosineAnnealing, LrScheduler, OneCycle};
 
     #[test]
-    fn one_cycle_test() {
+    fn boxed_schedulers_sgd_test() {
         let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+        let mut opt = SGD::new(varmap.all_vars(), 1e-4).unwrap();
+        let mut schedulers: Vec<Box<dyn LrScheduler<SGD>>> = vec![
+            Box::new(OneCycle::new(1e-3, 0.9, 25., 10)),
+            Box::new(CosineAnnealing::new(1e-3, 10, 1e-6)),
+        ];
 
-        scheduler.step(&mut opt);
+        for scheduler in schedulers.iter_mut() {
+            scheduler.step(&mut opt).unwrap();
 
-        assert_eq!(scheduler.get_lr(), 0.0005200000000000001);
-        assert_eq!(scheduler.get_momentum(), 0.46799999999999997);
-    }
+            assert_eq!(scheduler.step_num(), 1);
+            assert_eq!(opt.learning_rate(), scheduler.get_lr());
 
-    #[test]
-    fn one_cycle_mid_test() {
-        let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
-
-        // Go to mid
-        for _i in 0..=5 {
-            scheduler.step(&mut opt);
-        }
-
-        assert_eq!(scheduler.get_lr(), 0.0004131899517009691);
-        assert_eq!(scheduler.get_momentum(), 0.5641290434691278);
-    }
-
-    #[test]
-    fn one_cycle_end_test() {
-        let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+            scheduler.reset();
 
-        // Go to mid
-        for _i in 0..=10 {
-            scheduler.step(&mut opt);
+            assert_eq!(scheduler.step_num(), 0);
         }
-
-        assert_eq!(scheduler.get_lr(), 4e-5);
-        assert_eq!(scheduler.get_momentum(), 0.9);
-    }
-
-    #[test]
-    fn cosine_annealing_test() {
-        let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
-
-        scheduler.step(&mut opt);
-
-        assert_eq!(scheduler.get_lr(), 0.0009755527298894294);
-    }
-
-    #[test]
-    fn cosine_annealing_mid_test() {
-        let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
-
-        for _i in 0..=5 {
-            scheduler.step(&mut opt);
-            println!("{}", scheduler.get_lr());
-        }
-
-        assert_eq!(scheduler.get_lr(), 0.0003461460113097139);
-    }
-
-    #[test]
-    fn cosine_annealing_end_test() {
-        let varmap = VarMap::new();
-        let mut opt = AdamW::new(
-            varmap.all_vars(),
-            ParamsAdamW {
-                lr: 1e-4,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        let mut scheduler = CosineAnnealing::new(1e-3, 10, 1e-6);
-
-        for _i in 0..=10 {
-            scheduler.step(&mut opt);
-        }
-
-        assert_eq!(scheduler.get_lr(), 2.5447270110570702e-5);
     }
 }
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+
+use candle_core::Tensor;
+use candle_nn::{Optimizer, VarMap};
+
+use crate::error::{check_order, check_positive, check_range};
+use crate::{Metric, OneCycle, OneCycleBuilder, Schedule, SchedulerError};
+
+/// How [`LrFinder`] moves from `start_lr` to `end_lr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SweepMode {
+    /// Multiply the lr by the same factor every step, covering every order of
+    /// magnitude evenly.
+    Exponential,
+    /// Add the same amount every step, for a narrow range.
+    Linear,
+}
+
+/// How [`LrSweep::suggestion`] picks an lr from the loss curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Suggestion {
+    /// Where the loss falls fastest.
+    Steepest,
+    /// A tenth of the lr with the lowest loss.
+    MinLoss,
+    /// Partway down the longest stretch where the loss keeps falling, as in
+    /// fastai's `valley`. Usually the most robust of the three.
+    Valley,
+}
+
+/// Configures an [`LrFinder`].
+#[derive(Debug, Clone)]
+pub struct LrFinderBuilder {
+    start_lr: f64,
+    end_lr: f64,
+    num_steps: usize,
+    mode: SweepMode,
+    smoothing: f64,
+    divergence_threshold: f64,
+}
+
+impl LrFinderBuilder {
+    /// Steps to sweep over, at most. Defaults to 100.
+    pub fn num_steps(mut self, num_steps: usize) -> Self {
+        self.num_steps = num_steps;
+        self
+    }
+
+    /// Defaults to [`SweepMode::Exponential`].
+    pub fn mode(mut self, mode: SweepMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The weight of the running average of the loss over the latest loss,
+    /// 0 for no smoothing. Defaults to 0.98.
+    pub fn smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Stop once the smoothed loss is this many times its lowest value, or
+    /// for a negative lowest value, once it has risen by `threshold - 1` times
+    /// its magnitude. Defaults to 4.
+    pub fn divergence_threshold(mut self, divergence_threshold: f64) -> Self {
+        self.divergence_threshold = divergence_threshold;
+        self
+    }
+
+    /// Requires `0 < start_lr <= end_lr`, at least 2 steps, `smoothing` in
+    /// `[0, 0.999]` and a `divergence_threshold` of at least 1.
+    pub fn build(&self) -> Result<LrFinder, SchedulerError> {
+        check_positive("start_lr", self.start_lr)?;
+        check_positive("end_lr", self.end_lr)?;
+        check_order(("start_lr", self.start_lr), ("end_lr", self.end_lr))?;
+        check_range("num_steps", self.num_steps as f64, 2., f64::INFINITY)?;
+        check_range("smoothing", self.smoothing, 0., 0.999)?;
+        check_range(
+            "divergence_threshold",
+            self.divergence_threshold,
+            1.,
+            f64::INFINITY,
+        )?;
+
+        Ok(LrFinder {
+            start_lr: self.start_lr,
+            end_lr: self.end_lr,
+            num_steps: self.num_steps,
+            mode: self.mode,
+            smoothing: self.smoothing,
+            divergence_threshold: self.divergence_threshold,
+        })
+    }
+}
+
+/// A learning rate range test: train for a few steps while raising the lr,
+/// and watch where the loss falls fastest and where it blows up.
+///
+/// The weights in the [`VarMap`] are restored afterwards, and so is the
+/// optimizer's lr. Optimizer state such as AdamW's moments isn't, so start
+/// training with a fresh optimizer.
+///
+/// ```no_run
+/// use candle_core::{Device, Tensor};
+/// use candle_nn::{Optimizer, VarMap, SGD};
+/// use candle_scheduler::{LrFinder, Suggestion};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let varmap = VarMap::new();
+/// # let loss = |_step: usize| Tensor::new(1f64, &Device::Cpu);
+/// let mut opt = SGD::new(varmap.all_vars(), 0.)?;
+///
+/// let sweep = LrFinder::new(1e-7, 10., 100).run(&varmap, &mut opt, loss)?;
+/// let scheduler = sweep
+///     .one_cycle(Suggestion::Valley)
+///     .expect("the loss never fell")
+///     .total_steps(10_000)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LrFinder {
+    start_lr: f64,
+    end_lr: f64,
+    num_steps: usize,
+    mode: SweepMode,
+    smoothing: f64,
+    divergence_threshold: f64,
+}
+
+impl LrFinder {
+    /// An exponential sweep from `start_lr` to `end_lr` over `num_steps`.
+    ///
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`LrFinderBuilder::build`].
+    pub fn new(start_lr: f64, end_lr: f64, num_steps: usize) -> Self {
+        Self::builder(start_lr, end_lr)
+            .num_steps(num_steps)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid LrFinder: {err}"))
+    }
+
+    /// Start configuring a sweep from `start_lr` to `end_lr`.
+    pub fn builder(start_lr: f64, end_lr: f64) -> LrFinderBuilder {
+        LrFinderBuilder {
+            start_lr,
+            end_lr,
+            num_steps: 100,
+            mode: SweepMode::Exponential,
+            smoothing: 0.98,
+            divergence_threshold: 4.,
+        }
+    }
+
+    /// Run the sweep. `loss` is called with the step number and computes the
+    /// loss of the next batch; the optimizer then takes a step on it.
+    ///
+    /// Stops early if the loss isn't finite or diverges. Errors from `loss`
+    /// or the optimizer end the sweep too, after restoring the weights.
+    pub fn run<O: Optimizer>(
+        &self,
+        varmap: &VarMap,
+        optimizer: &mut O,
+        mut loss: impl FnMut(usize) -> candle_core::Result<Tensor>,
+    ) -> Result<LrSweep, SchedulerError> {
+        let snapshot = snapshot(varmap).map_err(training_error)?;
+        let initial_lr = optimizer.learning_rate();
+
+        let sweep = self.sweep(optimizer, &mut loss);
+
+        optimizer.set_learning_rate(initial_lr);
+        restore(varmap, &snapshot).map_err(training_error)?;
+        sweep
+    }
+
+    fn sweep<O: Optimizer>(
+        &self,
+        optimizer: &mut O,
+        loss: &mut impl FnMut(usize) -> candle_core::Result<Tensor>,
+    ) -> Result<LrSweep, SchedulerError> {
+        let mut sweep = LrSweep {
+            lrs: Vec::with_capacity(self.num_steps),
+            losses: Vec::with_capacity(self.num_steps),
+            raw_losses: Vec::with_capacity(self.num_steps),
+            mode: self.mode,
+            diverged: false,
+        };
+        let mut average = 0.;
+        let mut best = f64::INFINITY;
+
+        for step in 0..self.num_steps {
+            let lr = self.lr_at(step);
+            optimizer.set_learning_rate(lr);
+
+            let loss = loss(step).map_err(training_error)?;
+            let value = loss.value()?;
+            if !value.is_finite() {
+                sweep.diverged = true;
+                break;
+            }
+            optimizer.backward_step(&loss).map_err(training_error)?;
+
+            // Debiased like Adam's moments, so early steps aren't pulled to 0.
+            average = self.smoothing * average + (1. - self.smoothing) * value;
+            let smoothed = average / (1. - self.smoothing.powi(step as i32 + 1));
+
+            sweep.lrs.push(lr);
+            sweep.losses.push(smoothed);
+            sweep.raw_losses.push(value);
+
+            best = best.min(smoothed);
+            if smoothed > best + (self.divergence_threshold - 1.) * best.abs() {
+                sweep.diverged = true;
+                break;
+            }
+        }
+
+        Ok(sweep)
+    }
+}
+
+/// The lr at each step of the sweep, so it can be previewed or plotted.
+impl Schedule for LrFinder {
+    fn lr_at(&self, step: usize) -> f64 {
+        let pct = step.min(self.num_steps - 1) as f64 / (self.num_steps - 1) as f64;
+
+        match self.mode {
+            SweepMode::Exponential => self.start_lr * (self.end_lr / self.start_lr).powf(pct),
+            SweepMode::Linear => self.start_lr + (self.end_lr - self.start_lr) * pct,
+        }
+    }
+}
+
+fn training_error(err: candle_core::Error) -> SchedulerError {
+    SchedulerError::Training(err.to_string())
+}
+
+/// Copies of the weights in `varmap`, to [`restore`] later.
+pub(crate) fn snapshot(varmap: &VarMap) -> candle_core::Result<HashMap<String, Tensor>> {
+    let data = varmap.data().lock().unwrap();
+    data.iter()
+        .map(|(name, var)| Ok((name.clone(), var.as_tensor().copy()?)))
+        .collect()
+}
+
+pub(crate) fn restore(
+    varmap: &VarMap,
+    snapshot: &HashMap<String, Tensor>,
+) -> candle_core::Result<()> {
+    let data = varmap.data().lock().unwrap();
+    for (name, var) in data.iter() {
+        if let Some(weights) = snapshot.get(name) {
+            var.set(weights)?;
+        }
+    }
+    Ok(())
+}
+
+/// The lrs an [`LrFinder`] tried and the losses they gave, up to where the
+/// loss diverged.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LrSweep {
+    lrs: Vec<f64>,
+    losses: Vec<f64>,
+    raw_losses: Vec<f64>,
+    mode: SweepMode,
+    diverged: bool,
+}
+
+impl LrSweep {
+    pub fn lrs(&self) -> &[f64] {
+        &self.lrs
+    }
+
+    /// The smoothed losses, one per lr.
+    pub fn losses(&self) -> &[f64] {
+        &self.losses
+    }
+
+    pub fn raw_losses(&self) -> &[f64] {
+        &self.raw_losses
+    }
+
+    /// Whether the sweep stopped early because the loss blew up.
+    pub fn diverged(&self) -> bool {
+        self.diverged
+    }
+
+    /// An lr to train with, or `None` if the sweep is too short to tell.
+    pub fn suggestion(&self, method: Suggestion) -> Option<f64> {
+        let index = match method {
+            Suggestion::Steepest => self.steepest()?,
+            Suggestion::MinLoss => {
+                let (index, _) = self
+                    .losses
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+                return Some(self.lrs[index] / 10.);
+            }
+            Suggestion::Valley => self.valley()?,
+        };
+        Some(self.lrs[index])
+    }
+
+    /// A [`OneCycle`] peaking at the suggested lr, still needing its total
+    /// steps.
+    pub fn one_cycle(&self, method: Suggestion) -> Option<OneCycleBuilder> {
+        self.suggestion(method).map(OneCycle::builder)
+    }
+
+    /// Where the gradient of the loss over the lr, on the lr's scale, is the
+    /// most negative.
+    fn steepest(&self) -> Option<usize> {
+        if self.losses.len() < 3 {
+            return None;
+        }
+
+        let xs: Vec<f64> = match self.mode {
+            SweepMode::Exponential => self.lrs.iter().map(|lr| lr.ln()).collect(),
+            SweepMode::Linear => self.lrs.clone(),
+        };
+        let last = self.losses.len() - 1;
+        (0..=last)
+            .map(|i| {
+                let (before, after) = (i.saturating_sub(1), (i + 1).min(last));
+                (self.losses[after] - self.losses[before]) / (xs[after] - xs[before])
+            })
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// A third and a half into the longest decreasing subsequence of the
+    /// losses.
+    fn valley(&self) -> Option<usize> {
+        let n = self.losses.len();
+        if n < 2 {
+            return None;
+        }
+
+        let mut lengths = vec![1; n];
+        let (mut start, mut end) = (0, 0);
+        for i in 1..n {
+            for j in 0..i {
+                if self.losses[i] < self.losses[j] && lengths[i] < lengths[j] + 1 {
+                    lengths[i] = lengths[j] + 1;
+                }
+            }
+            if lengths[end] < lengths[i] {
+                end = i;
+                start = end.saturating_sub(lengths[end]);
+            }
+        }
+
+        let sections = (end - start) / 3;
+        Some(start + sections + sections / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::{DType, Device, Tensor};
+    use candle_nn::{Init, Optimizer, VarMap, SGD};
+
+    use crate::{LrFinder, Schedule, SchedulerError, Suggestion, SweepMode};
+
+    /// Falls around an lr of 1e-4, bottoms out near 1e-2, then blows up.
+    fn loss(lr: f64) -> f64 {
+        1. + 1. / (1. + (4. * (lr.log10() + 4.)).exp()) + 100. * (lr - 1e-2).max(0.)
+    }
+
+    fn decades_apart(a: f64, b: f64) -> f64 {
+        (a.log10() - b.log10()).abs()
+    }
+
+    #[test]
+    fn lr_finder_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.5).unwrap();
+        let finder = LrFinder::builder(1e-6, 1.)
+            .num_steps(100)
+            .smoothing(0.)
+            .build()
+            .unwrap();
+
+        let sweep = finder
+            .run(&varmap, &mut opt, |step| {
+                Tensor::new(loss(finder.lr_at(step)), &Device::Cpu)
+            })
+            .unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.5);
+        assert!(sweep.diverged());
+        assert!(sweep.lrs().len() < 100);
+        assert_eq!(sweep.lrs()[0], 1e-6);
+        assert_eq!(sweep.losses(), sweep.raw_losses());
+
+        let steepest = sweep.suggestion(Suggestion::Steepest).unwrap();
+        assert!(decades_apart(steepest, 1e-4) < 0.1, "{steepest}");
+        let min_loss = sweep.suggestion(Suggestion::MinLoss).unwrap();
+        assert!(decades_apart(min_loss, 1e-3) < 0.1, "{min_loss}");
+        let valley = sweep.suggestion(Suggestion::Valley).unwrap();
+        assert!(decades_apart(valley, 1e-4) < 0.5, "{valley}");
+
+        let scheduler = sweep
+            .one_cycle(Suggestion::Valley)
+            .unwrap()
+            .total_steps(100)
+            .build()
+            .unwrap();
+        assert_eq!(scheduler.phases()[0].end_lr(), valley);
+    }
+
+    #[test]
+    fn lr_finder_sweep_test() {
+        let finder = LrFinder::builder(1e-3, 1e-1)
+            .num_steps(3)
+            .mode(SweepMode::Linear)
+            .build()
+            .unwrap();
+
+        assert_eq!(finder.lr_at(1), 0.0505);
+        assert!((LrFinder::new(1e-3, 1e-1, 3).lr_at(1) - 1e-2).abs() < 1e-15);
+        assert_eq!(finder.lr_at(10), 1e-1);
+
+        assert!(matches!(
+            LrFinder::builder(1., 1e-3).build(),
+            Err(SchedulerError::Unordered { .. })
+        ));
+        assert!(matches!(
+            LrFinder::builder(1e-3, 1.).smoothing(1.).build(),
+            Err(SchedulerError::OutOfRange {
+                name: "smoothing",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn lr_finder_error_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.5).unwrap();
+
+        let sweep = LrFinder::new(1e-6, 1., 10).run(&varmap, &mut opt, |step| match step {
+            3 => Err(candle_core::Error::Msg("out of memory".to_string())),
+            _ => Tensor::new(1f64, &Device::Cpu),
+        });
+
+        assert!(matches!(sweep, Err(SchedulerError::Training(_))));
+        assert_eq!(opt.learning_rate(), 0.5);
+
+        let sweep = LrFinder::new(1e-6, 1., 10)
+            .run(&varmap, &mut opt, |step| {
+                Tensor::new(if step < 5 { 1. } else { f64::NAN }, &Device::Cpu)
+            })
+            .unwrap();
+
+        assert!(sweep.diverged());
+        assert_eq!(sweep.lrs().len(), 5);
+    }
+
+    #[test]
+    fn lr_finder_restore_test() {
+        let varmap = VarMap::new();
+        let w = varmap
+            .get(2, "w", Init::Const(1.), DType::F64, &Device::Cpu)
+            .unwrap();
+        let mut opt = SGD::new(varmap.all_vars(), 0.5).unwrap();
+        let finder = LrFinder::new(1e-3, 1e-1, 10);
+
+        let mut moved = false;
+        finder
+            .run(&varmap, &mut opt, |_step| {
+                moved |= w.to_vec1::<f64>()? != [1., 1.];
+                w.sqr()?.sum_all()
+            })
+            .unwrap();
+
+        assert!(moved);
+        assert_eq!(w.to_vec1::<f64>().unwrap(), [1., 1.]);
+
+        let mut moved = false;
+        let sweep = finder.run(&varmap, &mut opt, |step| {
+            moved |= w.to_vec1::<f64>()? != [1., 1.];
+            match step {
+                5 => Err(candle_core::Error::Msg("out of memory".to_string())),
+                _ => w.sqr()?.sum_all(),
+            }
+        });
+
+        assert!(matches!(sweep, Err(SchedulerError::Training(_))));
+        assert!(moved);
+        assert_eq!(w.to_vec1::<f64>().unwrap(), [1., 1.]);
+    }
+
+    #[test]
+    fn lr_finder_negative_loss_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.5).unwrap();
+        let finder = LrFinder::builder(1e-6, 1.)
+            .num_steps(10)
+            .smoothing(0.)
+            .build()
+            .unwrap();
+
+        let sweep = finder
+            .run(&varmap, &mut opt, |_step| Tensor::new(-10f64, &Device::Cpu))
+            .unwrap();
+
+        assert!(!sweep.diverged());
+        assert_eq!(sweep.lrs().len(), 10);
+
+        // Step 5 reaches -10 + 3 * 10 = 20, which is still fine.
+        let sweep = finder
+            .run(&varmap, &mut opt, |step| {
+                Tensor::new(
+                    if step < 5 { -10f64 } else { 15. + step as f64 },
+                    &Device::Cpu,
+                )
+            })
+            .unwrap();
+
+        assert!(sweep.diverged());
+        assert_eq!(sweep.lrs().len(), 7);
+    }
+}
@@ -0,0 +1,760 @@
+use std::fmt;
+
+use crate::config::non_default;
+use crate::error::{check_positive, check_range, check_steps};
+use crate::{
+    Anneal, Hyperparams, LrScheduler, Phase, PhaseSchedule, Schedule, SchedulerConfig,
+    SchedulerError, ToConfig, UnsupportedPolicy,
+};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// The 1cycle policy from "Super-Convergence" (Smith & Topin, 2017), with
+/// the same options and values as PyTorch's `OneCycleLR`.
+#[derive(Debug)]
+pub struct OneCycle {
+    schedule: PhaseSchedule,
+    cycle_momentum: bool,
+    /// Kept to describe the cycle, see [`SchedulerConfig`].
+    builder: OneCycleBuilder,
+}
+
+/// Configures a [`OneCycle`], mirroring the arguments of PyTorch's
+/// `OneCycleLR`. Defaults match PyTorch.
+#[derive(Debug, Clone)]
+pub struct OneCycleBuilder {
+    max_lr: f64,
+    total_steps: Option<usize>,
+    epochs: Option<(usize, usize)>,
+    pct_start: f64,
+    anneal_strategy: Anneal,
+    phase_anneals: Vec<(usize, Anneal)>,
+    cycle_momentum: bool,
+    base_momentum: f64,
+    max_momentum: f64,
+    div_factor: f64,
+    final_div_factor: f64,
+    three_phase: bool,
+}
+
+impl OneCycleBuilder {
+    pub fn total_steps(mut self, total_steps: usize) -> Self {
+        self.total_steps = Some(total_steps);
+        self
+    }
+
+    /// Derive the total steps as `epochs * steps_per_epoch`, used when
+    /// `total_steps` isn't set.
+    pub fn epochs(mut self, epochs: usize, steps_per_epoch: usize) -> Self {
+        self.epochs = Some((epochs, steps_per_epoch));
+        self
+    }
+
+    /// Fraction of the cycle spent increasing the lr. Defaults to 0.3.
+    pub fn pct_start(mut self, pct_start: f64) -> Self {
+        self.pct_start = pct_start;
+        self
+    }
+
+    /// Strategy for every phase without its own from
+    /// [`OneCycleBuilder::phase_anneal`]. Defaults to [`Anneal::Cos`].
+    pub fn anneal_strategy(mut self, anneal_strategy: Anneal) -> Self {
+        self.anneal_strategy = anneal_strategy;
+        self
+    }
+
+    /// Strategy for the phase at `index`: 0 is the warmup, 1 the decay, and 2
+    /// the final annihilation with [`OneCycleBuilder::three_phase`].
+    pub fn phase_anneal(mut self, index: usize, anneal: Anneal) -> Self {
+        self.phase_anneals.push((index, anneal));
+        self
+    }
+
+    /// Cycle momentum inversely to the lr. Defaults to true.
+    pub fn cycle_momentum(mut self, cycle_momentum: bool) -> Self {
+        self.cycle_momentum = cycle_momentum;
+        self
+    }
+
+    /// Lowest momentum, reached at the peak lr. Defaults to 0.85.
+    pub fn base_momentum(mut self, base_momentum: f64) -> Self {
+        self.base_momentum = base_momentum;
+        self
+    }
+
+    /// Highest momentum, at the start and end of the cycle. Defaults to 0.95.
+    pub fn max_momentum(mut self, max_momentum: f64) -> Self {
+        self.max_momentum = max_momentum;
+        self
+    }
+
+    /// The initial lr is `max_lr / div_factor`. Defaults to 25.
+    pub fn div_factor(mut self, div_factor: f64) -> Self {
+        self.div_factor = div_factor;
+        self
+    }
+
+    /// The final lr is `initial_lr / final_div_factor`. Defaults to 1e4.
+    pub fn final_div_factor(mut self, final_div_factor: f64) -> Self {
+        self.final_div_factor = final_div_factor;
+        self
+    }
+
+    /// Anneal back down to the initial lr before annihilating it in a third
+    /// phase, as in fastai, instead of going straight to the final lr.
+    pub fn three_phase(mut self, three_phase: bool) -> Self {
+        self.three_phase = three_phase;
+        self
+    }
+
+    pub fn build(&self) -> Result<OneCycle, SchedulerError> {
+        check_positive("max_lr", self.max_lr)?;
+        check_range("pct_start", self.pct_start, 0., 1.)?;
+        check_range("max_momentum", self.max_momentum, 0., 1.)?;
+        check_range("base_momentum", self.base_momentum, 0., self.max_momentum)?;
+        check_positive("div_factor", self.div_factor)?;
+        check_positive("final_div_factor", self.final_div_factor)?;
+
+        let total_steps = match (self.total_steps, self.epochs) {
+            (Some(total_steps), _) => check_steps("total_steps", total_steps)?,
+            (None, Some((epochs, steps_per_epoch))) => {
+                check_steps("epochs", epochs)? * check_steps("steps_per_epoch", steps_per_epoch)?
+            }
+            (None, None) => {
+                return Err(SchedulerError::Missing {
+                    name: "total_steps or epochs",
+                })
+            }
+        };
+        let total_steps = total_steps as f64;
+
+        let initial_lr = self.max_lr / self.div_factor;
+        let min_lr = initial_lr / self.final_div_factor;
+
+        let (max_momentum, base_momentum) = if self.cycle_momentum {
+            (self.max_momentum, self.base_momentum)
+        } else {
+            (self.max_momentum, self.max_momentum)
+        };
+
+        let phase = |end_step, start_lr, end_lr, start_momentum, end_momentum| {
+            Phase::new(
+                end_step,
+                (start_lr, end_lr),
+                Some((start_momentum, end_momentum)),
+                self.anneal_strategy.clone(),
+            )
+        };
+
+        let mut phases = if self.three_phase {
+            vec![
+                phase(
+                    self.pct_start * total_steps - 1.,
+                    initial_lr,
+                    self.max_lr,
+                    max_momentum,
+                    base_momentum,
+                ),
+                phase(
+                    2. * self.pct_start * total_steps - 2.,
+                    self.max_lr,
+                    initial_lr,
+                    base_momentum,
+                    max_momentum,
+                ),
+                phase(
+                    total_steps - 1.,
+                    initial_lr,
+                    min_lr,
+                    max_momentum,
+                    max_momentum,
+                ),
+            ]
+        } else {
+            vec![
+                phase(
+                    self.pct_start * total_steps - 1.,
+                    initial_lr,
+                    self.max_lr,
+                    max_momentum,
+                    base_momentum,
+                ),
+                phase(
+                    total_steps - 1.,
+                    self.max_lr,
+                    min_lr,
+                    base_momentum,
+                    max_momentum,
+                ),
+            ]
+        };
+
+        for (index, anneal) in &self.phase_anneals {
+            let phase = phases
+                .get_mut(*index)
+                .ok_or(SchedulerError::NoSuchPhase { index: *index })?;
+            phase.set_anneal(anneal.clone());
+        }
+
+        Ok(OneCycle {
+            schedule: PhaseSchedule::from_phases(phases)?,
+            cycle_momentum: self.cycle_momentum,
+            builder: self.clone(),
+        })
+    }
+
+    /// Build one scheduler per entry of `max_lrs`, like PyTorch's per param
+    /// group `max_lr`. Candle optimizers have a single lr, so each group is
+    /// its own optimizer.
+    pub fn build_groups(&self, max_lrs: &[f64]) -> Result<Vec<OneCycle>, SchedulerError> {
+        max_lrs
+            .iter()
+            .map(|&max_lr| {
+                OneCycleBuilder {
+                    max_lr,
+                    ..self.clone()
+                }
+                .build()
+            })
+            .collect()
+    }
+}
+
+impl OneCycle {
+    /// A cycle peaking at `max_lr` after 30% of `total_steps`, with momentum
+    /// going from `max_momentum` down to `max_momentum / div_factor` and back.
+    /// Unlike PyTorch, it ends at the initial lr of `max_lr / div_factor`; use
+    /// [`OneCycle::builder`] for the full set of options.
+    ///
+    /// Like PyTorch, the peak is at step `0.3 * total_steps - 1`, which may be
+    /// fractional. Before the builder existed it was rounded to a whole step,
+    /// so for `total_steps` that aren't a multiple of 10 the lrs around the
+    /// peak differ slightly from earlier versions.
+    ///
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`OneCycle::try_new`].
+    pub fn new(max_lr: f64, max_momentum: f64, div_factor: f32, total_steps: usize) -> Self {
+        Self::try_new(max_lr, max_momentum, div_factor, total_steps)
+            .unwrap_or_else(|err| panic!("invalid OneCycle: {err}"))
+    }
+
+    /// Requires a positive finite `max_lr`, `max_momentum` in `[0, 1]`,
+    /// `div_factor >= 1` and enough `total_steps` for both phases to span at
+    /// least one step.
+    pub fn try_new(
+        max_lr: f64,
+        max_momentum: f64,
+        div_factor: f32,
+        total_steps: usize,
+    ) -> Result<Self, SchedulerError> {
+        let div_factor = check_range("div_factor", div_factor as f64, 1., f64::INFINITY)?;
+
+        OneCycle::builder(max_lr)
+            .total_steps(total_steps)
+            .max_momentum(max_momentum)
+            .base_momentum(max_momentum / div_factor)
+            .div_factor(div_factor)
+            .final_div_factor(1.)
+            .build()
+    }
+
+    /// Start configuring a cycle that peaks at `max_lr`.
+    pub fn builder(max_lr: f64) -> OneCycleBuilder {
+        OneCycleBuilder {
+            max_lr,
+            total_steps: None,
+            epochs: None,
+            pct_start: 0.3,
+            anneal_strategy: Anneal::Cos,
+            phase_anneals: Vec::new(),
+            cycle_momentum: true,
+            base_momentum: 0.85,
+            max_momentum: 0.95,
+            div_factor: 25.,
+            final_div_factor: 1e4,
+            three_phase: false,
+        }
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.schedule.unsupported = policy;
+        self
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        self.schedule.phases()
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.schedule.get_lr()
+    }
+
+    /// With momentum cycling off this stays at `max_momentum`.
+    pub fn get_momentum(&self) -> f64 {
+        self.schedule
+            .get_momentum()
+            .expect("OneCycle phases have momentum")
+    }
+}
+
+impl Schedule for OneCycle {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.schedule.lr_at(step)
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        self.schedule
+            .momentum_at(step)
+            .filter(|_| self.cycle_momentum)
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for OneCycle {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        if self.cycle_momentum {
+            return self.schedule.step(optimizer);
+        }
+
+        self.schedule.step_num += 1;
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.schedule.step_num
+    }
+
+    fn reset(&mut self) {
+        self.schedule.step_num = 0;
+    }
+}
+
+impl ToConfig for OneCycle {
+    fn to_config(&self) -> SchedulerConfig {
+        let builder = &self.builder;
+        let default = OneCycle::builder(builder.max_lr);
+
+        SchedulerConfig::OneCycle {
+            max_lr: builder.max_lr,
+            total_steps: builder.total_steps,
+            epochs: builder.epochs.map(|(epochs, _)| epochs),
+            steps_per_epoch: builder.epochs.map(|(_, steps_per_epoch)| steps_per_epoch),
+            pct_start: non_default(builder.pct_start, default.pct_start),
+            anneal_strategy: non_default(builder.anneal_strategy.clone(), default.anneal_strategy),
+            phase_anneals: non_default(builder.phase_anneals.clone(), default.phase_anneals),
+            cycle_momentum: non_default(builder.cycle_momentum, default.cycle_momentum),
+            base_momentum: non_default(builder.base_momentum, default.base_momentum),
+            max_momentum: non_default(builder.max_momentum, default.max_momentum),
+            div_factor: non_default(builder.div_factor, default.div_factor),
+            final_div_factor: non_default(builder.final_div_factor, default.final_div_factor),
+            three_phase: non_default(builder.three_phase, default.three_phase),
+        }
+    }
+}
+
+/// Formats as a schedule expression with the options that differ from
+/// PyTorch's defaults, e.g. `one_cycle(0.001, 1000, pct_start=0.25)`.
+impl fmt::Display for OneCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for OneCycle {
+    type Config = Vec<Phase>;
+
+    fn state_dict(&self) -> SchedulerState<Vec<Phase>> {
+        SchedulerState {
+            momentum: self.momentum_at(self.schedule.step_num),
+            ..self.schedule.state_dict()
+        }
+    }
+
+    fn load_state_dict(&mut self, state: SchedulerState<Vec<Phase>>) -> Result<(), SchedulerError> {
+        self.schedule.load_state_dict(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{Anneal, LrScheduler, OneCycle, Schedule, SchedulerError, UnsupportedPolicy};
+
+    #[test]
+    fn one_cycle_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(scheduler.get_lr(), 0.0005200000000000001);
+        assert_eq!(scheduler.get_momentum(), 0.46799999999999997);
+    }
+
+    #[test]
+    fn one_cycle_mid_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        // Go to mid
+        for _i in 0..=5 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(scheduler.get_lr(), 0.0004131899517009691);
+        assert_eq!(scheduler.get_momentum(), 0.5641290434691278);
+    }
+
+    #[test]
+    fn one_cycle_end_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(
+            varmap.all_vars(),
+            ParamsAdamW {
+                lr: 1e-4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        // Go to mid
+        for _i in 0..=10 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(scheduler.get_lr(), 4e-5);
+        assert_eq!(scheduler.get_momentum(), 0.9);
+    }
+
+    #[test]
+    fn one_cycle_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, scheduler.get_momentum());
+    }
+
+    #[test]
+    fn one_cycle_unsupported_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1e-4).unwrap();
+        let mut scheduler =
+            OneCycle::new(1e-3, 0.9, 25., 10).with_unsupported_policy(UnsupportedPolicy::Error);
+
+        let result = scheduler.step(&mut opt);
+
+        assert!(matches!(
+            result,
+            Err(SchedulerError::UnsupportedHyperparam { .. })
+        ));
+        assert_eq!(opt.learning_rate(), scheduler.get_lr());
+    }
+
+    #[test]
+    fn one_cycle_lr_at_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        assert_eq!(scheduler.lr_at(0), 3.9999999999999996e-5);
+        assert_eq!(scheduler.momentum_at(0), Some(0.9));
+
+        for step in 1..=12 {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(scheduler.lr_at(step), scheduler.get_lr());
+            assert_eq!(scheduler.momentum_at(step), Some(scheduler.get_momentum()));
+        }
+    }
+
+    #[test]
+    fn one_cycle_fractional_peak_test() {
+        let scheduler = OneCycle::new(1e-3, 0.9, 25., 15);
+
+        assert_eq!(scheduler.phases()[0].end_step(), 3.5);
+        // Rounding the peak to step 4 used to give exactly 1e-3 there.
+        assert_eq!(scheduler.lr_at(3), 0.0009524650565931611);
+        assert_eq!(scheduler.lr_at(4), 0.0009946387965880617);
+        assert_eq!(scheduler.lr_at(5), 0.0009524650565931611);
+    }
+
+    #[test]
+    fn one_cycle_invalid_test() {
+        assert_eq!(
+            OneCycle::try_new(1e-3, 0.9, 25., 0).unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "total_steps"
+            }
+        );
+        assert_eq!(
+            OneCycle::try_new(1e-3, 0.9, 25., 3).unwrap_err(),
+            SchedulerError::EmptyPhase { index: 0 }
+        );
+        assert!(matches!(
+            OneCycle::try_new(f64::NAN, 0.9, 25., 10),
+            Err(SchedulerError::NonFinite { name: "max_lr", .. })
+        ));
+        assert!(matches!(
+            OneCycle::try_new(1e-3, 1.5, 25., 10),
+            Err(SchedulerError::OutOfRange {
+                name: "max_momentum",
+                ..
+            })
+        ));
+        assert!(OneCycle::try_new(1e-3, 0.9, 25., 5).is_ok());
+
+        assert!(matches!(
+            OneCycle::builder(1e-3)
+                .total_steps(10)
+                .anneal_strategy(Anneal::Exponential)
+                .base_momentum(0.)
+                .build(),
+            Err(SchedulerError::ExponentialEndpoints { index: 0, .. })
+        ));
+        assert!(matches!(
+            OneCycle::builder(1e-3)
+                .total_steps(10)
+                .phase_anneal(1, Anneal::Polynomial { power: 0. })
+                .build(),
+            Err(SchedulerError::NonPositive {
+                name: "polynomial power",
+                ..
+            })
+        ));
+        assert!(OneCycle::builder(1e-3)
+            .total_steps(10)
+            .anneal_strategy(Anneal::Exponential)
+            .build()
+            .is_ok());
+    }
+
+    // Reference values from PyTorch's OneCycleLR with the same arguments.
+    const PYTORCH_LRS: [f64; 10] = [
+        0.0040000000000000036,
+        0.052000000000000005,
+        0.1,
+        0.09504846320134738,
+        0.0811745653949763,
+        0.06112620219362893,
+        0.03887419780637107,
+        0.0188258346050237,
+        0.004951936798652629,
+        4e-07,
+    ];
+    const PYTORCH_MOMENTUMS: [f64; 10] = [
+        0.95,
+        0.8999999999999999,
+        0.85,
+        0.854951556604879,
+        0.8688255099070633,
+        0.8888739533021842,
+        0.9111260466978157,
+        0.9311744900929366,
+        0.945048443395121,
+        0.95,
+    ];
+
+    #[test]
+    fn one_cycle_pytorch_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::builder(0.1).total_steps(10).build().unwrap();
+
+        assert_eq!(scheduler.get_lr(), PYTORCH_LRS[0]);
+        assert_eq!(scheduler.get_momentum(), PYTORCH_MOMENTUMS[0]);
+
+        for step in 1..10 {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), PYTORCH_LRS[step]);
+            assert_eq!(opt.params().beta1, PYTORCH_MOMENTUMS[step]);
+        }
+    }
+
+    #[test]
+    fn one_cycle_pytorch_epochs_test() {
+        let scheduler = OneCycle::builder(0.1).epochs(2, 5).build().unwrap();
+
+        for (step, lr) in PYTORCH_LRS.iter().enumerate() {
+            assert_eq!(scheduler.lr_at(step), *lr);
+        }
+    }
+
+    #[test]
+    fn one_cycle_pytorch_three_phase_test() {
+        let scheduler = OneCycle::builder(0.1)
+            .total_steps(10)
+            .three_phase(true)
+            .build()
+            .unwrap();
+        let lrs = [
+            0.0040000000000000036,
+            0.052000000000000005,
+            0.1,
+            0.052000000000000005,
+            0.004,
+            0.0036180721853510196,
+            0.0026181721853510195,
+            0.0013822278146489802,
+            0.0003823278146489803,
+            4e-07,
+        ];
+        let momentums = [
+            0.95,
+            0.8999999999999999,
+            0.85,
+            0.8999999999999999,
+            0.95,
+            0.95,
+            0.95,
+            0.95,
+            0.95,
+            0.95,
+        ];
+
+        for step in 0..10 {
+            assert_eq!(scheduler.lr_at(step), lrs[step]);
+            assert_eq!(scheduler.momentum_at(step), Some(momentums[step]));
+        }
+    }
+
+    #[test]
+    fn one_cycle_pytorch_linear_test() {
+        let scheduler = OneCycle::builder(0.1)
+            .total_steps(10)
+            .anneal_strategy(Anneal::Linear)
+            .cycle_momentum(false)
+            .build()
+            .unwrap();
+        let lrs = [
+            0.004,
+            0.052000000000000005,
+            0.1,
+            0.08571434285714286,
+            0.07142868571428572,
+            0.05714302857142858,
+            0.042857371428571434,
+            0.028571714285714284,
+            0.014286057142857148,
+            3.999999999976245e-07,
+        ];
+
+        for (step, lr) in lrs.iter().enumerate() {
+            assert_eq!(scheduler.lr_at(step), *lr);
+            assert_eq!(scheduler.momentum_at(step), None);
+        }
+    }
+
+    #[test]
+    fn one_cycle_groups_test() {
+        let schedulers = OneCycle::builder(0.1)
+            .total_steps(10)
+            .build_groups(&[0.1, 0.01])
+            .unwrap();
+
+        assert_eq!(schedulers[0].lr_at(2), 0.1);
+        assert_eq!(schedulers[1].lr_at(2), 0.01);
+        assert_eq!(
+            OneCycle::builder(0.1).build().unwrap_err(),
+            SchedulerError::Missing {
+                name: "total_steps or epochs"
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn one_cycle_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = OneCycle::new(1e-3, 0.9, 25., 10);
+
+        for _i in 0..4 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let state = scheduler.state_dict();
+        let mut resumed = OneCycle::new(1e-3, 0.9, 25., 10);
+        resumed.load_state_dict(state).unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+        assert_eq!(resumed.get_momentum(), scheduler.get_momentum());
+
+        scheduler.step(&mut opt).unwrap();
+        resumed.step(&mut opt).unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+        assert_eq!(opt.learning_rate(), resumed.get_lr());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn one_cycle_json_resume_test() {
+        let scheduler = OneCycle::new(3e-4, 0.95, 25., 100);
+        // Computed, not typed, so it only reads back exactly with
+        // `float_roundtrip`.
+        assert_eq!(scheduler.phases()[0].start_lr(), 1.1999999999999999e-5);
+
+        let json = serde_json::to_string(&scheduler.state_dict()).unwrap();
+        let mut resumed = OneCycle::new(3e-4, 0.95, 25., 100);
+        resumed
+            .load_state_dict(serde_json::from_str(&json).unwrap())
+            .unwrap();
+
+        assert_eq!(resumed.get_lr(), scheduler.get_lr());
+    }
+
+    #[test]
+    fn one_cycle_phase_anneal_test() {
+        let scheduler = OneCycle::builder(0.1)
+            .total_steps(10)
+            .anneal_strategy(Anneal::Linear)
+            .phase_anneal(1, Anneal::Exponential)
+            .cycle_momentum(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(1), 0.052000000000000005);
+        assert_eq!(scheduler.lr_at(9), 4e-7);
+        assert!(scheduler.lr_at(5) < Anneal::Linear.anneal(0.1, 4e-7, 3. / 7.));
+        assert_eq!(
+            OneCycle::builder(0.1)
+                .total_steps(10)
+                .phase_anneal(2, Anneal::Constant)
+                .build()
+                .unwrap_err(),
+            SchedulerError::NoSuchPhase { index: 2 }
+        );
+    }
+}
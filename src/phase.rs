@@ -0,0 +1,476 @@
+use crate::error::{check_finite, check_positive, check_range, check_steps};
+use crate::{Anneal, Hyperparams, LrScheduler, Schedule, SchedulerError, UnsupportedPolicy};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// One segment of a [`PhaseSchedule`], ending at `end_step`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Phase {
+    end_step: f64,
+    start_lr: f64,
+    end_lr: f64,
+    momentum: Option<(f64, f64)>,
+    anneal: Anneal,
+}
+
+impl Phase {
+    pub(crate) fn new(
+        end_step: f64,
+        (start_lr, end_lr): (f64, f64),
+        momentum: Option<(f64, f64)>,
+        anneal: Anneal,
+    ) -> Self {
+        Phase {
+            end_step,
+            start_lr,
+            end_lr,
+            momentum,
+            anneal,
+        }
+    }
+
+    /// The step this phase ends on. It starts where the previous phase ended,
+    /// or at step 0.
+    pub fn end_step(&self) -> f64 {
+        self.end_step
+    }
+
+    pub fn start_lr(&self) -> f64 {
+        self.start_lr
+    }
+
+    pub fn end_lr(&self) -> f64 {
+        self.end_lr
+    }
+
+    /// Start and end momentum, if this phase drives momentum.
+    pub fn momentum(&self) -> Option<(f64, f64)> {
+        self.momentum
+    }
+
+    pub fn anneal(&self) -> &Anneal {
+        &self.anneal
+    }
+
+    pub(crate) fn set_anneal(&mut self, anneal: Anneal) {
+        self.anneal = anneal;
+    }
+}
+
+/// How long a phase lasts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Duration {
+    Steps(usize),
+    /// A fraction of the schedule's total steps.
+    Fraction(f64),
+}
+
+/// A phase to append to a [`PhaseScheduleBuilder`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct PhaseSpec {
+    pub(crate) duration: Duration,
+    pub(crate) lr: (f64, f64),
+    pub(crate) momentum: Option<(f64, f64)>,
+    /// Defaults to [`Anneal::Cos`], like [`PhaseSpec::new`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) anneal: Anneal,
+}
+
+impl PhaseSpec {
+    /// A phase taking the lr from `start_lr` to `end_lr` over `steps`.
+    pub fn steps(steps: usize, start_lr: f64, end_lr: f64) -> Self {
+        Self::new(Duration::Steps(steps), start_lr, end_lr)
+    }
+
+    /// A phase taking the lr from `start_lr` to `end_lr` over `fraction` of
+    /// the total steps.
+    pub fn fraction(fraction: f64, start_lr: f64, end_lr: f64) -> Self {
+        Self::new(Duration::Fraction(fraction), start_lr, end_lr)
+    }
+
+    /// Hold the lr at `lr` for `duration`.
+    pub fn constant(duration: Duration, lr: f64) -> Self {
+        Self::new(duration, lr, lr).anneal(Anneal::Constant)
+    }
+
+    pub fn new(duration: Duration, start_lr: f64, end_lr: f64) -> Self {
+        PhaseSpec {
+            duration,
+            lr: (start_lr, end_lr),
+            momentum: None,
+            anneal: Anneal::Cos,
+        }
+    }
+
+    /// Also take momentum from `start` to `end` over the phase.
+    pub fn momentum(mut self, start: f64, end: f64) -> Self {
+        self.momentum = Some((start, end));
+        self
+    }
+
+    /// Defaults to [`Anneal::Cos`].
+    pub fn anneal(mut self, anneal: Anneal) -> Self {
+        self.anneal = anneal;
+        self
+    }
+}
+
+/// Appends phases one after the other to make a [`PhaseSchedule`].
+#[derive(Debug, Clone, Default)]
+pub struct PhaseScheduleBuilder {
+    total_steps: Option<usize>,
+    phases: Vec<PhaseSpec>,
+}
+
+impl PhaseScheduleBuilder {
+    /// Needed to resolve [`Duration::Fraction`] phases.
+    pub fn total_steps(mut self, total_steps: usize) -> Self {
+        self.total_steps = Some(total_steps);
+        self
+    }
+
+    pub fn phase(mut self, phase: PhaseSpec) -> Self {
+        self.phases.push(phase);
+        self
+    }
+
+    pub fn build(&self) -> Result<PhaseSchedule, SchedulerError> {
+        let mut end_step = 0.;
+        let mut phases = Vec::with_capacity(self.phases.len());
+
+        for spec in &self.phases {
+            end_step += match spec.duration {
+                Duration::Steps(steps) => check_steps("phase steps", steps)? as f64,
+                Duration::Fraction(fraction) => {
+                    let total_steps = self.total_steps.ok_or(SchedulerError::Missing {
+                        name: "total_steps",
+                    })?;
+                    check_range("phase fraction", fraction, 0., 1.)?
+                        * check_steps("total_steps", total_steps)? as f64
+                }
+            };
+
+            check_finite("phase start lr", spec.lr.0)?;
+            check_finite("phase end lr", spec.lr.1)?;
+            if let Some((start, end)) = spec.momentum {
+                check_range("phase start momentum", start, 0., 1.)?;
+                check_range("phase end momentum", end, 0., 1.)?;
+            }
+
+            phases.push(Phase::new(
+                end_step,
+                spec.lr,
+                spec.momentum,
+                spec.anneal.clone(),
+            ));
+        }
+
+        PhaseSchedule::from_phases(phases)
+    }
+}
+
+/// A piecewise schedule of consecutive [`Phase`]s, each annealing the lr,
+/// and optionally momentum, between its own start and end values.
+///
+/// Trapezoidal, triangular, knee and other custom shapes can be built from
+/// phases without writing a new scheduler:
+///
+/// ```
+/// use candle_scheduler::{Anneal, Duration, PhaseSchedule, PhaseSpec};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// // Warmup, hold, then decay.
+/// let trapezoid = PhaseSchedule::builder()
+///     .total_steps(10_000)
+///     .phase(PhaseSpec::steps(500, 0., 1e-3).anneal(Anneal::Linear))
+///     .phase(PhaseSpec::constant(Duration::Fraction(0.75), 1e-3))
+///     .phase(PhaseSpec::fraction(0.2, 1e-3, 0.).anneal(Anneal::Linear))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Steps past the last phase stay at its end values.
+#[derive(Debug)]
+pub struct PhaseSchedule {
+    pub(crate) step_num: usize,
+    phases: Vec<Phase>,
+    pub(crate) unsupported: UnsupportedPolicy,
+}
+
+/// Each phase has to end after the one before it, otherwise computing how far
+/// through a phase a step is divides by zero.
+fn validate_phases(phases: &[Phase]) -> Result<(), SchedulerError> {
+    if phases.is_empty() {
+        return Err(SchedulerError::Missing { name: "phases" });
+    }
+
+    let mut start_step = 0.;
+
+    for (index, phase) in phases.iter().enumerate() {
+        if phase.end_step <= start_step {
+            return Err(SchedulerError::EmptyPhase { index });
+        }
+        start_step = phase.end_step;
+
+        check_anneal(index, &phase.anneal, (phase.start_lr, phase.end_lr))?;
+        if let Some(momentum) = phase.momentum {
+            check_anneal(index, &phase.anneal, momentum)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Exponential annealing is geometric, so `start * (end / start)^pct` is NaN
+/// or infinite unless both ends are non-zero with the same sign. A
+/// polynomial with a power of 0 or less blows up at the end of the phase.
+pub(crate) fn check_anneal(
+    index: usize,
+    anneal: &Anneal,
+    (start, end): (f64, f64),
+) -> Result<(), SchedulerError> {
+    match anneal {
+        Anneal::Exponential if start * end <= 0. => {
+            Err(SchedulerError::ExponentialEndpoints { index, start, end })
+        }
+        Anneal::Polynomial { power } => check_positive("polynomial power", *power).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+impl PhaseSchedule {
+    pub fn builder() -> PhaseScheduleBuilder {
+        PhaseScheduleBuilder::default()
+    }
+
+    pub(crate) fn from_phases(phases: Vec<Phase>) -> Result<Self, SchedulerError> {
+        validate_phases(&phases)?;
+
+        Ok(PhaseSchedule {
+            step_num: 0,
+            phases,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+
+    /// How to handle optimizers that can't set momentum. Defaults to
+    /// [`UnsupportedPolicy::Ignore`], which only schedules the learning rate.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+
+    /// The phase containing `step` and how far through it `step` is, in
+    /// `0..=1`. Steps past the end stay at the end of the last phase.
+    fn phase_at(&self, step: usize) -> (&Phase, f64) {
+        let step = step as f64;
+        let mut start_step = 0.;
+
+        for phase in self.phases.as_slice() {
+            if step <= phase.end_step {
+                let pct = (step - start_step) / (phase.end_step - start_step);
+                return (phase, pct);
+            };
+            start_step = phase.end_step;
+        }
+
+        (self.phases.last().expect("PhaseSchedule has phases"), 1.)
+    }
+}
+
+impl Schedule for PhaseSchedule {
+    fn lr_at(&self, step: usize) -> f64 {
+        let (phase, pct) = self.phase_at(step);
+        phase.anneal.anneal(phase.start_lr, phase.end_lr, pct)
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        let (phase, pct) = self.phase_at(step);
+        let (start, end) = phase.momentum?;
+        Some(phase.anneal.anneal(start, end, pct))
+    }
+}
+
+impl<O: Hyperparams> LrScheduler<O> for PhaseSchedule {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for PhaseSchedule {
+    type Config = Vec<Phase>;
+
+    fn state_dict(&self) -> SchedulerState<Vec<Phase>> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: self.phases.clone(),
+        }
+    }
+
+    fn load_state_dict(&mut self, state: SchedulerState<Vec<Phase>>) -> Result<(), SchedulerError> {
+        state.validate(&self.phases)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap};
+
+    use crate::{
+        Anneal, Duration, LrScheduler, PhaseSchedule, PhaseSpec, Schedule, SchedulerError,
+    };
+
+    #[test]
+    fn trapezoidal_test() {
+        let schedule = PhaseSchedule::builder()
+            .total_steps(100)
+            .phase(PhaseSpec::steps(10, 0., 1e-3).anneal(Anneal::Linear))
+            .phase(PhaseSpec::constant(Duration::Fraction(0.7), 1e-3))
+            .phase(PhaseSpec::fraction(0.2, 1e-3, 0.).anneal(Anneal::Linear))
+            .build()
+            .unwrap();
+
+        assert_eq!(schedule.lr_at(0), 0.);
+        assert_eq!(schedule.lr_at(5), 5e-4);
+        assert_eq!(schedule.lr_at(10), 1e-3);
+        assert_eq!(schedule.lr_at(50), 1e-3);
+        assert_eq!(schedule.lr_at(90), 5e-4);
+        assert_eq!(schedule.lr_at(100), 0.);
+        assert_eq!(schedule.lr_at(150), 0.);
+        assert_eq!(schedule.phases()[1].end_step(), 80.);
+    }
+
+    #[test]
+    fn triangular_momentum_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut schedule = PhaseSchedule::builder()
+            .phase(
+                PhaseSpec::steps(4, 1e-4, 1e-3)
+                    .momentum(0.95, 0.85)
+                    .anneal(Anneal::Linear),
+            )
+            .phase(
+                PhaseSpec::steps(4, 1e-3, 1e-4)
+                    .momentum(0.85, 0.95)
+                    .anneal(Anneal::Linear),
+            )
+            .build()
+            .unwrap();
+
+        for _i in 0..4 {
+            schedule.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(opt.learning_rate(), 1e-3);
+        assert_eq!(opt.params().beta1, 0.85);
+    }
+
+    #[test]
+    fn phase_schedule_invalid_test() {
+        assert_eq!(
+            PhaseSchedule::builder().build().unwrap_err(),
+            SchedulerError::Missing { name: "phases" }
+        );
+        assert_eq!(
+            PhaseSchedule::builder()
+                .phase(PhaseSpec::fraction(0.5, 1e-3, 0.))
+                .build()
+                .unwrap_err(),
+            SchedulerError::Missing {
+                name: "total_steps"
+            }
+        );
+        assert_eq!(
+            PhaseSchedule::builder()
+                .phase(PhaseSpec::steps(0, 1e-3, 0.))
+                .build()
+                .unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "phase steps"
+            }
+        );
+    }
+
+    #[test]
+    fn phase_schedule_anneal_endpoints_test() {
+        let build = |phase: PhaseSpec| PhaseSchedule::builder().phase(phase).build();
+
+        assert_eq!(
+            build(PhaseSpec::steps(10, 0., 1e-3).anneal(Anneal::Exponential)).unwrap_err(),
+            SchedulerError::ExponentialEndpoints {
+                index: 0,
+                start: 0.,
+                end: 1e-3
+            }
+        );
+        assert!(matches!(
+            build(PhaseSpec::steps(10, -1e-3, 1e-3).anneal(Anneal::Exponential)),
+            Err(SchedulerError::ExponentialEndpoints { .. })
+        ));
+        assert!(matches!(
+            build(
+                PhaseSpec::steps(10, 1e-3, 1e-4)
+                    .momentum(0.9, 0.)
+                    .anneal(Anneal::Exponential)
+            ),
+            Err(SchedulerError::ExponentialEndpoints { .. })
+        ));
+        assert!(build(PhaseSpec::steps(10, 1e-3, 1e-4).anneal(Anneal::Exponential)).is_ok());
+        assert_eq!(
+            build(PhaseSpec::steps(10, 1e-3, 0.).anneal(Anneal::Polynomial { power: -1. }))
+                .unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "polynomial power",
+                value: -1.
+            }
+        );
+    }
+}
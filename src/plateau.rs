@@ -0,0 +1,689 @@
+use std::fmt;
+
+use candle_core::{DType, Tensor};
+use candle_nn::Optimizer;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_order, check_positive, check_range};
+use crate::{LrScheduler, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Whether a lower or higher metric is better.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PlateauMode {
+    /// For losses.
+    #[default]
+    Min,
+    /// For accuracies and other scores.
+    Max,
+}
+
+/// How far a metric has to move past the best so far to count as better.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ThresholdMode {
+    /// By `threshold` times the best value.
+    #[default]
+    Rel,
+    /// By `threshold` itself.
+    Abs,
+}
+
+/// A value that [`ReduceLrOnPlateau::report`] can take, either a number or a
+/// scalar [`Tensor`] such as a mean loss.
+pub trait Metric {
+    fn value(&self) -> Result<f64, SchedulerError>;
+}
+
+impl Metric for f64 {
+    fn value(&self) -> Result<f64, SchedulerError> {
+        Ok(*self)
+    }
+}
+
+impl Metric for f32 {
+    fn value(&self) -> Result<f64, SchedulerError> {
+        Ok(*self as f64)
+    }
+}
+
+impl Metric for Tensor {
+    fn value(&self) -> Result<f64, SchedulerError> {
+        self.to_dtype(DType::F64)
+            .and_then(|metric| metric.to_scalar::<f64>())
+            .map_err(|err| SchedulerError::Metric(err.to_string()))
+    }
+}
+
+impl<M: Metric + ?Sized> Metric for &M {
+    fn value(&self) -> Result<f64, SchedulerError> {
+        (**self).value()
+    }
+}
+
+/// Why and when [`ReduceLrOnPlateau`] reduced the lr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reduction {
+    /// The report that triggered it, counting from 1.
+    pub epoch: usize,
+    /// Number of `step` calls made before it.
+    pub step_num: usize,
+    pub old_lr: f64,
+    pub new_lr: f64,
+    /// The metric that was reported.
+    pub metric: f64,
+    /// The best metric so far, which it failed to improve on.
+    pub best: f64,
+    /// Reports in a row without improvement, one more than the patience.
+    pub bad_epochs: usize,
+}
+
+/// Configures a [`ReduceLrOnPlateau`]. The defaults match PyTorch's.
+#[derive(Debug, Clone)]
+pub struct ReduceLrOnPlateauBuilder {
+    lr: f64,
+    mode: PlateauMode,
+    factor: f64,
+    patience: usize,
+    threshold: f64,
+    threshold_mode: ThresholdMode,
+    cooldown: usize,
+    min_lr: f64,
+    eps: f64,
+}
+
+impl ReduceLrOnPlateauBuilder {
+    /// Defaults to [`PlateauMode::Min`].
+    pub fn mode(mut self, mode: PlateauMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Multiplies the lr on each reduction, in `(0, 1]`. Defaults to 0.1.
+    pub fn factor(mut self, factor: f64) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Reports without improvement to allow before reducing. Defaults to 10.
+    pub fn patience(mut self, patience: usize) -> Self {
+        self.patience = patience;
+        self
+    }
+
+    /// Defaults to 1e-4.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Defaults to [`ThresholdMode::Rel`].
+    pub fn threshold_mode(mut self, threshold_mode: ThresholdMode) -> Self {
+        self.threshold_mode = threshold_mode;
+        self
+    }
+
+    /// Reports to wait after a reduction before counting bad reports again.
+    /// Defaults to 0.
+    pub fn cooldown(mut self, cooldown: usize) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// The lr is never reduced below this. Defaults to 0.
+    pub fn min_lr(mut self, min_lr: f64) -> Self {
+        self.min_lr = min_lr;
+        self
+    }
+
+    /// Reductions smaller than this are skipped. Defaults to 1e-8.
+    pub fn eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+
+    pub fn build(&self) -> Result<ReduceLrOnPlateau, SchedulerError> {
+        check_finite("lr", self.lr)?;
+        check_positive("factor", self.factor)?;
+        check_range("factor", self.factor, 0., 1.)?;
+        check_range("threshold", self.threshold, 0., f64::INFINITY)?;
+        check_range("min_lr", self.min_lr, 0., f64::INFINITY)?;
+        check_order(("min_lr", self.min_lr), ("lr", self.lr))?;
+        check_range("eps", self.eps, 0., f64::INFINITY)?;
+
+        Ok(ReduceLrOnPlateau {
+            config: ReduceLrOnPlateauConfig {
+                base_lr: self.lr,
+                mode: self.mode,
+                factor: self.factor,
+                patience: self.patience,
+                threshold: self.threshold,
+                threshold_mode: self.threshold_mode,
+                cooldown: self.cooldown,
+                min_lr: self.min_lr,
+                eps: self.eps,
+            },
+            lr: self.lr,
+            best: None,
+            num_bad_epochs: 0,
+            cooldown_counter: 0,
+            epoch: 0,
+            step_num: 0,
+            reductions: Vec::new(),
+        })
+    }
+}
+
+/// The settings of a [`ReduceLrOnPlateau`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReduceLrOnPlateauConfig {
+    pub base_lr: f64,
+    pub mode: PlateauMode,
+    pub factor: f64,
+    pub patience: usize,
+    pub threshold: f64,
+    pub threshold_mode: ThresholdMode,
+    pub cooldown: usize,
+    pub min_lr: f64,
+    pub eps: f64,
+}
+
+/// Reduces the lr when a metric stops improving, like PyTorch's
+/// `ReduceLROnPlateau`.
+///
+/// Metrics are passed to [`ReduceLrOnPlateau::report`], usually once per
+/// epoch. `step` only reapplies the current lr, so the scheduler can still be
+/// driven as an [`LrScheduler`]. The lr depends on the metrics seen so far, so
+/// there's no closed-form [`Schedule`](crate::Schedule).
+///
+/// ```
+/// use candle_scheduler::{PlateauMode, ReduceLrOnPlateau};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let scheduler = ReduceLrOnPlateau::builder(1e-3)
+///     .mode(PlateauMode::Max)
+///     .patience(3)
+///     .factor(0.5)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReduceLrOnPlateau {
+    config: ReduceLrOnPlateauConfig,
+    lr: f64,
+    best: Option<f64>,
+    num_bad_epochs: usize,
+    cooldown_counter: usize,
+    epoch: usize,
+    step_num: usize,
+    reductions: Vec<Reduction>,
+}
+
+impl ReduceLrOnPlateau {
+    /// Minimizing a metric with PyTorch's defaults.
+    ///
+    /// # Panics
+    ///
+    /// If `lr` is negative or isn't finite.
+    pub fn new(lr: f64) -> Self {
+        Self::builder(lr)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid ReduceLrOnPlateau: {err}"))
+    }
+
+    pub fn builder(lr: f64) -> ReduceLrOnPlateauBuilder {
+        ReduceLrOnPlateauBuilder {
+            lr,
+            mode: PlateauMode::Min,
+            factor: 0.1,
+            patience: 10,
+            threshold: 1e-4,
+            threshold_mode: ThresholdMode::Rel,
+            cooldown: 0,
+            min_lr: 0.,
+            eps: 1e-8,
+        }
+    }
+
+    pub fn config(&self) -> &ReduceLrOnPlateauConfig {
+        &self.config
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr
+    }
+
+    /// The best metric reported so far.
+    pub fn best(&self) -> Option<f64> {
+        self.best
+    }
+
+    /// Reports in a row without improvement.
+    pub fn num_bad_epochs(&self) -> usize {
+        self.num_bad_epochs
+    }
+
+    pub fn in_cooldown(&self) -> bool {
+        self.cooldown_counter > 0
+    }
+
+    /// Number of metrics reported.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Every reduction so far, oldest first.
+    pub fn reductions(&self) -> &[Reduction] {
+        &self.reductions
+    }
+
+    /// Record `metric` and reduce the lr if it has plateaued. Returns the
+    /// reduction, if one happened.
+    pub fn report<O: Optimizer>(
+        &mut self,
+        optimizer: &mut O,
+        metric: impl Metric,
+    ) -> Result<Option<Reduction>, SchedulerError> {
+        let metric = metric.value()?;
+        self.epoch += 1;
+
+        if self.is_better(metric) {
+            self.best = Some(metric);
+            self.num_bad_epochs = 0;
+        } else {
+            self.num_bad_epochs += 1;
+        }
+
+        if self.in_cooldown() {
+            self.cooldown_counter -= 1;
+            self.num_bad_epochs = 0;
+        }
+
+        let mut reduction = None;
+
+        if self.num_bad_epochs > self.config.patience {
+            reduction = self.reduce(metric);
+            self.cooldown_counter = self.config.cooldown;
+            self.num_bad_epochs = 0;
+        }
+
+        optimizer.set_learning_rate(self.lr);
+        Ok(reduction)
+    }
+
+    /// NaN is never better, as in PyTorch.
+    fn is_better(&self, metric: f64) -> bool {
+        let Some(best) = self.best else {
+            return !metric.is_nan();
+        };
+        let threshold = self.config.threshold;
+
+        match (self.config.mode, self.config.threshold_mode) {
+            (PlateauMode::Min, ThresholdMode::Rel) => metric < best * (1. - threshold),
+            (PlateauMode::Min, ThresholdMode::Abs) => metric < best - threshold,
+            (PlateauMode::Max, ThresholdMode::Rel) => metric > best * (1. + threshold),
+            (PlateauMode::Max, ThresholdMode::Abs) => metric > best + threshold,
+        }
+    }
+
+    fn reduce(&mut self, metric: f64) -> Option<Reduction> {
+        let new_lr = (self.lr * self.config.factor).max(self.config.min_lr);
+
+        if self.lr - new_lr <= self.config.eps {
+            return None;
+        }
+
+        let reduction = Reduction {
+            epoch: self.epoch,
+            step_num: self.step_num,
+            old_lr: self.lr,
+            new_lr,
+            metric,
+            best: self.best.unwrap_or(metric),
+            bad_epochs: self.num_bad_epochs,
+        };
+
+        self.lr = new_lr;
+        self.reductions.push(reduction);
+        Some(reduction)
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for ReduceLrOnPlateau {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.lr);
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    /// Back to the base lr, forgetting every metric reported.
+    fn reset(&mut self) {
+        self.lr = self.config.base_lr;
+        self.best = None;
+        self.num_bad_epochs = 0;
+        self.cooldown_counter = 0;
+        self.epoch = 0;
+        self.step_num = 0;
+        self.reductions.clear();
+    }
+}
+
+impl ToConfig for ReduceLrOnPlateau {
+    fn to_config(&self) -> SchedulerConfig {
+        let config = &self.config;
+        let default = ReduceLrOnPlateau::builder(config.base_lr);
+
+        SchedulerConfig::ReduceLrOnPlateau {
+            lr: config.base_lr,
+            mode: non_default(config.mode, default.mode),
+            factor: non_default(config.factor, default.factor),
+            patience: non_default(config.patience, default.patience),
+            threshold: non_default(config.threshold, default.threshold),
+            threshold_mode: non_default(config.threshold_mode, default.threshold_mode),
+            cooldown: non_default(config.cooldown, default.cooldown),
+            min_lr: non_default(config.min_lr, default.min_lr),
+            eps: non_default(config.eps, default.eps),
+        }
+    }
+}
+
+/// Formats the settings as a schedule expression, e.g. `plateau(patience=3,
+/// lr=0.001)`. The reductions so far aren't included.
+impl fmt::Display for ReduceLrOnPlateau {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// What's saved with a [`ReduceLrOnPlateau`] state. Its progress depends on
+/// the metrics reported, not just the step, so it's saved along with the
+/// config. Only the config has to match on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReduceLrOnPlateauCheckpoint {
+    pub config: ReduceLrOnPlateauConfig,
+    pub best: Option<f64>,
+    pub num_bad_epochs: usize,
+    pub cooldown_counter: usize,
+    pub epoch: usize,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for ReduceLrOnPlateau {
+    type Config = ReduceLrOnPlateauCheckpoint;
+
+    fn state_dict(&self) -> SchedulerState<ReduceLrOnPlateauCheckpoint> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.lr,
+            momentum: None,
+            config: ReduceLrOnPlateauCheckpoint {
+                config: self.config.clone(),
+                best: self.best,
+                num_bad_epochs: self.num_bad_epochs,
+                cooldown_counter: self.cooldown_counter,
+                epoch: self.epoch,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<ReduceLrOnPlateauCheckpoint>,
+    ) -> Result<(), SchedulerError> {
+        if state.config.config != self.config {
+            return Err(SchedulerError::StateMismatch {
+                expected: format!("{:?}", self.config),
+                found: format!("{:?}", state.config.config),
+            });
+        }
+
+        self.lr = check_finite("lr", state.lr)?;
+        self.best = state.config.best;
+        self.num_bad_epochs = state.config.num_bad_epochs;
+        self.cooldown_counter = state.config.cooldown_counter;
+        self.epoch = state.config.epoch;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::{Device, Tensor};
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{
+        LrScheduler, PlateauMode, ReduceLrOnPlateau, Reduction, SchedulerError, ThresholdMode,
+    };
+
+    #[test]
+    fn plateau_patience_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::builder(1.)
+            .patience(2)
+            .factor(0.5)
+            .build()
+            .unwrap();
+
+        for _i in 0..3 {
+            assert_eq!(scheduler.report(&mut opt, 1.).unwrap(), None);
+        }
+
+        assert_eq!(scheduler.num_bad_epochs(), 2);
+
+        let reduction = scheduler.report(&mut opt, 1.).unwrap();
+
+        assert_eq!(
+            reduction,
+            Some(Reduction {
+                epoch: 4,
+                step_num: 0,
+                old_lr: 1.,
+                new_lr: 0.5,
+                metric: 1.,
+                best: 1.,
+                bad_epochs: 3,
+            })
+        );
+        assert_eq!(opt.learning_rate(), 0.5);
+        assert_eq!(scheduler.num_bad_epochs(), 0);
+        assert_eq!(scheduler.reductions().len(), 1);
+    }
+
+    #[test]
+    fn plateau_improvement_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::builder(1.).patience(1).build().unwrap();
+
+        for metric in [1., 0.9, 0.8, 0.7] {
+            scheduler.report(&mut opt, metric).unwrap();
+        }
+
+        assert_eq!(scheduler.best(), Some(0.7));
+        assert_eq!(opt.learning_rate(), 1.);
+
+        // Too small an improvement with a relative threshold.
+        scheduler.report(&mut opt, 0.69999).unwrap();
+        scheduler.report(&mut opt, 0.69999).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.1);
+    }
+
+    #[test]
+    fn plateau_max_abs_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::builder(1.)
+            .mode(PlateauMode::Max)
+            .threshold_mode(ThresholdMode::Abs)
+            .threshold(0.1)
+            .patience(0)
+            .build()
+            .unwrap();
+
+        scheduler.report(&mut opt, 0.5).unwrap();
+        scheduler.report(&mut opt, 0.7).unwrap();
+
+        assert_eq!(opt.learning_rate(), 1.);
+
+        scheduler.report(&mut opt, 0.75).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.1);
+        assert_eq!(scheduler.best(), Some(0.7));
+    }
+
+    #[test]
+    fn plateau_cooldown_min_lr_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::builder(1.)
+            .patience(0)
+            .cooldown(2)
+            .min_lr(0.05)
+            .build()
+            .unwrap();
+
+        scheduler.report(&mut opt, 1.).unwrap();
+        scheduler.report(&mut opt, 1.).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.1);
+        assert!(scheduler.in_cooldown());
+
+        scheduler.report(&mut opt, 1.).unwrap();
+        scheduler.report(&mut opt, 1.).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.1);
+
+        scheduler.report(&mut opt, 1.).unwrap();
+
+        assert_eq!(opt.learning_rate(), 0.05);
+
+        for _i in 0..5 {
+            scheduler.report(&mut opt, 1.).unwrap();
+        }
+
+        assert_eq!(opt.learning_rate(), 0.05);
+        assert_eq!(scheduler.reductions().len(), 2);
+    }
+
+    #[test]
+    fn plateau_tensor_metric_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::new(1.);
+        let loss = Tensor::new(0.25f64, &Device::Cpu).unwrap();
+
+        scheduler.report(&mut opt, &loss).unwrap();
+
+        assert_eq!(scheduler.best(), Some(0.25));
+
+        let losses = Tensor::from_vec(vec![0.25f64, 0.5], 2, &Device::Cpu).unwrap();
+
+        assert!(matches!(
+            scheduler.report(&mut opt, losses),
+            Err(SchedulerError::Metric(_))
+        ));
+    }
+
+    #[test]
+    fn plateau_step_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.5).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::new(1e-3);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), 1e-3);
+        assert_eq!(scheduler.epoch(), 0);
+    }
+
+    #[test]
+    fn plateau_invalid_test() {
+        assert!(matches!(
+            ReduceLrOnPlateau::builder(1e-3).factor(1.5).build(),
+            Err(SchedulerError::OutOfRange { name: "factor", .. })
+        ));
+        assert_eq!(
+            ReduceLrOnPlateau::builder(1e-3)
+                .min_lr(1e-2)
+                .build()
+                .unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "min_lr",
+                upper: "lr"
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn plateau_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 1.).unwrap();
+        let mut scheduler = ReduceLrOnPlateau::builder(1.).patience(1).build().unwrap();
+
+        for _i in 0..3 {
+            scheduler.report(&mut opt, 1.).unwrap();
+        }
+        scheduler.report(&mut opt, 2.).unwrap();
+
+        let mut resumed = ReduceLrOnPlateau::builder(1.).patience(1).build().unwrap();
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(resumed.get_lr(), 0.1);
+        assert_eq!(resumed.best(), Some(1.));
+        assert_eq!(resumed.num_bad_epochs(), 1);
+
+        let mut other = ReduceLrOnPlateau::builder(1.).patience(2).build().unwrap();
+        assert!(matches!(
+            other.load_state_dict(scheduler.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn plateau_mode_serde_test() {
+        assert_eq!(
+            serde_json::to_string(&PlateauMode::Max).unwrap(),
+            r#""max""#
+        );
+        assert_eq!(
+            serde_json::to_string(&ThresholdMode::Abs).unwrap(),
+            r#""abs""#
+        );
+        assert_eq!(
+            serde_json::from_str::<PlateauMode>(r#""min""#).unwrap(),
+            PlateauMode::Min
+        );
+        assert_eq!(
+            serde_json::from_str::<ThresholdMode>(r#""rel""#).unwrap(),
+            ThresholdMode::Rel
+        );
+    }
+}
@@ -0,0 +1,552 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::error::check_steps;
+use crate::{Phase, Schedule, SchedulerError};
+
+/// Line colors, in the order schedules are added.
+const COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+const LEFT: f64 = 72.;
+const RIGHT: f64 = 24.;
+const BOTTOM: f64 = 48.;
+const PANEL_GAP: f64 = 24.;
+
+/// A figure of the lr of one or more schedules over `steps` steps, rendered
+/// to SVG, or PNG with the `png` feature.
+///
+/// Schedules that drive momentum get a second panel for it below the lr.
+/// Phase boundaries are drawn as dashed lines across both.
+///
+/// ```no_run
+/// use candle_scheduler::{CosineAnnealing, OneCycle, Plot};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let one_cycle = OneCycle::new(1e-3, 0.95, 25., 10_000);
+/// let cosine = CosineAnnealing::new(1e-3, 10_000, 1e-6);
+///
+/// Plot::new(10_000)
+///     .schedule("one cycle", &one_cycle)
+///     .schedule("cosine", &cosine)
+///     .phases(one_cycle.phases())
+///     .log_scale(true)
+///     .save_svg("lr.svg")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Plot<'a> {
+    steps: usize,
+    series: Vec<(String, &'a dyn Schedule)>,
+    boundaries: Vec<f64>,
+    log_scale: bool,
+    title: Option<String>,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Plot<'a> {
+    /// An empty 800x480 figure of steps 0 to `steps`.
+    pub fn new(steps: usize) -> Self {
+        Plot {
+            steps,
+            series: Vec::new(),
+            boundaries: Vec::new(),
+            log_scale: false,
+            title: None,
+            width: 800,
+            height: 480,
+        }
+    }
+
+    /// Overlay another schedule. The legend is shown once there are two.
+    pub fn schedule(mut self, label: impl Into<String>, schedule: &'a dyn Schedule) -> Self {
+        self.series.push((label.into(), schedule));
+        self
+    }
+
+    /// Mark where each phase ends and the next starts, e.g. from
+    /// [`OneCycle::phases`].
+    ///
+    /// [`OneCycle::phases`]: crate::OneCycle::phases
+    pub fn phases(mut self, phases: &[Phase]) -> Self {
+        let inner = &phases[..phases.len().saturating_sub(1)];
+        self.boundaries
+            .extend(inner.iter().map(|phase| phase.end_step()));
+        self
+    }
+
+    /// Plot the lr on a log scale, to see the ends of warmups and decays.
+    /// Steps with an lr of 0 are left out.
+    pub fn log_scale(mut self, log_scale: bool) -> Self {
+        self.log_scale = log_scale;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// The size in pixels, at least 200x150. Defaults to 800x480.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn to_svg(&self) -> Result<String, SchedulerError> {
+        check_steps("steps", self.steps)?;
+        if self.series.is_empty() {
+            return Err(SchedulerError::Missing { name: "schedule" });
+        }
+        if self.width < 200 {
+            return Err(out_of_range("width", self.width, 200));
+        }
+        if self.height < 150 {
+            return Err(out_of_range("height", self.height, 150));
+        }
+
+        let (width, height) = (self.width as f64, self.height as f64);
+        let count = (self.steps + 1).min(2 * self.width as usize);
+        let samples: Vec<usize> = (0..count).map(|i| i * self.steps / (count - 1)).collect();
+        let x = |step: f64| LEFT + step / self.steps as f64 * (width - LEFT - RIGHT);
+        let xs: Vec<f64> = samples.iter().map(|&step| x(step as f64)).collect();
+
+        let lrs: Vec<Vec<f64>> = self
+            .series
+            .iter()
+            .map(|(_, schedule)| samples.iter().map(|&step| schedule.lr_at(step)).collect())
+            .collect();
+        let momentums: Vec<Option<Vec<f64>>> = self
+            .series
+            .iter()
+            .map(|(_, schedule)| {
+                let values: Vec<Option<f64>> = samples
+                    .iter()
+                    .map(|&step| schedule.momentum_at(step))
+                    .collect();
+                values.iter().any(Option::is_some).then(|| {
+                    values
+                        .iter()
+                        .map(|value| value.unwrap_or(f64::NAN))
+                        .collect()
+                })
+            })
+            .collect();
+
+        let top = if self.title.is_some() { 44. } else { 20. };
+        let inner = height - top - BOTTOM;
+        let lr_height = match momentums.iter().any(Option::is_some) {
+            true => (inner - PANEL_GAP) * 0.65,
+            false => inner,
+        };
+        let mut panels = vec![Panel {
+            name: "lr",
+            top,
+            height: lr_height,
+            axis: Axis::fit(lrs.iter().flatten().copied(), self.log_scale)?,
+            curves: lrs.iter().map(Some).collect(),
+        }];
+        if momentums.iter().any(Option::is_some) {
+            panels.push(Panel {
+                name: "momentum",
+                top: top + lr_height + PANEL_GAP,
+                height: inner - lr_height - PANEL_GAP,
+                axis: Axis::fit(momentums.iter().flatten().flatten().copied(), false)?,
+                curves: momentums.iter().map(Option::as_ref).collect(),
+            });
+        }
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+             <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n",
+            w = self.width,
+            h = self.height,
+        );
+        if let Some(title) = &self.title {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"28\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+                (LEFT + width - RIGHT) / 2.,
+                escape(title)
+            );
+        }
+
+        let right = width - RIGHT;
+        for panel in &panels {
+            let bottom = panel.top + panel.height;
+            for (value, label) in panel.axis.ticks() {
+                let y = panel.y(value).unwrap_or(bottom);
+                let _ = writeln!(
+                    svg,
+                    "<line x1=\"{LEFT}\" y1=\"{y:.2}\" x2=\"{right}\" y2=\"{y:.2}\" stroke=\"#e5e5e5\"/>\n\
+                     <text x=\"{}\" y=\"{y:.2}\" dy=\"0.35em\" text-anchor=\"end\">{label}</text>",
+                    LEFT - 6.,
+                );
+            }
+            let _ = writeln!(
+                svg,
+                "<text transform=\"translate(16 {:.2}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+                panel.top + panel.height / 2.,
+                panel.name
+            );
+
+            for &boundary in &self.boundaries {
+                if boundary > 0. && boundary < self.steps as f64 {
+                    let x = x(boundary);
+                    let _ = writeln!(
+                        svg,
+                        "<line x1=\"{x:.2}\" y1=\"{:.2}\" x2=\"{x:.2}\" y2=\"{bottom:.2}\" \
+                         stroke=\"#999\" stroke-dasharray=\"4 4\"/>",
+                        panel.top
+                    );
+                }
+            }
+
+            for (i, curve) in panel.curves.iter().enumerate() {
+                if let Some(values) = curve {
+                    let _ = writeln!(
+                        svg,
+                        "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>",
+                        path(&xs, values.iter().map(|&value| panel.y(value))),
+                        COLORS[i % COLORS.len()]
+                    );
+                }
+            }
+
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{LEFT}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"none\" stroke=\"#333\"/>",
+                panel.top,
+                right - LEFT,
+                panel.height
+            );
+        }
+
+        let bottom = height - BOTTOM;
+        for (step, label) in Axis::linear(0., self.steps as f64, true).ticks() {
+            let x = x(step);
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{x:.2}\" y1=\"{bottom}\" x2=\"{x:.2}\" y2=\"{}\" stroke=\"#333\"/>\n\
+                 <text x=\"{x:.2}\" y=\"{}\" text-anchor=\"middle\">{label}</text>",
+                bottom + 4.,
+                bottom + 18.
+            );
+        }
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">step</text>",
+            (LEFT + right) / 2.,
+            height - 8.
+        );
+
+        if self.series.len() > 1 {
+            for (i, (label, _)) in self.series.iter().enumerate() {
+                let y = top + 16. + 18. * i as f64;
+                let _ = writeln!(
+                    svg,
+                    "<line x1=\"{:.1}\" y1=\"{y}\" x2=\"{:.1}\" y2=\"{y}\" stroke=\"{}\" stroke-width=\"2\"/>\n\
+                     <text x=\"{:.1}\" y=\"{y}\" dy=\"0.35em\" text-anchor=\"end\">{}</text>",
+                    right - 30.,
+                    right - 10.,
+                    COLORS[i % COLORS.len()],
+                    right - 36.,
+                    escape(label)
+                );
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    pub fn save_svg(&self, path: impl AsRef<Path>) -> Result<(), SchedulerError> {
+        std::fs::write(path, self.to_svg()?).map_err(plot_error)
+    }
+
+    /// Rasterize the SVG. Text is drawn with the system's fonts.
+    #[cfg(feature = "png")]
+    pub fn to_png(&self) -> Result<Vec<u8>, SchedulerError> {
+        use resvg::tiny_skia::{Pixmap, Transform};
+        use resvg::usvg::{self, TreeParsing, TreeTextToPath};
+
+        let mut tree =
+            usvg::Tree::from_str(&self.to_svg()?, &usvg::Options::default()).map_err(plot_error)?;
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        tree.convert_text(&fonts);
+
+        let mut pixmap = Pixmap::new(self.width, self.height)
+            .ok_or_else(|| SchedulerError::Plot("couldn't allocate the image".to_string()))?;
+        resvg::Tree::from_usvg(&tree).render(Transform::default(), &mut pixmap.as_mut());
+        pixmap.encode_png().map_err(plot_error)
+    }
+
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), SchedulerError> {
+        std::fs::write(path, self.to_png()?).map_err(plot_error)
+    }
+}
+
+fn plot_error(err: impl std::fmt::Display) -> SchedulerError {
+    SchedulerError::Plot(err.to_string())
+}
+
+fn out_of_range(name: &'static str, value: u32, min: u32) -> SchedulerError {
+    SchedulerError::OutOfRange {
+        name,
+        value: value as f64,
+        min: min as f64,
+        max: f64::INFINITY,
+    }
+}
+
+struct Panel<'v> {
+    name: &'static str,
+    top: f64,
+    height: f64,
+    axis: Axis,
+    /// One per schedule, `None` for those without momentum.
+    curves: Vec<Option<&'v Vec<f64>>>,
+}
+
+impl Panel<'_> {
+    /// `None` for values that can't be drawn, which break the line.
+    fn y(&self, value: f64) -> Option<f64> {
+        let fraction = self.axis.fraction(value)?;
+        Some(self.top + self.height * (1. - fraction))
+    }
+}
+
+/// The range of a y axis, with ticks at multiples of `tick` units of
+/// `10^exponent`, or at every `tick` decades on a log scale.
+struct Axis {
+    low: f64,
+    high: f64,
+    log: bool,
+    tick: i64,
+    exponent: i32,
+}
+
+impl Axis {
+    fn fit(values: impl Iterator<Item = f64>, log: bool) -> Result<Self, SchedulerError> {
+        let (low, high) = values
+            .filter(|value| value.is_finite() && (!log || *value > 0.))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+                (low.min(value), high.max(value))
+            });
+
+        if low > high {
+            return match log {
+                true => Err(SchedulerError::NonPositive {
+                    name: "lr",
+                    value: 0.,
+                }),
+                false => Ok(Axis::linear(0., 1., false)),
+            };
+        }
+
+        if log {
+            let low = low.log10().floor();
+            let high = high.log10().ceil().max(low + 1.);
+            return Ok(Axis {
+                low,
+                high,
+                log,
+                tick: ((high - low) / 6.).ceil() as i64,
+                exponent: 0,
+            });
+        }
+
+        // Start lrs from 0 unless they only vary a little, like momentum.
+        let low = if low >= 0. && low <= high / 2. {
+            0.
+        } else {
+            low
+        };
+        Ok(match high > low {
+            true => Axis::linear(low, high, false),
+            false if high == 0. => Axis::linear(0., 1., false),
+            // A constant, from 0.
+            false => Axis::linear(low.min(0.), high.max(0.), false),
+        })
+    }
+
+    /// About five ticks of 1, 2 or 5 units, widened to fit them.
+    fn linear(low: f64, high: f64, integer: bool) -> Self {
+        let raw = (high - low) / 5.;
+        let exponent = raw.log10().floor() as i32;
+        let (tick, exponent) = match raw / 10f64.powi(exponent) {
+            // Steps are whole.
+            _ if integer && exponent < 0 => (1, 0),
+            fraction if fraction <= 1. => (1, exponent),
+            fraction if fraction <= 2. => (2, exponent),
+            fraction if fraction <= 5. => (5, exponent),
+            _ => (1, exponent + 1),
+        };
+
+        let unit = tick as f64 * 10f64.powi(exponent);
+        Axis {
+            low: match integer {
+                true => low,
+                false => (low / unit).floor() * unit,
+            },
+            high: match integer {
+                true => high,
+                false => (high / unit).ceil() * unit,
+            },
+            log: false,
+            tick,
+            exponent,
+        }
+    }
+
+    /// Where `value` falls between the bottom, 0, and the top, 1.
+    fn fraction(&self, value: f64) -> Option<f64> {
+        let value = match self.log {
+            true if value > 0. => value.log10(),
+            true => return None,
+            false => value,
+        };
+        value
+            .is_finite()
+            .then(|| (value - self.low) / (self.high - self.low))
+    }
+
+    fn ticks(&self) -> Vec<(f64, String)> {
+        if self.log {
+            return (self.low as i64..=self.high as i64)
+                .step_by(self.tick as usize)
+                .map(|decade| (10f64.powi(decade as i32), format!("1e{decade}")))
+                .collect();
+        }
+
+        let unit = self.tick as f64 * 10f64.powi(self.exponent);
+        let first = (self.low / unit - 1e-9).ceil() as i64;
+        let last = (self.high / unit + 1e-9).floor() as i64;
+        (first..=last)
+            .map(|n| (n as f64 * unit, label(n * self.tick, self.exponent)))
+            .collect()
+    }
+}
+
+/// `units * 10^exponent`, in scientific notation when it's that precise or
+/// large.
+fn label(units: i64, exponent: i32) -> String {
+    let value = units as f64 * 10f64.powi(exponent);
+    if units == 0 {
+        return "0".to_string();
+    }
+    if exponent >= -3 && value.abs() < 1e6 {
+        let precision = (-exponent).max(0) as usize;
+        return format!("{value:.precision$}");
+    }
+
+    let sign = if units < 0 { "-" } else { "" };
+    let digits = units.unsigned_abs().to_string();
+    let exponent = exponent + digits.len() as i32 - 1;
+    match digits.trim_end_matches('0').split_at(1) {
+        (first, "") => format!("{sign}{first}e{exponent}"),
+        (first, rest) => format!("{sign}{first}.{rest}e{exponent}"),
+    }
+}
+
+fn path(xs: &[f64], ys: impl Iterator<Item = Option<f64>>) -> String {
+    let mut d = String::new();
+    let mut drawing = false;
+    for (x, y) in xs.iter().zip(ys) {
+        match y {
+            Some(y) => {
+                let command = if drawing { 'L' } else { 'M' };
+                let _ = write!(d, "{command}{x:.2},{y:.2} ");
+                drawing = true;
+            }
+            None => drawing = false,
+        }
+    }
+    d.trim_end().to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CosineAnnealing, LambdaLr, OneCycle, Plot, SchedulerError};
+
+    use super::label;
+
+    #[test]
+    fn plot_svg_test() {
+        let one_cycle = OneCycle::new(1e-3, 0.95, 25., 100);
+        let cosine = CosineAnnealing::new(1e-3, 100, 0.);
+        let svg = Plot::new(100)
+            .schedule("one cycle", &one_cycle)
+            .schedule("cosine <5>", &cosine)
+            .phases(one_cycle.phases())
+            .title("lr")
+            .to_svg()
+            .unwrap();
+
+        assert!(svg.starts_with("<svg "));
+        // Two lrs and the momentum of the one cycle schedule.
+        assert_eq!(svg.matches("<path ").count(), 3);
+        // The warmup's end in the lr and momentum panels.
+        assert_eq!(svg.matches("stroke-dasharray").count(), 2);
+        assert!(svg.contains(">cosine &lt;5&gt;</text>"));
+        assert!(svg.contains(">momentum</text>"));
+    }
+
+    #[test]
+    fn plot_log_scale_test() {
+        let warmup = LambdaLr::new(1e-2, |step| step as f64 / 100.);
+        let svg = Plot::new(100)
+            .schedule("", &warmup)
+            .log_scale(true)
+            .to_svg()
+            .unwrap();
+
+        assert!(svg.contains(">1e-2</text>"));
+        assert!(!svg.contains(">momentum</text>"));
+        // Step 0, with an lr of 0, is left out.
+        assert!(!svg.contains("d=\"M72.00,"));
+        assert!(svg.contains("d=\"M79.04,"));
+
+        let cosine = CosineAnnealing::new(1e-3, 100, 0.);
+        assert!(matches!(
+            Plot::new(100).to_svg(),
+            Err(SchedulerError::Missing { name: "schedule" })
+        ));
+        assert!(matches!(
+            Plot::new(100).schedule("", &cosine).size(100, 100).to_svg(),
+            Err(SchedulerError::OutOfRange { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn plot_label_test() {
+        assert_eq!(label(0, -4), "0");
+        assert_eq!(label(15, -5), "1.5e-4");
+        assert_eq!(label(10, -5), "1e-4");
+        assert_eq!(label(25, -2), "0.25");
+        assert_eq!(label(4, 3), "4000");
+        assert_eq!(label(2, 6), "2e6");
+        assert_eq!(label(5, -4), "5e-4");
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn plot_png_test() {
+        let cosine = CosineAnnealing::new(1e-3, 100, 0.);
+        let png = Plot::new(100).schedule("", &cosine).to_png().unwrap();
+
+        assert_eq!(&png[..4], b"\x89PNG");
+    }
+}
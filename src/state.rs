@@ -0,0 +1,46 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::SchedulerError;
+
+/// A checkpoint of a scheduler's progress.
+///
+/// `lr` and `momentum` are recorded for inspection. On load they're
+/// recomputed from `step_num`, so a resumed run picks up exactly where it
+/// stopped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerState<C> {
+    pub step_num: usize,
+    pub lr: f64,
+    pub momentum: Option<f64>,
+    pub config: C,
+}
+
+impl<C: PartialEq + fmt::Debug> SchedulerState<C> {
+    /// Check this state was saved from a scheduler configured like `config`.
+    pub fn validate(&self, config: &C) -> Result<(), SchedulerError> {
+        if &self.config == config {
+            Ok(())
+        } else {
+            Err(SchedulerError::StateMismatch {
+                expected: format!("{config:?}"),
+                found: format!("{:?}", self.config),
+            })
+        }
+    }
+}
+
+/// Save and restore a scheduler, like PyTorch's `state_dict()`.
+pub trait StateDict {
+    /// Everything that defines the schedule apart from its progress.
+    type Config: Serialize + DeserializeOwned + PartialEq + fmt::Debug;
+
+    fn state_dict(&self) -> SchedulerState<Self::Config>;
+
+    /// Resume from `state`, failing if it was saved with a different config.
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<Self::Config>,
+    ) -> Result<(), SchedulerError>;
+}
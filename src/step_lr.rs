@@ -0,0 +1,459 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_positive, check_steps};
+use crate::{LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Decays the lr by `gamma` every `step_size` epochs, like PyTorch's `StepLR`.
+///
+/// An epoch is one step unless [`StepLr::with_steps_per_epoch`] is used, so
+/// `step` can be called once per epoch or once per batch.
+#[derive(Debug)]
+pub struct StepLr {
+    base_lr: f64,
+    step_size: usize,
+    gamma: f64,
+    steps_per_epoch: usize,
+    step_num: usize,
+}
+
+impl StepLr {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`StepLr::try_new`].
+    pub fn new(lr: f64, step_size: usize, gamma: f64) -> Self {
+        Self::try_new(lr, step_size, gamma).unwrap_or_else(|err| panic!("invalid StepLr: {err}"))
+    }
+
+    /// Requires a finite `lr`, a positive `step_size` and a positive finite
+    /// `gamma`.
+    pub fn try_new(lr: f64, step_size: usize, gamma: f64) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_steps("step_size", step_size)?;
+        check_positive("gamma", gamma)?;
+
+        Ok(StepLr {
+            base_lr: lr,
+            step_size,
+            gamma,
+            steps_per_epoch: 1,
+            step_num: 0,
+        })
+    }
+
+    /// Count `step_size` in epochs of `steps_per_epoch` steps, for calling
+    /// `step` once per batch.
+    pub fn with_steps_per_epoch(mut self, steps_per_epoch: usize) -> Result<Self, SchedulerError> {
+        self.steps_per_epoch = check_steps("steps_per_epoch", steps_per_epoch)?;
+        Ok(self)
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    /// The current epoch.
+    pub fn epoch(&self) -> usize {
+        self.step_num / self.steps_per_epoch
+    }
+
+    /// Jump to the first step of `epoch` and update the optimizer. Fails if
+    /// the step overflows a `usize`.
+    pub fn step_epoch<O: Optimizer>(
+        &mut self,
+        optimizer: &mut O,
+        epoch: usize,
+    ) -> Result<(), SchedulerError> {
+        self.step_num = epoch
+            .checked_mul(self.steps_per_epoch)
+            .ok_or(SchedulerError::StepOverflow { name: "epoch" })?;
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+}
+
+impl Schedule for StepLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        let epoch = step / self.steps_per_epoch;
+        self.base_lr * self.gamma.powf((epoch / self.step_size) as f64)
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for StepLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for StepLr {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::StepLr {
+            lr: self.base_lr,
+            step_size: self.step_size,
+            gamma: self.gamma,
+            steps_per_epoch: non_default(self.steps_per_epoch, 1),
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `step(30, 0.1, lr=0.1)`.
+impl fmt::Display for StepLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`StepLr`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StepLrConfig {
+    pub base_lr: f64,
+    pub step_size: usize,
+    pub gamma: f64,
+    pub steps_per_epoch: usize,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for StepLr {
+    type Config = StepLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<StepLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: StepLrConfig {
+                base_lr: self.base_lr,
+                step_size: self.step_size,
+                gamma: self.gamma,
+                steps_per_epoch: self.steps_per_epoch,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<StepLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+/// Decays the lr by `gamma` at each milestone epoch, like PyTorch's
+/// `MultiStepLR`.
+///
+/// Milestones are sorted on construction. A milestone given twice decays
+/// twice, as in PyTorch.
+#[derive(Debug)]
+pub struct MultiStepLr {
+    base_lr: f64,
+    milestones: Vec<usize>,
+    gamma: f64,
+    steps_per_epoch: usize,
+    step_num: usize,
+}
+
+impl MultiStepLr {
+    /// # Panics
+    ///
+    /// If the arguments are invalid, see [`MultiStepLr::try_new`].
+    pub fn new(lr: f64, milestones: impl Into<Vec<usize>>, gamma: f64) -> Self {
+        Self::try_new(lr, milestones, gamma)
+            .unwrap_or_else(|err| panic!("invalid MultiStepLr: {err}"))
+    }
+
+    /// Requires a finite `lr` and a positive finite `gamma`.
+    pub fn try_new(
+        lr: f64,
+        milestones: impl Into<Vec<usize>>,
+        gamma: f64,
+    ) -> Result<Self, SchedulerError> {
+        check_finite("lr", lr)?;
+        check_positive("gamma", gamma)?;
+
+        let mut milestones = milestones.into();
+        milestones.sort_unstable();
+
+        Ok(MultiStepLr {
+            base_lr: lr,
+            milestones,
+            gamma,
+            steps_per_epoch: 1,
+            step_num: 0,
+        })
+    }
+
+    /// Count milestones in epochs of `steps_per_epoch` steps, for calling
+    /// `step` once per batch.
+    pub fn with_steps_per_epoch(mut self, steps_per_epoch: usize) -> Result<Self, SchedulerError> {
+        self.steps_per_epoch = check_steps("steps_per_epoch", steps_per_epoch)?;
+        Ok(self)
+    }
+
+    pub fn milestones(&self) -> &[usize] {
+        &self.milestones
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    /// The current epoch.
+    pub fn epoch(&self) -> usize {
+        self.step_num / self.steps_per_epoch
+    }
+
+    /// Jump to the first step of `epoch` and update the optimizer. Fails if
+    /// the step overflows a `usize`.
+    pub fn step_epoch<O: Optimizer>(
+        &mut self,
+        optimizer: &mut O,
+        epoch: usize,
+    ) -> Result<(), SchedulerError> {
+        self.step_num = epoch
+            .checked_mul(self.steps_per_epoch)
+            .ok_or(SchedulerError::StepOverflow { name: "epoch" })?;
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+}
+
+impl Schedule for MultiStepLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        let epoch = step / self.steps_per_epoch;
+        let passed = self
+            .milestones
+            .partition_point(|&milestone| milestone <= epoch);
+        self.base_lr * self.gamma.powf(passed as f64)
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for MultiStepLr {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for MultiStepLr {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::MultiStepLr {
+            lr: self.base_lr,
+            milestones: self.milestones.clone(),
+            gamma: self.gamma,
+            steps_per_epoch: non_default(self.steps_per_epoch, 1),
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `multistep([30, 80], 0.1, lr=0.1)`.
+impl fmt::Display for MultiStepLr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`MultiStepLr`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MultiStepLrConfig {
+    pub base_lr: f64,
+    pub milestones: Vec<usize>,
+    pub gamma: f64,
+    pub steps_per_epoch: usize,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for MultiStepLr {
+    type Config = MultiStepLrConfig;
+
+    fn state_dict(&self) -> SchedulerState<MultiStepLrConfig> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: MultiStepLrConfig {
+                base_lr: self.base_lr,
+                milestones: self.milestones.clone(),
+                gamma: self.gamma,
+                steps_per_epoch: self.steps_per_epoch,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<MultiStepLrConfig>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{LrScheduler, MultiStepLr, Schedule, SchedulerError, StepLr};
+
+    #[test]
+    fn step_lr_pytorch_test() {
+        // The example from PyTorch's StepLR docs.
+        let scheduler = StepLr::new(0.05, 30, 0.1);
+
+        assert_eq!(scheduler.lr_at(0), 0.05);
+        assert_eq!(scheduler.lr_at(29), 0.05);
+        assert_eq!(scheduler.lr_at(30), 0.005000000000000001);
+        assert_eq!(scheduler.lr_at(59), 0.005000000000000001);
+        assert_eq!(scheduler.lr_at(60), 0.0005000000000000001);
+    }
+
+    #[test]
+    fn step_lr_steps_per_epoch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = StepLr::new(0.1, 2, 0.5).with_steps_per_epoch(3).unwrap();
+
+        for _i in 0..5 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        assert_eq!(scheduler.epoch(), 1);
+        assert_eq!(opt.learning_rate(), 0.1);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(scheduler.epoch(), 2);
+        assert_eq!(opt.learning_rate(), 0.05);
+
+        scheduler.step_epoch(&mut opt, 6).unwrap();
+
+        assert_eq!(LrScheduler::<SGD>::step_num(&scheduler), 18);
+        assert_eq!(opt.learning_rate(), 0.0125);
+        assert_eq!(
+            scheduler.step_epoch(&mut opt, usize::MAX),
+            Err(SchedulerError::StepOverflow { name: "epoch" })
+        );
+        assert_eq!(LrScheduler::<SGD>::step_num(&scheduler), 18);
+    }
+
+    #[test]
+    fn multi_step_lr_pytorch_test() {
+        // The example from PyTorch's MultiStepLR docs.
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.05).unwrap();
+        let mut scheduler = MultiStepLr::new(0.05, [80, 30], 0.1);
+        let mut lrs = vec![scheduler.get_lr()];
+
+        for _i in 0..100 {
+            scheduler.step(&mut opt).unwrap();
+            lrs.push(opt.learning_rate());
+        }
+
+        assert_eq!(scheduler.milestones(), [30, 80]);
+        assert_eq!(lrs[29], 0.05);
+        assert_eq!(lrs[30], 0.005000000000000001);
+        assert_eq!(lrs[79], 0.005000000000000001);
+        assert_eq!(lrs[80], 0.0005000000000000001);
+        assert_eq!(lrs[100], 0.0005000000000000001);
+    }
+
+    #[test]
+    fn multi_step_lr_repeated_milestone_test() {
+        let scheduler = MultiStepLr::new(0.1, [2, 2], 0.5)
+            .with_steps_per_epoch(2)
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(3), 0.1);
+        assert_eq!(scheduler.lr_at(4), 0.025);
+    }
+
+    #[test]
+    fn multi_step_lr_step_epoch_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.1).unwrap();
+        let mut scheduler = MultiStepLr::new(0.1, [2, 4], 0.5)
+            .with_steps_per_epoch(3)
+            .unwrap();
+
+        scheduler.step_epoch(&mut opt, 4).unwrap();
+
+        assert_eq!(LrScheduler::<SGD>::step_num(&scheduler), 12);
+        assert_eq!(opt.learning_rate(), 0.025);
+        assert_eq!(
+            scheduler.step_epoch(&mut opt, usize::MAX / 2),
+            Err(SchedulerError::StepOverflow { name: "epoch" })
+        );
+    }
+
+    #[test]
+    fn step_lr_invalid_test() {
+        assert_eq!(
+            StepLr::try_new(0.1, 0, 0.1).unwrap_err(),
+            SchedulerError::ZeroSteps { name: "step_size" }
+        );
+        assert_eq!(
+            MultiStepLr::try_new(0.1, [10], -0.1).unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "gamma",
+                value: -0.1
+            }
+        );
+        assert!(matches!(
+            StepLr::new(0.1, 10, 0.1).with_steps_per_epoch(0),
+            Err(SchedulerError::ZeroSteps {
+                name: "steps_per_epoch"
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn multi_step_lr_state_mismatch_test() {
+        let scheduler = MultiStepLr::new(0.1, [10, 20], 0.1);
+        let mut other = MultiStepLr::new(0.1, [10, 30], 0.1);
+
+        assert!(matches!(
+            other.load_state_dict(scheduler.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+}
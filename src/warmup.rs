@@ -0,0 +1,464 @@
+use std::fmt;
+
+use crate::config::non_default;
+use crate::error::{check_positive, check_range, check_steps};
+use crate::phase::check_anneal;
+use crate::{
+    Anneal, Hyperparams, LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig,
+    UnsupportedPolicy,
+};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Configures a [`Warmup`].
+#[derive(Debug, Clone)]
+pub struct WarmupBuilder<S> {
+    inner: S,
+    warmup_steps: usize,
+    start_factor: f64,
+    anneal: Anneal,
+    momentum: Option<(f64, f64)>,
+}
+
+impl<S> WarmupBuilder<S> {
+    /// Fraction of the inner schedule's initial lr to start from, in `[0, 1]`.
+    /// Defaults to 0.
+    pub fn start_factor(mut self, start_factor: f64) -> Self {
+        self.start_factor = start_factor;
+        self
+    }
+
+    /// How the factor rises from `start_factor` to 1. Defaults to
+    /// [`Anneal::Linear`]. [`Anneal::Exponential`] needs a non-zero
+    /// `start_factor` and momentum endpoints, [`Anneal::Polynomial`] a
+    /// positive power, and [`Anneal::Constant`] holds `start_factor` for the
+    /// whole warmup.
+    pub fn anneal(mut self, anneal: Anneal) -> Self {
+        self.anneal = anneal;
+        self
+    }
+
+    /// Also warm momentum up from `start` to `end`, using the same strategy
+    /// as the lr. Without this, momentum is held at the inner schedule's
+    /// initial momentum during warmup.
+    pub fn momentum(mut self, start: f64, end: f64) -> Self {
+        self.momentum = Some((start, end));
+        self
+    }
+
+    pub fn build(self) -> Result<Warmup<S>, SchedulerError> {
+        check_steps("warmup_steps", self.warmup_steps)?;
+        check_range("start_factor", self.start_factor, 0., 1.)?;
+        if self.anneal == Anneal::Exponential {
+            check_positive("start_factor", self.start_factor)?;
+        }
+        check_anneal(0, &self.anneal, (self.start_factor, 1.))?;
+        if let Some((start, end)) = self.momentum {
+            check_range("warmup start momentum", start, 0., 1.)?;
+            check_range("warmup end momentum", end, 0., 1.)?;
+            check_anneal(0, &self.anneal, (start, end))?;
+        }
+
+        Ok(Warmup {
+            inner: self.inner,
+            warmup_steps: self.warmup_steps,
+            start_factor: self.start_factor,
+            anneal: self.anneal,
+            momentum: self.momentum,
+            step_num: 0,
+            unsupported: UnsupportedPolicy::default(),
+        })
+    }
+}
+
+/// Warms up into any scheduler over `warmup_steps`.
+///
+/// During warmup the lr is a factor of the inner schedule's initial lr,
+/// rising from the start factor to 1. Once warmup is over the inner scheduler
+/// takes over from its own step 0, so it never sees the warmup steps.
+///
+/// ```
+/// use candle_scheduler::{Anneal, CosineAnnealing, Warmup};
+/// # fn main() -> Result<(), candle_scheduler::SchedulerError> {
+/// let scheduler = Warmup::builder(CosineAnnealing::new(1e-3, 10_000, 1e-6), 500)
+///     .start_factor(0.01)
+///     .anneal(Anneal::Exponential)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Warmup<S> {
+    inner: S,
+    warmup_steps: usize,
+    start_factor: f64,
+    anneal: Anneal,
+    momentum: Option<(f64, f64)>,
+    step_num: usize,
+    unsupported: UnsupportedPolicy,
+}
+
+impl<S> Warmup<S> {
+    /// Linear warmup from 0 over `warmup_steps`.
+    ///
+    /// # Panics
+    ///
+    /// If `warmup_steps` is 0.
+    pub fn new(inner: S, warmup_steps: usize) -> Self {
+        Self::builder(inner, warmup_steps)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid Warmup: {err}"))
+    }
+
+    /// Start configuring a warmup into `inner`, which should not have been
+    /// stepped yet.
+    pub fn builder(inner: S, warmup_steps: usize) -> WarmupBuilder<S> {
+        WarmupBuilder {
+            inner,
+            warmup_steps,
+            start_factor: 0.,
+            anneal: Anneal::Linear,
+            momentum: None,
+        }
+    }
+
+    /// How to handle optimizers that can't set momentum during warmup.
+    /// Defaults to [`UnsupportedPolicy::Ignore`]. The inner scheduler keeps
+    /// its own policy.
+    pub fn with_unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    pub fn warmup_steps(&self) -> usize {
+        self.warmup_steps
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn warmup_pct(&self, step: usize) -> f64 {
+        step as f64 / self.warmup_steps as f64
+    }
+}
+
+impl<S: Schedule> Warmup<S> {
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+
+    pub fn get_momentum(&self) -> Option<f64> {
+        self.momentum_at(self.step_num)
+    }
+}
+
+impl<S: Schedule> Schedule for Warmup<S> {
+    fn lr_at(&self, step: usize) -> f64 {
+        if step >= self.warmup_steps {
+            return self.inner.lr_at(step - self.warmup_steps);
+        }
+
+        let factor = self
+            .anneal
+            .anneal(self.start_factor, 1., self.warmup_pct(step));
+        factor * self.inner.lr_at(0)
+    }
+
+    fn momentum_at(&self, step: usize) -> Option<f64> {
+        if step >= self.warmup_steps {
+            return self.inner.momentum_at(step - self.warmup_steps);
+        }
+
+        match self.momentum {
+            Some((start, end)) => Some(self.anneal.anneal(start, end, self.warmup_pct(step))),
+            None => self.inner.momentum_at(0),
+        }
+    }
+}
+
+impl<O: Hyperparams, S: LrScheduler<O> + Schedule> LrScheduler<O> for Warmup<S> {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        // The inner scheduler sets everything itself once it has steps of its
+        // own. On the last warmup step it's still at step 0.
+        if self.step_num > self.warmup_steps {
+            return self.inner.step(optimizer);
+        }
+
+        optimizer.set_learning_rate(self.get_lr());
+
+        match self.get_momentum() {
+            Some(momentum) => self.unsupported.apply(optimizer.set_momentum(momentum)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    fn reset(&mut self) {
+        self.step_num = 0;
+        self.inner.reset();
+    }
+}
+
+impl<S: ToConfig> ToConfig for Warmup<S> {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::Warmup {
+            warmup_steps: self.warmup_steps,
+            start_factor: non_default(self.start_factor, 0.),
+            anneal: non_default(self.anneal.clone(), Anneal::Linear),
+            momentum: self.momentum,
+            inner: Box::new(self.inner.to_config()),
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `warmup(500) >> cosine(10000)`.
+impl<S: ToConfig> fmt::Display for Warmup<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`Warmup`] state, wrapping the inner
+/// scheduler's config.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WarmupConfig<C> {
+    pub warmup_steps: usize,
+    pub start_factor: f64,
+    pub anneal: Anneal,
+    pub momentum: Option<(f64, f64)>,
+    pub inner: C,
+}
+
+#[cfg(feature = "serde")]
+impl<S: StateDict + Schedule> StateDict for Warmup<S> {
+    type Config = WarmupConfig<S::Config>;
+
+    fn state_dict(&self) -> SchedulerState<WarmupConfig<S::Config>> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: self.get_momentum(),
+            config: WarmupConfig {
+                warmup_steps: self.warmup_steps,
+                start_factor: self.start_factor,
+                anneal: self.anneal.clone(),
+                momentum: self.momentum,
+                inner: self.inner.state_dict().config,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<WarmupConfig<S::Config>>,
+    ) -> Result<(), SchedulerError> {
+        state.validate(&self.state_dict().config)?;
+
+        let inner_step = state.step_num.saturating_sub(self.warmup_steps);
+        self.inner.load_state_dict(SchedulerState {
+            step_num: inner_step,
+            lr: self.inner.lr_at(inner_step),
+            momentum: self.inner.momentum_at(inner_step),
+            config: state.config.inner,
+        })?;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{Anneal, CosineAnnealing, LrScheduler, OneCycle, Schedule, SchedulerError, Warmup};
+
+    #[test]
+    fn linear_warmup_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 4);
+
+        assert_eq!(scheduler.get_lr(), 0.);
+
+        for lr in [2.5e-4, 5e-4, 7.5e-4, 1e-3] {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        for step in 1..=3 {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), scheduler.inner().lr_at(step));
+            assert_eq!(
+                LrScheduler::<SGD>::step_num(scheduler.inner()),
+                step,
+                "inner scheduler starts counting after warmup"
+            );
+        }
+    }
+
+    #[test]
+    fn exponential_warmup_test() {
+        let scheduler = Warmup::builder(CosineAnnealing::new(1., 10, 0.), 2)
+            .start_factor(0.01)
+            .anneal(Anneal::Exponential)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(0), 0.01);
+        assert_eq!(scheduler.lr_at(1), 0.1);
+        assert_eq!(scheduler.lr_at(2), 1.);
+    }
+
+    #[test]
+    fn constant_warmup_test() {
+        let scheduler = Warmup::builder(CosineAnnealing::new(1., 10, 0.), 3)
+            .start_factor(0.1)
+            .anneal(Anneal::Constant)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.lr_at(0), 0.1);
+        assert_eq!(scheduler.lr_at(2), 0.1);
+        assert_eq!(scheduler.lr_at(3), 1.);
+    }
+
+    #[test]
+    fn momentum_warmup_test() {
+        let varmap = VarMap::new();
+        let mut opt = AdamW::new(varmap.all_vars(), ParamsAdamW::default()).unwrap();
+        let mut scheduler = Warmup::builder(OneCycle::new(1e-3, 0.95, 25., 10), 2)
+            .momentum(0.99, 0.95)
+            .build()
+            .unwrap();
+
+        assert_eq!(scheduler.get_momentum(), Some(0.99));
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, 0.97);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, 0.95);
+
+        scheduler.step(&mut opt).unwrap();
+
+        assert_eq!(opt.params().beta1, scheduler.inner().get_momentum());
+        assert_eq!(scheduler.get_momentum(), scheduler.inner().momentum_at(1));
+    }
+
+    #[test]
+    fn warmup_reset_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 2);
+
+        for _i in 0..5 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        LrScheduler::<SGD>::reset(&mut scheduler);
+
+        assert_eq!(LrScheduler::<SGD>::step_num(scheduler.inner()), 0);
+        assert_eq!(scheduler.get_lr(), 0.);
+    }
+
+    #[test]
+    fn warmup_invalid_test() {
+        let inner = || CosineAnnealing::new(1e-3, 10, 0.);
+
+        assert_eq!(
+            Warmup::builder(inner(), 0).build().unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "warmup_steps"
+            }
+        );
+        assert!(matches!(
+            Warmup::builder(inner(), 10).start_factor(1.5).build(),
+            Err(SchedulerError::OutOfRange {
+                name: "start_factor",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Warmup::builder(inner(), 10)
+                .anneal(Anneal::Exponential)
+                .build(),
+            Err(SchedulerError::NonPositive {
+                name: "start_factor",
+                ..
+            })
+        ));
+        assert_eq!(
+            Warmup::builder(inner(), 10)
+                .anneal(Anneal::Polynomial { power: -1. })
+                .build()
+                .unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "polynomial power",
+                value: -1.
+            }
+        );
+        assert_eq!(
+            Warmup::builder(inner(), 10)
+                .start_factor(0.1)
+                .anneal(Anneal::Exponential)
+                .momentum(0., 0.9)
+                .build()
+                .unwrap_err(),
+            SchedulerError::ExponentialEndpoints {
+                index: 0,
+                start: 0.,
+                end: 0.9
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn warmup_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 3);
+
+        for _i in 0..5 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let mut resumed = Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 3);
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(LrScheduler::<SGD>::step_num(resumed.inner()), 2);
+
+        scheduler.step(&mut opt).unwrap();
+        let lr = opt.learning_rate();
+        resumed.step(&mut opt).unwrap();
+
+        assert_eq!(opt.learning_rate(), lr);
+
+        let mismatched = Warmup::new(CosineAnnealing::new(1e-3, 10, 0.), 4);
+        assert!(matches!(
+            resumed.load_state_dict(mismatched.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+
+use candle_nn::Optimizer;
+
+use crate::config::non_default;
+use crate::error::{check_finite, check_order, check_steps};
+use crate::phase::check_anneal;
+use crate::{Anneal, LrScheduler, Schedule, SchedulerConfig, SchedulerError, ToConfig};
+#[cfg(feature = "serde")]
+use crate::{SchedulerState, StateDict};
+
+/// Configures a [`Wsd`].
+#[derive(Debug, Clone)]
+pub struct WsdBuilder {
+    lr: f64,
+    min_lr: f64,
+    warmup_steps: usize,
+    decay: Anneal,
+    cooldown: Option<(usize, usize)>,
+}
+
+impl WsdBuilder {
+    /// Steps to ramp the lr up linearly from 0. Defaults to 0.
+    pub fn warmup_steps(mut self, warmup_steps: usize) -> Self {
+        self.warmup_steps = warmup_steps;
+        self
+    }
+
+    /// The lr at the end of the cooldown. Defaults to 0.
+    pub fn min_lr(mut self, min_lr: f64) -> Self {
+        self.min_lr = min_lr;
+        self
+    }
+
+    /// The shape of the cooldown. Defaults to [`Anneal::Linear`];
+    /// [`Anneal::Cos`] and [`Anneal::OneMinusSqrt`] are the other usual
+    /// choices. [`Anneal::Exponential`] needs `lr` and `min_lr` non-zero with
+    /// the same sign, and [`Anneal::Polynomial`] a positive power.
+    pub fn decay(mut self, decay: Anneal) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Cool down over `steps` starting at step `start`, for when the length
+    /// of the run is known up front. See [`Wsd::begin_cooldown`] otherwise.
+    pub fn cooldown(mut self, start: usize, steps: usize) -> Self {
+        self.cooldown = Some((start, steps));
+        self
+    }
+
+    /// Requires finite lrs with `min_lr <= lr`, a decay that can anneal
+    /// between them, and a positive cooldown that starts after warmup.
+    pub fn build(&self) -> Result<Wsd, SchedulerError> {
+        check_finite("lr", self.lr)?;
+        check_finite("min_lr", self.min_lr)?;
+        check_order(("min_lr", self.min_lr), ("lr", self.lr))?;
+        check_anneal(0, &self.decay, (self.lr, self.min_lr))?;
+        if let Some((start, steps)) = self.cooldown {
+            check_steps("cooldown_steps", steps)?;
+            check_order(
+                ("warmup_steps", self.warmup_steps as f64),
+                ("cooldown_start", start as f64),
+            )?;
+        }
+
+        Ok(Wsd {
+            lr: self.lr,
+            min_lr: self.min_lr,
+            warmup_steps: self.warmup_steps,
+            decay: self.decay.clone(),
+            cooldown: self.cooldown,
+            step_num: 0,
+        })
+    }
+}
+
+/// Warmup-stable-decay: a linear warmup, a constant lr for as long as
+/// training runs, then a cooldown to `min_lr`.
+///
+/// The cooldown doesn't need to be planned ahead. Call
+/// [`Wsd::begin_cooldown`] whenever training should wrap up, or branch
+/// several cooldowns off one stable-phase checkpoint.
+///
+/// ```
+/// use candle_nn::{Optimizer, VarMap, SGD};
+/// use candle_scheduler::{Anneal, LrScheduler, Wsd};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let varmap = VarMap::new();
+/// # let mut opt = SGD::new(varmap.all_vars(), 0.)?;
+/// let mut scheduler = Wsd::builder(1e-3)
+///     .warmup_steps(100)
+///     .decay(Anneal::OneMinusSqrt)
+///     .build()?;
+///
+/// for _step in 0..1_000 {
+///     scheduler.step(&mut opt)?;
+/// }
+///
+/// scheduler.begin_cooldown(200)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Wsd {
+    lr: f64,
+    min_lr: f64,
+    warmup_steps: usize,
+    decay: Anneal,
+    cooldown: Option<(usize, usize)>,
+    step_num: usize,
+}
+
+impl Wsd {
+    /// A linear warmup over `warmup_steps` to `lr`, decaying linearly to 0
+    /// once a cooldown begins.
+    ///
+    /// # Panics
+    ///
+    /// If `lr` is negative or isn't finite.
+    pub fn new(lr: f64, warmup_steps: usize) -> Self {
+        Self::builder(lr)
+            .warmup_steps(warmup_steps)
+            .build()
+            .unwrap_or_else(|err| panic!("invalid Wsd: {err}"))
+    }
+
+    /// Start configuring a schedule peaking at `lr`.
+    pub fn builder(lr: f64) -> WsdBuilder {
+        WsdBuilder {
+            lr,
+            min_lr: 0.,
+            warmup_steps: 0,
+            decay: Anneal::Linear,
+            cooldown: None,
+        }
+    }
+
+    /// Decay to `min_lr` over the next `steps`, starting now, or once warmup
+    /// is over if it hasn't finished yet.
+    ///
+    /// Calling it again replaces the current cooldown, restarting the decay
+    /// from the peak lr.
+    pub fn begin_cooldown(&mut self, steps: usize) -> Result<(), SchedulerError> {
+        check_steps("cooldown_steps", steps)?;
+
+        self.cooldown = Some((self.step_num.max(self.warmup_steps), steps));
+        Ok(())
+    }
+
+    /// The step the cooldown starts at and its length, if one has begun.
+    pub fn cooldown(&self) -> Option<(usize, usize)> {
+        self.cooldown
+    }
+
+    /// Whether the cooldown has run its course.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.cooldown, Some((start, steps)) if self.step_num >= start + steps)
+    }
+
+    pub fn get_lr(&self) -> f64 {
+        self.lr_at(self.step_num)
+    }
+}
+
+impl Schedule for Wsd {
+    fn lr_at(&self, step: usize) -> f64 {
+        if step < self.warmup_steps {
+            return self.lr * step as f64 / self.warmup_steps as f64;
+        }
+
+        match self.cooldown {
+            Some((start, steps)) if step >= start => {
+                let pct = ((step - start) as f64 / steps as f64).min(1.);
+                self.decay.anneal(self.lr, self.min_lr, pct)
+            }
+            _ => self.lr,
+        }
+    }
+}
+
+impl<O: Optimizer> LrScheduler<O> for Wsd {
+    fn step(&mut self, optimizer: &mut O) -> Result<(), SchedulerError> {
+        self.step_num += 1;
+
+        optimizer.set_learning_rate(self.get_lr());
+        Ok(())
+    }
+
+    fn get_lr(&self) -> f64 {
+        self.get_lr()
+    }
+
+    fn step_num(&self) -> usize {
+        self.step_num
+    }
+
+    /// Rewinds to the first step. A cooldown begun at runtime is kept, so a
+    /// rerun follows the same schedule.
+    fn reset(&mut self) {
+        self.step_num = 0;
+    }
+}
+
+impl ToConfig for Wsd {
+    fn to_config(&self) -> SchedulerConfig {
+        SchedulerConfig::Wsd {
+            lr: self.lr,
+            min_lr: non_default(self.min_lr, 0.),
+            warmup_steps: non_default(self.warmup_steps, 0),
+            decay: non_default(self.decay.clone(), Anneal::Linear),
+            cooldown: self.cooldown,
+        }
+    }
+}
+
+/// Formats as a schedule expression, e.g. `wsd(1000, lr=0.001)`, including a
+/// cooldown once it has begun.
+impl fmt::Display for Wsd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_config().fmt(f)
+    }
+}
+
+/// The configuration saved with a [`Wsd`] state.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WsdConfig {
+    pub lr: f64,
+    pub min_lr: f64,
+    pub warmup_steps: usize,
+    pub decay: Anneal,
+}
+
+/// What's saved with a [`Wsd`] state. The cooldown can begin at runtime, so
+/// it's saved along with the config. Only the config has to match on load.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WsdCheckpoint {
+    pub config: WsdConfig,
+    pub cooldown: Option<(usize, usize)>,
+}
+
+#[cfg(feature = "serde")]
+impl StateDict for Wsd {
+    type Config = WsdCheckpoint;
+
+    fn state_dict(&self) -> SchedulerState<WsdCheckpoint> {
+        SchedulerState {
+            step_num: self.step_num,
+            lr: self.get_lr(),
+            momentum: None,
+            config: WsdCheckpoint {
+                config: WsdConfig {
+                    lr: self.lr,
+                    min_lr: self.min_lr,
+                    warmup_steps: self.warmup_steps,
+                    decay: self.decay.clone(),
+                },
+                cooldown: self.cooldown,
+            },
+        }
+    }
+
+    fn load_state_dict(
+        &mut self,
+        state: SchedulerState<WsdCheckpoint>,
+    ) -> Result<(), SchedulerError> {
+        let config = self.state_dict().config.config;
+        if state.config.config != config {
+            return Err(SchedulerError::StateMismatch {
+                expected: format!("{config:?}"),
+                found: format!("{:?}", state.config.config),
+            });
+        }
+
+        self.cooldown = state.config.cooldown;
+        self.step_num = state.step_num;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_nn::{Optimizer, VarMap, SGD};
+
+    #[cfg(feature = "serde")]
+    use crate::StateDict;
+    use crate::{Anneal, LrScheduler, Schedule, SchedulerError, Wsd};
+
+    #[test]
+    fn wsd_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Wsd::builder(1.)
+            .warmup_steps(4)
+            .min_lr(0.1)
+            .build()
+            .unwrap();
+
+        for lr in [0.25, 0.5, 0.75, 1., 1., 1.] {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        assert_eq!(scheduler.lr_at(1_000_000), 1.);
+
+        scheduler.begin_cooldown(4).unwrap();
+
+        assert_eq!(scheduler.cooldown(), Some((6, 4)));
+
+        for lr in [0.775, 0.55, 0.32499999999999996, 0.09999999999999998] {
+            scheduler.step(&mut opt).unwrap();
+
+            assert_eq!(opt.learning_rate(), lr);
+        }
+
+        assert!(scheduler.is_finished());
+        assert_eq!(scheduler.lr_at(100), 0.09999999999999998);
+    }
+
+    #[test]
+    fn wsd_decay_shapes_test() {
+        let cos = Wsd::builder(1.)
+            .warmup_steps(4)
+            .min_lr(0.1)
+            .decay(Anneal::Cos)
+            .cooldown(6, 4)
+            .build()
+            .unwrap();
+        let sqrt = Wsd::builder(1.)
+            .warmup_steps(4)
+            .min_lr(0.1)
+            .decay(Anneal::OneMinusSqrt)
+            .cooldown(6, 4)
+            .build()
+            .unwrap();
+
+        let lrs: Vec<f64> = (6..11).map(|step| cos.lr_at(step)).collect();
+        assert_eq!(
+            lrs,
+            [1., 0.8681980515339464, 0.55, 0.23180194846605365, 0.1]
+        );
+
+        let lrs: Vec<f64> = (6..11).map(|step| sqrt.lr_at(step)).collect();
+        assert_eq!(
+            lrs,
+            [1., 0.55, 0.36360389693210715, 0.22057713659400527, 0.1]
+        );
+    }
+
+    #[test]
+    fn wsd_cooldown_during_warmup_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Wsd::new(1., 4);
+
+        scheduler.step(&mut opt).unwrap();
+        scheduler.begin_cooldown(2).unwrap();
+
+        assert_eq!(scheduler.cooldown(), Some((4, 2)));
+        assert_eq!(scheduler.lr_at(3), 0.75);
+        assert_eq!(scheduler.lr_at(5), 0.5);
+    }
+
+    #[test]
+    fn wsd_invalid_test() {
+        assert_eq!(
+            Wsd::builder(1e-3).min_lr(1e-2).build().unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "min_lr",
+                upper: "lr"
+            }
+        );
+        assert_eq!(
+            Wsd::builder(1e-3)
+                .warmup_steps(100)
+                .cooldown(50, 10)
+                .build()
+                .unwrap_err(),
+            SchedulerError::Unordered {
+                lower: "warmup_steps",
+                upper: "cooldown_start"
+            }
+        );
+        assert_eq!(
+            Wsd::builder(1e-3)
+                .decay(Anneal::Polynomial { power: 0. })
+                .build()
+                .unwrap_err(),
+            SchedulerError::NonPositive {
+                name: "polynomial power",
+                value: 0.
+            }
+        );
+        assert_eq!(
+            Wsd::builder(1e-3)
+                .decay(Anneal::Exponential)
+                .build()
+                .unwrap_err(),
+            SchedulerError::ExponentialEndpoints {
+                index: 0,
+                start: 1e-3,
+                end: 0.
+            }
+        );
+        assert!(Wsd::builder(1e-3)
+            .min_lr(1e-5)
+            .decay(Anneal::Exponential)
+            .build()
+            .is_ok());
+        assert_eq!(
+            Wsd::new(1e-3, 0).begin_cooldown(0).unwrap_err(),
+            SchedulerError::ZeroSteps {
+                name: "cooldown_steps"
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn wsd_resume_test() {
+        let varmap = VarMap::new();
+        let mut opt = SGD::new(varmap.all_vars(), 0.).unwrap();
+        let mut scheduler = Wsd::new(1., 4);
+
+        for _i in 0..6 {
+            scheduler.step(&mut opt).unwrap();
+        }
+
+        let stable = scheduler.state_dict();
+        scheduler.begin_cooldown(4).unwrap();
+        scheduler.step(&mut opt).unwrap();
+
+        let mut resumed = Wsd::new(1., 4);
+        resumed.load_state_dict(scheduler.state_dict()).unwrap();
+
+        assert_eq!(resumed.cooldown(), Some((6, 4)));
+        assert_eq!(resumed.get_lr(), 0.75);
+
+        // Branch a longer cooldown off the stable checkpoint.
+        let mut branch = Wsd::new(1., 4);
+        branch.load_state_dict(stable).unwrap();
+        branch.begin_cooldown(8).unwrap();
+
+        assert_eq!(branch.lr_at(10), 0.5);
+
+        let mut other = Wsd::new(2., 4);
+        assert!(matches!(
+            other.load_state_dict(scheduler.state_dict()),
+            Err(SchedulerError::StateMismatch { .. })
+        ));
+    }
+}